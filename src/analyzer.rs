@@ -1,20 +1,57 @@
 use std::{
-    thread::{self, JoinHandle} ,
+    thread,
     collections::HashMap, 
     time::{Duration, SystemTime}};
 
 use colored::*;
 use configparser::ini::Ini;
+use rayon::prelude::*;
 
 // use binance::errors::ErrorKind as BinanceLibErrorKind;
-use binance::{api::*, model::{Prices, SymbolPrice}};
+use binance::api::*;
 use binance::account::*;
 use binance::market::*;
-use binance::config::*;
+use binance::userstream::UserStream;
 
-use crate::exchangeinfo::QuantityInfo;
+use crate::credentials;
+use crate::endpoint::Endpoint;
+use crate::error::{Action, Context, Error, Result};
+use crate::retry::{self, RetryPolicy};
+use crate::exchangeinfo::{self, QuantityInfo, SymbolInfo};
+use crate::quarantine::{self, Evidence, Quarantine};
+use crate::userdata;
 
 mod executor;
+mod fees;
+mod journal;
+mod latency;
+mod lifetime;
+mod orders;
+mod peg;
+mod pricing;
+mod rebalancer;
+mod reconcile;
+mod scanner;
+mod scoring;
+mod snapshot;
+mod strategy;
+mod tracker;
+mod triangle;
+
+use latency::LatencyModel;
+use orders::RingTag;
+use reconcile::Reconciler;
+use scoring::FillModel;
+use triangle::Triangle;
+pub use executor::Fill;
+pub use fees::FeeModel;
+pub use orders::{OrderKind, OrderPolicy};
+pub use pricing::{LegPrice, PricingPolicy, Quote};
+pub use scanner::{ExecutionPlan, PlannedOrder, Scanner};
+pub use snapshot::{Ring, Snapshot, SymbolId};
+#[cfg(test)]
+pub(crate) use snapshot::fixture;
+pub use strategy::{AccountView, Expected, Intent, MarketView, OrderIntent, Report, RingIntent, Strategy};
 // TODO:
 // 1. Compute RISK involve each pair by ASK/BID ratio.
 // Lower GAP = Higher fillable possibility.
@@ -22,8 +59,9 @@ mod executor;
 // 0.4 ~ 0.7% may take very long time to fill. 
 //
 // Answer: 
-// just tuning SYM_B from -2.0 ask-> -2.0 bid 
+// just tuning leg B from ask-2 -> bid+2 ( see [pricing] )
 // will open up to a whole new range of stuffs.
+// Rings are now ranked by expected value from FillModel ( see scoring.rs ).
 //
 //
 const IS_DEBUG:bool = false;
@@ -34,23 +72,36 @@ const MAX_INVEST:f64 =100.0;// etc: BUSD = 368.18;
 const PROFIT_WARNING:f64 = 9.0;// percent
 const PROFIT_MINIMUM:f64 = 0.5;// percent
 
-const SYMBOL_CACHE_FILE:&str = "symbols.cache";
+const DEFAULT_STRATEGIES:&str = "triangle"; // [strategies] enabled
+const SYMBOL_CACHE_FILE:&str = "rings.cache"; // legs with base, quote & side
 const DELAY_INIT: Duration = Duration::from_millis(2000); // each block last 1 secs
 
-// how aggressive we create new orderbooks, 
-// best = { 2.0 bid -2.0 ask -100.0 ask 2 safe } where profit around 0.6% ~ 0.3%
-// risk = { 2.0 ask 2.0 bid 0.0 ask 2 safe } where profit could be > 5%
-const SYM_A_STEP:f64 = 1.0;     // Buy stable-symbol <--- loss for speed,              higher is new orderbook
-const SYM_B_STEP:f64 = 1.0;    // Sell symbol-bridge <--- MAIN profit here,            lower is new orderbook
-const SYM_C_STEP:f64 = 1.0;  // Sell bridge-stable <--- minor profit by BTC delay,   lower is new orderbook
-const SAFE_LIFETIME:i32 = 0;    // ensure a trade last for 3 blocks. 
+// how aggressive we create new orderbooks is now [pricing] preset, see pricing.rs.
+// Buy stable-symbol  <--- loss for speed,              higher is new orderbook
+// Sell symbol-bridge <--- MAIN profit here,            lower is new orderbook
+// Sell bridge-stable <--- minor profit by BTC delay,   lower is new orderbook
+const SAFE_LIFETIME:u32 = 1;    // default [lifetime] min_blocks, ring must stay profitable for more blocks than this.
 
 pub struct RingResult {
-    symbol :String,
-    percentage :f64, 
-    profit :f64, 
-    qty :f64, 
-    optimal_invest :f64
+    pub ring :usize, // index into interned rings
+    pub percentage :f64, 
+    pub profit :f64, 
+    pub probability :f64,    // all 3 legs filled at policy prices
+    pub expected_value :f64, // ranking key
+    pub is_anomaly :bool,    // profit > warning ratio, goes to quarantine
+    pub fees :f64,           // in stablecoin, see fees.rs
+    pub fees_in_bnb :f64,    // part of fees paid in BNB
+    pub qty :f64, 
+    pub optimal_invest :f64
+}
+
+/// What prices a ring : policy prices of its legs, fill odds, fees and drift while earlier legs fill.
+#[derive(Clone, Copy)]
+pub struct RingModels<'a> {
+    pub policy: &'a PricingPolicy,
+    pub fill: &'a FillModel,
+    pub fees: &'a FeeModel,
+    pub latency: &'a LatencyModel
 }
 
 pub struct RingComponent {
@@ -59,22 +110,51 @@ pub struct RingComponent {
     stablecoin: String
 }
 
-// 'a is to fix the damn "named lifetime parameter" to indicate same data flow
-pub fn symbol_discovery<'a>(config: &Ini, market: &Market) -> HashMap<String, Vec<String>>{
+/// Order side of a ring leg.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+    Buy,
+    Sell
+}
+
+/// One pair of a ring, with what we trade on it.
+#[derive(Clone, Debug)]
+pub struct RingLeg {
+    pub symbol: String, // XTZBUSD
+    pub base: String,   // XTZ
+    pub quote: String,  // BUSD
+    pub side: Side      // BUY : quote -> base, SELL : base -> quote
+}
+
+impl RingLeg {
+    /// cache format : "XTZBUSD XTZ BUSD BUY"
+    fn to_cache(&self) -> String {
+        let side = match self.side { Side::Buy => "BUY", Side::Sell => "SELL" };
+        return format!("{} {} {} {}", self.symbol, self.base, self.quote, side);
+    }
+
+    fn from_cache(text: &str) -> Option<RingLeg> {
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        if fields.len() != 4 { return None; }
+        let side = match fields[3] { "BUY" => Side::Buy, "SELL" => Side::Sell, _ => return None };
+        return Some(RingLeg { symbol: fields[0].to_uppercase(), base: fields[1].to_uppercase(), quote: fields[2].to_uppercase(), side });
+    }
+}
+
+/// Build rings from exchangeInfo base/quote assets :
+/// buy SYMBOL/stablecoin > sell SYMBOL/bridge > sell bridge/stablecoin.
+pub fn symbol_discovery(config: &Ini) -> Result<HashMap<String, Vec<RingLeg>>> {
     //
     // LOAD CONFIGS
     //
-    let _key_bridge = &config.get("symbols","bridges").unwrap();
-    let _key_ignored = &config.get("symbols","ignored").unwrap();
-    let bridge_symbols:Vec<&str> = _key_bridge.split(',').collect();
-    let ignored_symbols:Vec<&str> = _key_ignored.split(',').collect();
+    let (stablecoin, bridge) = bridges(config)?;
+    let (stablecoin, bridge) = (stablecoin.as_str(), bridge.as_str());
+    let _key_ignored = &config.get("symbols","ignored").unwrap_or_default();
+    let ignored_symbols:Vec<&str> = _key_ignored.split(',').map(|s| s.trim()).collect();
     //
     // INIT CACHES
     //
-    let mut data_cache:Vec<SymbolPrice> = vec![];
-    let mut symbols_stablecoin:Vec<&str> = vec![];
-    let mut symbols_with_bridge:Vec<&str> = vec![]; 
-    let mut symbols_rings: HashMap<String, Vec<String>> = HashMap::new();
+    let mut symbols_rings: HashMap<String, Vec<RingLeg>> = HashMap::new();
     //
     // FIND & LOAD CACHED FILE
     //
@@ -82,13 +162,14 @@ pub fn symbol_discovery<'a>(config: &Ini, market: &Market) -> HashMap<String, Ve
     match cache_file.load(SYMBOL_CACHE_FILE) {
         Ok(_) => { 
             println!("> found prev cache");
-            for sym_map in cache_file.get_map() {
-                for discovered in sym_map {
-                    println!("> loading {} rings...", discovered.1.len());
-                    for symbol_ring in discovered.1 {
-                        let ring:Vec<String> = 
-                        symbol_ring.1.unwrap().split(",").map(|s| s.to_string()).collect();
-                        symbols_rings.insert( symbol_ring.0.to_uppercase(), ring);
+            for discovered in cache_file.get_map().unwrap_or_default().into_values() {
+                println!("> loading {} rings...", discovered.len());
+                for (symbol, ring) in discovered {
+                    let ring: Option<Vec<RingLeg>> = ring.unwrap_or_default()
+                        .split(',').map(RingLeg::from_cache).collect();
+                    match ring {
+                        Some(ring) if ring.len() == 3 => { symbols_rings.insert(symbol.to_uppercase(), ring); },
+                        _ => println!("> skip broken cache entry {}", symbol)
                     }
                 }
             }
@@ -96,71 +177,21 @@ pub fn symbol_discovery<'a>(config: &Ini, market: &Market) -> HashMap<String, Ve
         _error => {
             println!("> can't find symbol cache >> building one now ...");
             //
-            // Fetching all symbols from Binance.
+            // Fetching all symbols from exchangeInfo.
             //
-            match market.get_all_prices() {
-                Ok(answer) => {
-
-                    match answer {
-                        // need to match the exact enum
-                        Prices::AllPrices(data) => {
-                            //
-                            // ground caching for other hashes 
-                            //
-                            data_cache = data.clone();
-                            
-                            for symbol_price in &data_cache {
-                                let key = symbol_price.symbol.as_str();
-                                let mut bridge_iter = bridge_symbols.iter();
-                                //
-                                // Find only stablecoin/bridge pairs :
-                                //
-                                if bridge_iter.any(|b| key.contains(b)) && !ignored_symbols.contains(&key){
-                                    // Stablecoin :
-                                    if key.ends_with(bridge_symbols[0]) {
-                                        symbols_stablecoin.push(&key); 
-                                    }
-                                    // Bridge :
-                                    else if key.ends_with(bridge_symbols[1]){
-                                        symbols_with_bridge.push(&key);
-                                    } 
-                                } 
-                            }
-                        }
-                    }
-                },
-                Err(e) => println!("Error with data_cache = {:2}\n", e),
-            }
-            println!("- Total bridge-pairs : {}", symbols_with_bridge.len());
-            println!("- Total stablecoin-pairs : {}", symbols_stablecoin.len());
-
-            for sym in symbols_stablecoin {
-                // 1 - stablecoin
-                // 2 - bridge
-                let name = String::from(sym.strip_suffix(bridge_symbols[0]).unwrap());
-                let bridge = [name.clone(), bridge_symbols[1].to_string()].join("");
-                if symbols_with_bridge.contains(&bridge.as_str()) {
-                    //
-                    // Note:
-                    // Remember to clone/new String to copy/concat around.
-                    //
-                    symbols_rings.insert(String::from(name), vec![
-                        String::from(sym),                              // sym => XTZ-BUSD,
-                        bridge.clone(),                                 // bridge => XTZ-BNB,
-                        [bridge_symbols[1], bridge_symbols[0]].join("") // bridge-stablecoin => BNB-BUSD
-                    ]);
-                }
-            }
+            let symbols = exchangeinfo::fetch_symbols(&Endpoint::load(config)?.rest).context("symbol discovery")?;
+            symbols_rings = discover_rings(&symbols, stablecoin, bridge, &ignored_symbols);
             println!("- Total symbol rings is {}", symbols_rings.len());
             //
             // Save all discovered symbols 
             //
             let mut cache_file = Ini::new();
             for sym in &symbols_rings {
+                let legs: Vec<String> = sym.1.iter().map(|leg| leg.to_cache()).collect();
                 cache_file.set(
                     "discovered", 
                     sym.0.as_str(), 
-                    Option::from(sym.1.join(",")));
+                    Option::from(legs.join(",")));
             }
             match cache_file.write(SYMBOL_CACHE_FILE) {
                 Ok(_) => println!("> built symbols cache."),
@@ -168,21 +199,66 @@ pub fn symbol_discovery<'a>(config: &Ini, market: &Market) -> HashMap<String, Ve
             }
         }
     }
+    // Quarantined rings are kept, analysis skips them until cooldown ends.
     // Done !
     println!("> built rings map.");
+    return Ok(symbols_rings);
+}
+
+/// Rings of every asset quoted in both `stablecoin` & `bridge`, closed by bridge/stablecoin,
+/// from pairs that are TRADING, spot enabled and not `ignored`.
+pub fn discover_rings(symbols: &HashMap<String, SymbolInfo>, stablecoin: &str, bridge: &str, ignored: &[&str]) -> HashMap<String, Vec<RingLeg>> {
+    let mut symbols_rings: HashMap<String, Vec<RingLeg>> = HashMap::new();
+    //
+    // keyed by (base, quote).
+    //
+    let mut pairs: HashMap<(String, String), String> = HashMap::new();
+    for info in symbols.values() {
+        if info.status == "TRADING" && info.is_spot_trading_allowed && !ignored.contains(&info.symbol.as_str()) {
+            pairs.insert((info.base_asset.clone(), info.quote_asset.clone()), info.symbol.clone());
+        }
+    }
+    let leg = |base: &str, quote: &str, side: Side| -> Option<RingLeg> {
+        pairs.get(&(base.to_string(), quote.to_string()))
+            .map(|symbol| RingLeg { symbol: symbol.clone(), base: base.to_string(), quote: quote.to_string(), side })
+    };
+    let count_quoted = |quote: &str| pairs.keys().filter(|(_, q)| q == quote).count();
+    println!("- Total bridge-pairs : {}", count_quoted(bridge));
+    println!("- Total stablecoin-pairs : {}", count_quoted(stablecoin));
+
+    match leg(bridge, stablecoin, Side::Sell) {
+        Some(bridge_stable) => {
+            for (base, quote) in pairs.keys() {
+                if quote != stablecoin || base == bridge { continue; }
+                // 1 - stablecoin
+                // 2 - bridge
+                if let (Some(buy), Some(sell)) = (leg(base, stablecoin, Side::Buy), leg(base, bridge, Side::Sell)) {
+                    symbols_rings.insert(base.clone(), vec![
+                        buy,                    // sym => XTZ-BUSD,
+                        sell,                   // bridge => XTZ-BNB,
+                        bridge_stable.clone()   // bridge-stablecoin => BNB-BUSD
+                    ]);
+                }
+            }
+        },
+        None => println!("Error: no {}/{} pair to close rings.", bridge, stablecoin)
+    }
     return symbols_rings;
 }
+
+/// `[symbols] bridges` : stablecoin, bridge.
+fn bridges(config: &Ini) -> Result<(String, String)> {
+    let bridges: Vec<String> = config.get("symbols", "bridges").unwrap_or_default()
+        .split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect();
+    if bridges.len() < 2 { return Err(Error::config("symbols", "bridges", "needs stablecoin & bridge, etc: BUSD,BNB")); }
+    return Ok((bridges[0].clone(), bridges[1].clone()));
+}
 /// This update tickers into ASK+BID table.
-fn update_orderbooks(
-    market: &Market, symbol_caches: &Vec<String>,
-    tickers_a: &mut HashMap<String, [f64;2]>, 
-    tickers_b: &mut HashMap<String, [f64;2]>,
-    tickers_c: &mut HashMap<String, [f64;2]>,
-    quantity_info: &HashMap<String, QuantityInfo>
- ) -> bool {
+fn update_orderbooks(market: &Market, snapshot: &mut Snapshot) -> bool {
     //
     // update orderbooks 
     //
+    snapshot.reset();
     match market.get_all_book_tickers() {
         Ok(answer) => {
             match &answer {
@@ -192,21 +268,7 @@ fn update_orderbooks(
                             // took 600ms ~ 290ms to fetch all tickers
                             for ticker in tickers {
                                 // add only ring symbols
-                                if symbol_caches.contains(&ticker.symbol) {
-                                    let step_price = quantity_info[&ticker.symbol].step_price;
-                                    let new_a_price_bid = correct_price_filter(&ticker.symbol, quantity_info, ticker.bid_price + SYM_A_STEP * step_price);
-                                    let new_b_price_bid = correct_price_filter(&ticker.symbol, quantity_info, ticker.bid_price + SYM_B_STEP * step_price);
-                                    let new_c_price_bid = correct_price_filter(&ticker.symbol, quantity_info, ticker.bid_price + SYM_C_STEP * step_price);
-                                    let new_a_price_ask = correct_price_filter(&ticker.symbol, quantity_info, ticker.ask_price - SYM_A_STEP * step_price);
-                                    let new_b_price_ask = correct_price_filter(&ticker.symbol, quantity_info, ticker.ask_price - SYM_B_STEP * step_price);
-                                    let new_c_price_ask = correct_price_filter(&ticker.symbol, quantity_info, ticker.ask_price - SYM_C_STEP * step_price);
-                                    // tickers_a.entry(ticker.symbol.clone()).or_insert([new_a_price, ticker.bid_qty]);
-                                    // tickers_b.entry(ticker.symbol.clone()).or_insert([new_b_price, ticker.ask_qty]);
-                                    // tickers_c.entry(ticker.symbol.clone()).or_insert([new_c_price, ticker.ask_qty]);
-                                    tickers_a.entry(ticker.symbol.clone()).or_insert([new_a_price_bid, new_a_price_ask]);
-                                    tickers_b.entry(ticker.symbol.clone()).or_insert([new_b_price_bid, new_b_price_ask]);
-                                    tickers_c.entry(ticker.symbol.clone()).or_insert([new_c_price_bid, new_c_price_ask]);
-                                }
+                                snapshot.set_book(&ticker.symbol, [ticker.bid_price, ticker.ask_price], [ticker.bid_qty, ticker.ask_qty]);
                             }
                         }
                     }
@@ -214,289 +276,420 @@ fn update_orderbooks(
             };
             return true;
         },
-        Err(e) => println!("Error: {:?}\n\n> will break the loop now.\n> RailGun out.", &e.0)
+        Err(e) => println!("Error: {:?}\n> skip this block, retry after {} ms.", &e.0, DELAY_INIT.as_millis())
     };
     return false;
 }
 
-fn compute_rings(rings: &HashMap<String, Vec<String>>, balance: f64,
-    tickers_a: &HashMap<String, [f64;2]>, 
-    tickers_b: &HashMap<String, [f64;2]>, 
-    tickers_c: &HashMap<String, [f64;2]>,
-    quantity_info: &HashMap<String, QuantityInfo>) -> Vec<RingResult>{
-    //
-    // THREADPOOL
-    //
-    let mut compute_pool:Vec<JoinHandle<Option<RingResult>>> = vec![];
-
-    // let test_rings = ["TORN"];//  QUICK, STRAX, RGT, CVX, RAD, ORN, CHESS
-    // only check testing rings:
-    // for test in test_rings {
-    for ring in rings {
-        // copying data :
-        // let _ring = rings[test].clone(); // need to swap with 2 other lines below for parallel trade.
-        // let symbol = String::from(test); // need to swap with 2 other lines below for parallel trade.
-        let symbol = ring.0.clone(); // coin name
-        let _ring = ring.1.clone();  // ring of pairs
-        let _tickers_a = tickers_a.clone();
-        let _tickers_b = tickers_b.clone();
-        let _tickers_c = tickers_c.clone();
-        let _quantity_info = quantity_info.clone();
-        let _balance = balance.clone();
-        // spawn computation          
-        let thread = thread::spawn(move || { analyze_ring(symbol, _ring, _balance, _tickers_a, _tickers_b, _tickers_c, _quantity_info) });
-        compute_pool.push(thread);
-    }
-    let mut round_result = vec![];
-    for computer in compute_pool {
-        match computer.join().unwrap() {
-            Some(result) => round_result.push(result),
-            None => {}
-        }
-    }
+/// Evaluate all rings against one shared snapshot on the rayon worker pool.
+fn compute_rings(rings: &[Ring], balance: f64, snapshot: &Snapshot, models: RingModels,
+    is_skipped: &(dyn Fn(&str) -> bool + Sync)) -> Vec<RingResult>{
+    let round_result: Vec<RingResult> = rings.par_iter()
+        .enumerate()
+        .filter(|(_, ring)| snapshot.is_complete(ring) && !is_skipped(&ring.symbol))
+        .filter_map(|(index, ring)| analyze_ring(index, ring, balance, snapshot, models))
+        .collect();
     if IS_DEBUG { println!("> result: {} profitable rings", round_result.len()); }
     return round_result;
 }
 
 /// Compute profit on each ring 
-pub fn analyze_ring( symbol: String, _ring: Vec<String>, min_invest: f64,
-    tickers_a: HashMap<String, [f64;2]>, 
-    tickers_b: HashMap<String, [f64;2]>, 
-    tickers_c: HashMap<String, [f64;2]>,
-    quantity_info: HashMap<String, QuantityInfo>) -> Option<RingResult> {
+pub fn analyze_ring(index: usize, ring: &Ring, min_invest: f64, snapshot: &Snapshot, models: RingModels) -> Option<RingResult> {
+    let RingModels { policy, fill: model, fees: fee_model, latency } = models;
     // NOTE:
     // what I'm thinking here, is that we analyze how many price step,
     // we can take forward or backward in this gap. Therefore, maximize 
     // the flexibility of our choice to speed up the order filling.
     //
-    let ring_prices = build_ring(&_ring, &tickers_a, &tickers_b, &tickers_c);
-    let price_gap_a = correct_price_filter(&_ring[0], &quantity_info, ring_prices[0][1] - ring_prices[0][0]);
-    let price_gap_b = correct_price_filter(&_ring[1], &quantity_info, ring_prices[1][1] - ring_prices[1][0]);
-    let price_gap_c = correct_price_filter(&_ring[2], &quantity_info, ring_prices[2][1] - ring_prices[2][0]);
+    let info_a = &snapshot.quantity[ring.legs[0]];
+    let info_b = &snapshot.quantity[ring.legs[1]];
+    let info_c = &snapshot.quantity[ring.legs[2]];
+    let ring_prices = build_ring(ring, snapshot, policy);
+    let book_a = &snapshot.books[ring.legs[0]];
+    let book_b = &snapshot.books[ring.legs[1]];
+    let book_c = &snapshot.books[ring.legs[2]];
+    let price_gap_a = correct_price(info_a, book_a[1] - book_a[0]);
+    let price_gap_b = correct_price(info_b, book_b[1] - book_b[0]);
+    let price_gap_c = correct_price(info_c, book_c[1] - book_c[0]);
     //
     // is it profitable ? 
-    let fees = 1.0 - (0.075/100.0);
     let warning_ratio = PROFIT_WARNING; // as ~ 5.0%
     let mut min_invest = min_invest;
     if IS_TESTING { min_invest = 50.0; }
     let optimal_invest = if min_invest > MAX_INVEST { MAX_INVEST } else { min_invest };
 
-    // best
-    let sum =  (optimal_invest / ring_prices[0][0]) * ring_prices[1][1] * ring_prices[2][1] * fees * fees * fees;
-    let profit = sum - optimal_invest;
+    // best : filled at our policy prices, maker where we rest in the book
+    let ring_fees = fee_model.ring(ring, snapshot, &ring_prices, optimal_invest, false);
+    // prices drift while orders are on their way, margin must survive it.
+    let latency_cost = latency.cost(ring, model, optimal_invest);
+    let profit = ring_fees.out - ring_fees.in_bnb - optimal_invest - latency_cost;
 
-    // worst
-    let sum_worst = optimal_invest / ring_prices[0][1] * ring_prices[1][0] * ring_prices[2][0] * fees * fees * fees;
-    let profit_worst = sum_worst - optimal_invest;
+    // worst : crossing the book on every leg, all taker
+    let worst_prices = [book_a[1], book_b[0], book_c[0]];
+    let worst_fees = fee_model.ring(ring, snapshot, &worst_prices, optimal_invest, true);
+    let profit_worst = worst_fees.out - worst_fees.in_bnb - optimal_invest - latency_cost;
     //
     //
     // OK
     // let's say, we only accept profit > 0.5% and risk < 0.2%
     if profit > (PROFIT_MINIMUM/100.0) * optimal_invest {
-        println!("\n> analyze: {} has {} steps", &_ring[0], 
-            correct_price_filter(&_ring[0], &quantity_info, price_gap_a/quantity_info[&_ring[0]].step_price));
-        println!("> analyze: {} has {} steps", &_ring[1], 
-            correct_price_filter(&_ring[1], &quantity_info,price_gap_b/quantity_info[&_ring[1]].step_price));
-        println!("> analyze: {} has {} steps", &_ring[2], 
-            correct_price_filter(&_ring[2], &quantity_info,price_gap_c/quantity_info[&_ring[2]].step_price));
-        println!("> profit: best/worst = {:.2} / {:.2}", profit, profit_worst);
+        println!("\n> analyze: {} has {} steps", &ring.pairs[0], correct_price(info_a, price_gap_a/info_a.step_price));
+        println!("> analyze: {} has {} steps", &ring.pairs[1], correct_price(info_b, price_gap_b/info_b.step_price));
+        println!("> analyze: {} has {} steps", &ring.pairs[2], correct_price(info_c, price_gap_c/info_c.step_price));
+        let score = model.score(ring, snapshot, &ring_prices, optimal_invest, profit, profit_worst);
+        println!("> profit: best/worst = {:.2} / {:.2} | fees ${:.4} | latency ${:.4} | fill {:.1}% | ev = {:.4}", 
+        profit, profit_worst, ring_fees.fees, latency_cost, score.probability * 100.0, score.expected_value);
         //let qty = optimal_invest / ring_prices[0][0];       // println!("optimal / price {} = {}", symbol ,qty);
         let percentage = (profit/optimal_invest)*100.0;     // Ranking w/ Profit
         // LOG
-        let ring_details = format!("{:?} > {:?} > {:?}", ring_prices[0], ring_prices[1], ring_prices[2]).to_string().cyan();
+        let ring_details = format!("{:?} > {:?} > {:?}", book_a, book_b, book_c).to_string().cyan();
         let log_profit = format!("{:.5}{} {} ${:.4} max: ${:4.2}\t | {}", 
-        (&percentage).to_string().yellow(), "%".yellow(), "=".bold(), (&profit).to_string().green(), &optimal_invest, &symbol.bold());
+        (&percentage).to_string().yellow(), "%".yellow(), "=".bold(), (&profit).to_string().green(), &optimal_invest, ring.symbol.bold());
         //
-        // WARNING: invalid pairs
-        if profit > optimal_invest * (warning_ratio/100.0) {     
-            if !IS_DEBUG { println!("\n{}\n{} - {}\n\n", log_profit, ring_details, "WARNING: REMOVE THIS PAIR".red()); }
-            return None;
+        // WARNING: invalid pairs, quarantined by caller
+        let is_anomaly = profit > optimal_invest * (warning_ratio/100.0);
+        if is_anomaly {     
+            if !IS_DEBUG { println!("\n{}\n{} - {}\n\n", log_profit, ring_details, "WARNING: QUARANTINE THIS RING".red()); }
         }
         //
         // PROFITABLE: normal log
-        if IS_DEBUG && IS_DETAIL { println!("\n{}\n{}", log_profit, ring_details); }
-        return Some(RingResult { symbol, percentage, profit, qty:0.0, optimal_invest }); 
+        else if IS_DEBUG && IS_DETAIL { println!("\n{}\n{}", log_profit, ring_details); }
+        return Some(RingResult { ring: index, percentage, profit, 
+            probability: score.probability, expected_value: score.expected_value, is_anomaly,
+            fees: ring_fees.fees, fees_in_bnb: ring_fees.in_bnb, qty:0.0, optimal_invest }); 
     }
     return None;
 }
 
-pub fn init_threads(config: &Ini, market: &Market, symbols_cache: &Vec<String>, 
-    rings: HashMap<String, Vec<String>>, quantity_info: &HashMap<String, QuantityInfo>){
+/// Run every enabled strategy on the same books, block after block :
+/// what they ask for is executed, reconciled & journaled here, errors decide whether we go on.
+pub fn init_threads(config: &Ini, market: &Market, quarantine: Quarantine,
+    rings: HashMap<String, Vec<RingLeg>>, quantity_info: &HashMap<String, QuantityInfo>) -> Result<()> {
     //
     // ACCOUNT
-    let account = get_account(config);
-    let mut trade_best = String::new();
-    let mut trade_lifetime = 0; // as blocks, longer a trade last, more stable price is.
+    let mut account = get_account(config)?;
+    let user_data = userdata::start(config, &account, get_user_stream(config)?)?;
+    let (stablecoin, _) = bridges(config)?;
+    let retry = RetryPolicy::from_config(config);
+    let mut reconciler = Reconciler::from_config(config, &stablecoin);
+    // every order carries `rg-RUN-BLOCK-RING-LEG`, see orders.rs.
+    let run_id = orders::run_id(quarantine::now_ms());
+    orders::report_open_orders(&account);
     //
-    // RING COMPONENTS
-    //
-    let _config_bridges = &config.get("symbols", "bridges").unwrap();
-    let config_bridges:Vec<&str> = _config_bridges.split(',').collect();
-    let mut ring_component = RingComponent {
-        symbol: String::from(""),
-        bridge: String::from(config_bridges[1]),
-        stablecoin: String::from(config_bridges[0]) 
-    };
-    let mut virtual_account = executor::get_balance( &account, &ring_component.stablecoin).unwrap();
+    // STRATEGIES & SNAPSHOT: interned once, refreshed in place every block.
+    let mut strategies = load_strategies(config, quarantine, rings)?;
+    let mut snapshot = Snapshot::empty();
+    for strategy in strategies.iter_mut() { strategy.intern(&mut snapshot, quantity_info); }
+    let fee_model = FeeModel::load(config, &account, &snapshot);
+    let view = AccountView { account: &account, user_data: &user_data, balances: HashMap::new() };
+    for strategy in strategies.iter_mut() { strategy.start(config, &view, &snapshot)?; }
+    let mut assets: Vec<String> = vec![];
+    for asset in strategies.iter().flat_map(|strategy| strategy.assets()) {
+        if !assets.contains(&asset) { assets.push(asset); }
+    }
 
     println!("> searching...");
     //
     // BLOCK COUNT
     //
     let mut block_count = 0;
+    let mut failed_trades = 0;
     loop {
         let benchmark = SystemTime::now();  // BENCHMARK
-        let mut tickers_update_time:Duration = Duration::from_millis(0);
-        let mut tickers_a: HashMap<String, [f64;2]> = HashMap::new();
-        let mut tickers_b: HashMap<String, [f64;2]> = HashMap::new();
-        let mut tickers_c: HashMap<String, [f64;2]> = HashMap::new();
-        let is_prices_updated = update_orderbooks(&market, &symbols_cache, &mut tickers_a, &mut tickers_b, &mut tickers_c, &quantity_info);
-        match is_prices_updated {
-            true => { // Check time : 
-                match benchmark.elapsed() {
-                    Ok(elapsed) => tickers_update_time = elapsed,
-                    Err(e) => println!("> can't benchmark update_orderbooks: {:?}", e)
-                }
-            },
-            false => thread::sleep(DELAY_INIT) // wait for a while...
-            //false => return // break the loop.
+        if !update_orderbooks(market, &mut snapshot) {
+            thread::sleep(DELAY_INIT); // wait for a while...
+            continue;
         }
-        if is_prices_updated {
-            // Get computed result 
-            let mut round_result = compute_rings( &rings, virtual_account.clone(), &tickers_a, &tickers_b, &tickers_c, quantity_info);
-            let arbitrage_count = round_result.len();
-
-            // If there's profitable ring AND binance didn't lag more than a second 
-            if arbitrage_count > 0 /*&& tickers_update_time < DELAY_INIT*/ {
-                println!("\n> ===================[ Block {} ]=================== <", block_count.to_string().yellow());
-                // tickers time
-                println!("{}", format!("#{}: updated orderbooks in {} ms", 
-                block_count.to_string().yellow(), tickers_update_time.as_millis().to_string().yellow()));
-                // Sort by Profit 
-                round_result.sort_by(|a, b| b.profit.partial_cmp(&a.profit).unwrap());
-                println!("> found {} arbitrages.", arbitrage_count);
-                println!("____________________________");
-                for result in &round_result {
-                    println!("| {:.2}% = ${:.2}   | {}",
-                    result.percentage, result.profit,   result.symbol);
+        let elapsed_ms = || benchmark.elapsed().map_or(0, |elapsed| elapsed.as_millis());
+        let market_view = MarketView { block: block_count, now_ms: quarantine::now_ms(), update_ms: elapsed_ms(), snapshot: &snapshot, fee_model: &fee_model };
+        let balances = if assets.is_empty() { HashMap::new() } else { reconcile::balances(&account, &user_data, &assets) };
+        let account_view = AccountView { account: &account, user_data: &user_data, balances };
+        let mut is_resync_needed = false;
+        for strategy in strategies.iter_mut() {
+            for intent in strategy.on_block(&market_view, &account_view) {
+                let mode = match &intent {
+                    Intent::Ring(ring) => String::from(if ring.is_parallel { "parallel" } else { "linear" }),
+                    Intent::Order(_) => strategy.name().to_string()
                 };
-                println!("____________________________");
-                println!();
-                let trade = &round_result[0];
-                // record lifetime for each trade
-                if trade_best != trade.symbol { 
-                    trade_lifetime = 0; // restart
-                    trade_best = trade.symbol.clone();  
-                } else { 
-                    trade_lifetime += 1; 
+                let tag = RingTag::new(&run_id, block_count, intent.symbol());
+                let latency_ms = elapsed_ms() as f64;
+                let before = reconciler.before(&account, &user_data, intent.traded());
+                let mut fills: Vec<executor::Fill> = vec![];
+                let mut run = executor::Execution { account: &account, user_data: &user_data, quantity_info, tag: &tag, retry: &retry, fills: &mut fills };
+                let result = execute(&mut run, market, &intent);
+                // what fills explain against what balances did, failed trades included.
+                if let Some(before) = before {
+                    reconciler.after(&account, &user_data, before, &fills, &market_view, &tag);
                 }
-                if trade_lifetime > SAFE_LIFETIME {
-                    println!("> best: {} | {:.2}% = ${:.2} | alive: {} blocks.",
-                    trade.symbol, trade.percentage, trade.profit, trade_lifetime);
-                    // Build ring prices
-                    let final_ring = &rings[&trade.symbol];
-                    let ring_prices = build_ring(final_ring, &tickers_a, &tickers_b, &tickers_c);
-
-                    // 2. send best trade > executor
-                    ring_component.symbol = trade.symbol.clone(); 
-                    println!("> best: {} > {} > {}", ring_component.symbol, ring_component.bridge, ring_component.stablecoin);
-                    println!("> best: buy {} > sell {} > sell {}", ring_prices[0][0], ring_prices[1][1], ring_prices[2][1]);
-                    // show log
-                    let new_balance:Option<f64> = executor::execute_final_ring(&account, &market, &ring_component, final_ring, &ring_prices, trade.optimal_invest, quantity_info.clone());
-                    let mut final_profit:f64 = 0.0;
-                    // 3. wait for trade finish
-                    // 4. evaluate profit
-                    match new_balance {
-                        Some(_balance) => { 
-                            println!("> end of block {}.", block_count.to_string().yellow());
-                            if _balance > 0.0 { 
-                                final_profit = _balance - virtual_account; 
-                                virtual_account = _balance; 
-                                
-                                // benchmark every block 
-                                match benchmark.elapsed() {
-                                    Ok(elapsed) => {
-                                        let fmt = format!("#{}: ${} - trade {} {} for ${}/${} in {} ms",
-                                        block_count.to_string().yellow(), 
-                                        format!("{:.2}", virtual_account).green(),
-                                        format!("{:.2}", trade.qty).green(), 
-                                        trade.symbol.green(), 
-                                        format!("{:.2}", final_profit).yellow(),
-                                        format!("{:.2}", trade.profit).yellow(),  
-                                        elapsed.as_millis().to_string().yellow());
-                                        println!("{}", fmt);
-                                    }
-                                    Err(e) => println!("Error: {:?}", e)
-                                }
-                            }
-                        },
-                        None => { return; // Quit Loop because there is error. 
+                let action = result.as_ref().err().map(|e| retry.classify(e));
+                let report = Report { fills: &fills, result: &result, action, latency_ms };
+                let realized_profit = strategy.on_result(&market_view, &account_view, &intent, &report)?;
+                match result {
+                    Ok(_) => {
+                        failed_trades = 0;
+                        println!("> end of block {} after {} ms.", block_count.to_string().yellow(), elapsed_ms());
+                        journal::record(intent.symbol(), &tag.prefix(), &mode, intent.expected(), realized_profit);
+                    },
+                    Err(e) => {
+                        failed_trades += 1;
+                        println!("> {} {} ( {:?}, {} failed in a row )", "error:".red(), e, action, failed_trades);
+                        if failed_trades >= retry.halt_after {
+                            return Err(Error::Halt(format!("{} trades failed in a row, last: {}", failed_trades, e)));
+                        }
+                        match action.unwrap_or(Action::Shutdown) {
+                            Action::Retry => thread::sleep(retry.backoff(failed_trades, &e)),
+                            Action::ResyncTime => is_resync_needed = true,
+                            Action::RefreshBalances => {}, // each strategy refreshes what it tracks
+                            Action::SkipRing => println!("> skip {}.", intent.symbol()),
+                            Action::Shutdown => return Err(e) // Quit Loop because there is error.
                         }
                     }
                 }
-            //5. next block !
-            block_count += 1;
-            } 
-            else if IS_DEBUG { println!("> no arbitrage chances."); }
-            // BLOCK-TIME
-            thread::sleep(DELAY_INIT);        
-        } 
+            }
+        }
+        drop(account_view);
+        if is_resync_needed { retry::resync_time(config, &mut account)?; }
+        //5. next block !
+        block_count += 1;
+        // BLOCK-TIME
+        thread::sleep(DELAY_INIT);
+    }
+}
+
+/// `[strategies] enabled` : comma separated names, default triangle, lowercased and once each.
+fn enabled_strategies(config: &Ini) -> Vec<String> {
+    let enabled = config.get("strategies", "enabled").unwrap_or(String::from(DEFAULT_STRATEGIES)).to_lowercase();
+    let mut names: Vec<String> = vec![];
+    for name in enabled.split(',').map(|name| name.trim()).filter(|name| !name.is_empty()) {
+        if !names.iter().any(|known| known == name) { names.push(name.to_string()); }
+    }
+    return names;
+}
+
+/// Pairs enabled strategies trade beside rings, their quantity info is fetched with ring pairs.
+pub fn strategy_symbols(config: &Ini) -> Vec<String> {
+    let mut symbols: Vec<String> = vec![];
+    for name in enabled_strategies(config) {
+        if name == "peg" { symbols.extend(peg::symbols(config)); }
+    }
+    return symbols;
+}
+
+fn load_strategies(config: &Ini, quarantine: Quarantine, rings: HashMap<String, Vec<RingLeg>>) -> Result<Vec<Box<dyn Strategy>>> {
+    let mut strategies: Vec<Box<dyn Strategy>> = vec![];
+    let (mut quarantine, mut rings) = (Some(quarantine), Some(rings));
+    for name in enabled_strategies(config) {
+        match name.as_str() {
+            "triangle" => if let (Some(quarantine), Some(rings)) = (quarantine.take(), rings.take()) {
+                strategies.push(Box::new(Triangle::new(config, quarantine, rings)?));
+            },
+            "peg" => strategies.push(Box::new(peg::Peg::new(config)?)),
+            _ => return Err(Error::config("strategies", "enabled", &format!("unknown strategy {}", name)))
+        }
+    }
+    if strategies.is_empty() { return Err(Error::config("strategies", "enabled", "no strategy enabled")); }
+    println!("> strategies: {}", strategies.iter().map(|strategy| strategy.name()).collect::<Vec<_>>().join(", "));
+    return Ok(strategies);
+}
+
+/// Hand `intent` to the executor.
+fn execute(run: &mut executor::Execution, market: &Market, intent: &Intent) -> Result<Option<f64>> {
+    match intent {
+        Intent::Ring(ring) => {
+            let ring_component = RingComponent {
+                symbol: ring.ring.symbol.clone(),
+                bridge: ring.ring.assets[1].1.clone(),
+                stablecoin: ring.ring.assets[0].1.clone()
+            };
+            let final_ring = &ring.ring.pairs;
+            return match ring.is_parallel {
+                true => executor::execute_final_ring_pallarel(run, market, &ring_component, final_ring, &ring.prices, ring.invest, &ring.orders),
+                false => executor::execute_final_ring(run, market, &ring_component, final_ring, &ring.prices, ring.invest, &ring.orders)
+            };
+        },
+        Intent::Order(order) => return executor::execute_order(run, order)
     }
-    // ending
-    println!("\n> RailGun Out.\n");
 }
 
 /// correct price filter to ensure order pass through.
-fn correct_price_filter(symbol: &str, quantity_info: &HashMap<String, QuantityInfo>,  price: f64) -> f64 {
-    let move_price = quantity_info[symbol].move_price;
-    return f64::trunc(price  * move_price) / move_price;
+fn correct_price(info: &QuantityInfo, price: f64) -> f64 {
+    return info.round_price(price);
+}
+
+/// Build buy-sell-sell limit prices for a loopring from the pricing policy.
+fn build_ring(ring: &Ring, snapshot: &Snapshot, policy: &PricingPolicy) -> [f64;3] {
+    let mut prices = [0.0;3];
+    for (leg, id) in ring.legs.iter().enumerate() {
+        let info = &snapshot.quantity[*id];
+        prices[leg] = correct_price(info, policy.legs[leg].price(&snapshot.books[*id], info.step_price));
+    }
+    return prices;
+}
+
+/// Snapshot of a ring's prices & ticks, kept with its quarantine entry.
+fn build_evidence(ring: &Ring, snapshot: &Snapshot, policy: &PricingPolicy, result: &RingResult) -> Evidence {
+    let books: Vec<[f64;2]> = ring.legs.iter().map(|id| snapshot.books[*id]).collect();
+    let ticks: Vec<f64> = ring.legs.iter()
+        .map(|id| ((snapshot.books[*id][1] - snapshot.books[*id][0]) / snapshot.quantity[*id].step_price).round())
+        .collect();
+    return Evidence {
+        pairs: ring.pairs.clone(),
+        books,
+        prices: build_ring(ring, snapshot, policy).to_vec(),
+        ticks,
+        profit: result.profit,
+        percentage: result.percentage
+    };
+}
+
+/// Keys from `credentials`, a keystore or secrets file that can't be opened stops the bot
+/// instead of going on unauthenticated.
+fn keys(config: &Ini) -> Result<&'static credentials::Credentials> {
+    return credentials::load(config).map_err(|e| Error::config("keys", "credentials", &e));
 }
 
-/// Build buy-sell-sell vec![ prices , qty ] for a loopring.
-fn build_ring(ring: &Vec<String>, 
-    tickers_a: &HashMap<String, [f64;2]>, 
-    tickers_b: &HashMap<String, [f64;2]>, 
-    tickers_c: &HashMap<String, [f64;2]>) -> Vec<[f64;2]>{
-
-    // limit order strategy
-    let p1 = tickers_a.get(&ring[0]).unwrap(); // LIMIT_BUY
-    let p2 = tickers_b.get(&ring[1]).unwrap(); // LIMIT_SELL
-    let p3 = tickers_c.get(&ring[2]).unwrap(); // LIMIT_SELL
-
-    // ticker average strategy
-    // let p11 = tickers_buy.get(&ring[0]).unwrap(); 
-    // let p12 = tickers_sell.get(&ring[0]).unwrap(); 
-    // let p1 = &[(p11[0]+p12[0])/2.0, p12[1]];
-
-    // let p21 = tickers_buy.get(&ring[1]).unwrap(); 
-    // let p22 = tickers_sell.get(&ring[1]).unwrap(); 
-    // let p2 =  &[(p21[0]+p22[0])/2.0, p22[1]];
-
-    // let p31 = tickers_buy.get(&ring[2]).unwrap(); 
-    // let p32 = tickers_sell.get(&ring[2]).unwrap(); 
-    // let p3 =  &[(p31[0]+p32[0])/2.0, p32[1]];
-
-    // market order strategy
-    // let p1 = tickers_sell.get(&ring[0]).unwrap(); // LIMIT_BUY
-    // let p2 = tickers_buy.get(&ring[1]).unwrap(); // LIMIT_SELL
-    // let p3 = tickers_buy.get(&ring[2]).unwrap(); // LIMIT_SELL
-
-    // return values
-    return vec![*p1, *p2, *p3];
+pub fn get_market(config: &mut Ini) -> Result<Market> {
+    let endpoint = Endpoint::load(config)?.binance_config();
+    let keys = keys(config)?;
+    return Ok(Binance::new_with_config(Some(keys.api_key.clone()), Some(keys.secret_key.clone()), &endpoint));
 }
 
-pub fn get_market(config: &mut Ini) -> Market {
-    let mainnet = Config::default();//.set_rest_api_endpoint("https://testnet.binance.vision");
-    return Binance::new_with_config(
-        Some(config.get("keys", "api_key").unwrap()), 
-        Some(config.get("keys", "secret_key").unwrap()),
-        &mainnet);
+pub fn get_account(config: &Ini) -> Result<Account> {
+    let endpoint = Endpoint::load(config)?.binance_config();
+    let keys = keys(config)?;
+    return Ok(Binance::new_with_config(Some(keys.api_key.clone()), Some(keys.secret_key.clone()), &endpoint));
 }
 
-pub fn get_account(config: &Ini) -> Account {
-    let mainnet = Config::default();//.set_rest_api_endpoint("https://testnet.binance.vision");
-    return Binance::new_with_config(
-        Some(config.get("keys", "api_key").unwrap()), 
-        Some(config.get("keys", "secret_key").unwrap()),
-        &mainnet);
-}
\ No newline at end of file
+pub fn get_user_stream(config: &Ini) -> Result<UserStream> {
+    let endpoint = Endpoint::load(config)?.binance_config();
+    let keys = keys(config)?;
+    return Ok(Binance::new_with_config(Some(keys.api_key.clone()), None, &endpoint));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use binance::config::Config;
+    use crate::mock::{MockExchange, Scenario};
+
+    const EXCHANGE_INFO:&str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/exchange_info.json");
+    const BOOK_TICKERS:&str = include_str!("../tests/fixtures/book_tickers.json");
+
+    /// Mock serving the recorded exchangeInfo & book tickers, `books` replaced.
+    fn recorded(books: &[(&str, [f64;2])]) -> MockExchange {
+        let tickers: Vec<serde_json::Value> = serde_json::from_str(BOOK_TICKERS).unwrap();
+        let price = |ticker: &serde_json::Value, key: &str| ticker[key].as_str().unwrap().parse::<f64>().unwrap();
+        let mut step: HashMap<String, [f64;2]> = tickers.iter()
+            .map(|ticker| (ticker["symbol"].as_str().unwrap().to_string(), [price(ticker, "bidPrice"), price(ticker, "askPrice")]))
+            .collect();
+        for (symbol, book) in books { step.insert(symbol.to_string(), *book); }
+        let scenario = Scenario {
+            exchange_info: Some(EXCHANGE_INFO.to_string()),
+            balances: HashMap::from([(String::from("BUSD"), 1000.0)]),
+            prices: vec![step],
+            ..Default::default()
+        };
+        return MockExchange::start(scenario).unwrap();
+    }
+
+    /// Profitable rings of one block priced by `mock`, by ring symbol.
+    fn analyze(mock: &MockExchange) -> HashMap<String, RingResult> {
+        let symbols = exchangeinfo::fetch_symbols(&mock.url).unwrap();
+        let rings = discover_rings(&symbols, "BUSD", "BNB", &[]);
+        let pairs: Vec<String> = rings.values().flatten().map(|leg| leg.symbol.clone()).collect();
+        let quantity_info = exchangeinfo::fetch_quantity_from(&mock.url, &pairs).unwrap();
+        let (mut snapshot, rings) = Snapshot::new(&rings, &quantity_info);
+        let endpoint = Config::default().set_rest_api_endpoint(mock.url.clone());
+        let market: Market = Binance::new_with_config(None, None, &endpoint);
+        let account: Account = Binance::new_with_config(Some("key".into()), Some("secret".into()), &endpoint);
+        assert!(update_orderbooks(&market, &mut snapshot));
+
+        let mut config = Ini::new();
+        config.set("pricing", "preset", Some("market".into()));
+        config.set("fees", "per_symbol", Some("false".into()));
+        let policy = PricingPolicy::from_config(&config);
+        let fee_model = FeeModel::load(&config, &account, &snapshot);
+        let fill_model = FillModel::new(&snapshot);
+        let latency = LatencyModel::new(&config, &snapshot);
+        let models = RingModels { policy: &policy, fill: &fill_model, fees: &fee_model, latency: &latency };
+        return rings.iter().enumerate()
+            .filter(|(_, ring)| snapshot.is_complete(ring))
+            .filter_map(|(index, ring)| analyze_ring(index, ring, MAX_INVEST, &snapshot, models)
+                .map(|result| (ring.symbol.clone(), result)))
+            .collect();
+    }
+
+    #[test]
+    fn discovers_rings_from_recorded_exchange_info() {
+        let mock = recorded(&[]);
+        let symbols = exchangeinfo::fetch_symbols(&mock.url).unwrap();
+        // LUNA is on BREAK, EOSBUSD has spot disabled, DOT has no BNB pair.
+        let rings = discover_rings(&symbols, "BUSD", "BNB", &["QTUMBUSD"]);
+        let mut found: Vec<&String> = rings.keys().collect();
+        found.sort();
+        assert_eq!(found, ["ADA", "XTZ"]);
+        let xtz: Vec<(&str, &str, &str, Side)> = rings["XTZ"].iter()
+            .map(|leg| (leg.symbol.as_str(), leg.base.as_str(), leg.quote.as_str(), leg.side)).collect();
+        assert_eq!(xtz, [("XTZBUSD", "XTZ", "BUSD", Side::Buy), ("XTZBNB", "XTZ", "BNB", Side::Sell), ("BNBBUSD", "BNB", "BUSD", Side::Sell)]);
+
+        let rings = discover_rings(&symbols, "BUSD", "BTC", &[]);
+        assert_eq!(rings.keys().collect::<Vec<_>>(), ["XTZ"]);
+        assert!(discover_rings(&symbols, "BUSD", "ETH", &[]).is_empty());
+    }
+
+    #[test]
+    fn ring_legs_round_trip_through_cache() {
+        let leg = RingLeg { symbol: String::from("XTZBNB"), base: String::from("XTZ"), quote: String::from("BNB"), side: Side::Sell };
+        assert_eq!(leg.to_cache(), "XTZBNB XTZ BNB SELL");
+        let back = RingLeg::from_cache("xtzbnb xtz bnb SELL").unwrap();
+        assert_eq!((back.symbol, back.base, back.quote, back.side), (leg.symbol, leg.base, leg.quote, leg.side));
+        assert!(RingLeg::from_cache("XTZBNB XTZ BNB").is_none());
+        assert!(RingLeg::from_cache("XTZBNB XTZ BNB HOLD").is_none());
+    }
+
+    #[test]
+    fn bridges_need_stablecoin_and_bridge() {
+        let mut config = Ini::new();
+        config.set("symbols", "bridges", Some(" BUSD , BNB ".into()));
+        assert_eq!(bridges(&config).unwrap(), (String::from("BUSD"), String::from("BNB")));
+        config.set("symbols", "bridges", Some("BUSD".into()));
+        assert!(matches!(bridges(&config), Err(Error::Config { .. })));
+    }
+
+    #[test]
+    fn strategies_from_config() {
+        let mut config = Ini::new();
+        config.set("symbols", "bridges", Some("BUSD,BNB".into()));
+        let strategies = load_strategies(&config, Quarantine::load(&config), HashMap::new()).unwrap();
+        assert_eq!(strategies.iter().map(|strategy| strategy.name()).collect::<Vec<_>>(), ["triangle"]);
+        config.set("strategies", "enabled", Some(" Triangle , triangle".into()));
+        assert_eq!(load_strategies(&config, Quarantine::load(&config), HashMap::new()).unwrap().len(), 1);
+        config.set("strategies", "enabled", Some("triangle, grid".into()));
+        assert!(matches!(load_strategies(&config, Quarantine::load(&config), HashMap::new()), Err(Error::Config { .. })));
+        config.set("strategies", "enabled", Some("".into()));
+        assert!(matches!(load_strategies(&config, Quarantine::load(&config), HashMap::new()), Err(Error::Config { .. })));
+    }
+
+    #[test]
+    fn profit_of_recorded_books() {
+        let mock = recorded(&[]);
+        let results = analyze(&mock);
+        // ADA loses to fees.
+        assert_eq!(results.keys().collect::<Vec<_>>(), ["XTZ"]);
+        let xtz = &results["XTZ"];
+        // 100 BUSD > 100 XTZ > 0.34 BNB > 102 BUSD, 0.1% taken on each leg.
+        let out = 100.0 / 1.0 * 0.999 * 0.0034 * 0.999 * 300.0 * 0.999;
+        let percentage = (out - MAX_INVEST) / MAX_INVEST * 100.0;
+        assert!((xtz.percentage - percentage).abs() < 1e-6, "percentage {}", xtz.percentage);
+        assert_eq!(xtz.optimal_invest, MAX_INVEST);
+        assert!(!xtz.is_anomaly);
+    }
+
+    #[test]
+    fn profit_over_warning_is_an_anomaly() {
+        let mock = recorded(&[("XTZBNB", [0.0037, 0.003701])]);
+        let results = analyze(&mock);
+        assert!(results["XTZ"].percentage > PROFIT_WARNING);
+        assert!(results["XTZ"].is_anomaly);
+    }
+}
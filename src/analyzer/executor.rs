@@ -1,164 +1,121 @@
-
 use colored::*;
-use configparser::ini::Ini;
 
-use binance::api::*;
 use binance::account::*;
 use binance::market::Market;
-use binance::model::Transaction;
-use binance::errors::ErrorKind as BinanceLibErrorKind;
 
 use std::{
     collections::HashMap,
-    thread::{self, JoinHandle} ,
-    time::{Duration, SystemTime}};
+    time::SystemTime};
 
+use crate::error::{self, Context, Error, Result};
 use crate::exchangeinfo::QuantityInfo;
-use crate::analyzer::RingComponent;
+use crate::retry::RetryPolicy;
+use crate::userdata::UserData;
+use crate::analyzer::{RingComponent, Side};
+use super::orders::{self, NewOrder, OrderKind, OrderPolicy, RingTag};
+use super::tracker::{self, OrderState, TrackedOrder};
+use super::strategy::OrderIntent;
 use crate::analyzer::IS_TESTING;
 
 /// counting before dropping an ongoing order.
-const DROP_ORDER:i32 = 3;
-const DROP_ORDER_PARTIAL:i32 = 6;
+const DROP_ORDER:u32 = 3;
+const DROP_ORDER_PARTIAL:u32 = 6;
+const DROP_ORDER_LAST:u32 = 20; // last legs close the ring, worth waiting longer for
 const MIN_SHORT_SELLING_PROFIT:f64 = 0.01;
 
-/// Wait time between orders
-const POLLING_ORDER: Duration = Duration::from_millis(500);
-// const POLLING_ORDER_WAIT: Duration = Duration::from_millis(1000);
-
-/// Poll and Wait until an order is filled.
-fn polling_order(account: &Account, market: &Market, order_id: u64, qty: f64, symbol: &str, origin_balance: f64, 
-    final_ring: &Vec<String>, ring_component: &RingComponent, quantity_info: &HashMap<String,QuantityInfo>,
-    is_1st_order: bool, is_short_selling: bool) -> Option<f64> {
-    let mut polling_count = 0;
-    println!("> order: #{} for {} {}", &order_id.to_string().yellow(), qty.to_string().green(), &symbol.green());
-    loop {
-        match account.order_status(symbol, order_id) {
-            Ok(answer) => {
-                match answer.status.as_str() {
-                    "FILLED" => {
-                        println!("> executed qty: {}/{} after {} polls.", answer.executed_qty.green(), qty, &polling_count);
-                        return Some(answer.executed_qty.parse::<f64>().unwrap());
-                    },  // can move on next symbol
-                    "CANCELED" => return None, // on purpose ;) move to next round ?
-                    "NEW" => if polling_count > DROP_ORDER && is_1st_order { 
-                        match account.cancel_order(symbol, order_id){
-                            Ok(_) => { 
-                                println!("> cancelled #{} after {} polls.", order_id.to_string().yellow(), polling_count);
-                                return None; // cancel and re-buy like market-buy.
-                            },
-                            Err(e) => format_error(e.0)
-                        }
-                    } else if polling_count > DROP_ORDER && !is_1st_order && !is_short_selling { 
-                        // NOTE: SELL NOW IF PROFITABLE 
-                        // * we can sell current asset if it's profitable
-                        //
-                        let tickers_symbol = get_tickers_for(&market, &final_ring[0]);
-                        let symbol_qty = answer.orig_qty.parse::<f64>().unwrap();
-                        let sum = symbol_qty * tickers_symbol[1]; // if we short-sell by ask price.
-                        let profit = sum - origin_balance;
-                        if profit > MIN_SHORT_SELLING_PROFIT {
-                            println!("> new: waited {} polls >> sell now for {}", polling_count, profit);
-                            match account.cancel_order(symbol, order_id){
-                                Ok(_) => { 
-                                    println!("> cancelled #{} after {} polls.", order_id.to_string().yellow(), polling_count);
-                                    match account.limit_sell(&final_ring[0], symbol_qty, tickers_symbol[1]) {
-                                        Ok(result) => {
-                                            polling_order(&account, &market, result.order_id, qty, &final_ring[0], origin_balance, &final_ring, &ring_component, &quantity_info, false, true);
-                                            let _balance = get_balance(&account, &ring_component.stablecoin).unwrap();
-                                            println!("> sold {} {} for {:.2}", result.executed_qty, &final_ring[0].green(), _balance - origin_balance);
-                                            return None;
-                                        },
-                                        Err(e) => format_error(e.0)
-                                    }
-                                    return None; // cancel and re-buy like market-buy.
-                                },
-                                Err(e) => format_error(e.0)
-                            }
-                        } //else { println!("> new: not profitable for short-selling {:.2}", profit) }
-                    },
-                    "PARTIALLY_FILLED" => { //WARNING: NOT TESTED
-                        // SELL PARTIAL FILLED ASSET 
-                        // - if wait for too long + profitable.
-                        //
-                        if !is_1st_order && polling_count > DROP_ORDER_PARTIAL {
-                            // get remaining qty
-                            let symbol_asset = correct_price_filter(&final_ring[0], &quantity_info, 
-                            answer.orig_qty.parse::<f64>().unwrap() - answer.executed_qty.parse::<f64>().unwrap()); 
-                            // with new bridge qty
-                            let bridge_asset = get_balance(&account, &ring_component.bridge).unwrap();
-                            // update prices
-                            let tickers_symbol = get_tickers_for(&market, &final_ring[0]);
-                            let tickers_bridge = get_tickers_for(&market, &final_ring[2]);
-                            let sum = symbol_asset * tickers_symbol[1] + bridge_asset * tickers_bridge[1]; // to sell fast
-                            let profit = sum - origin_balance;
-                            if profit > MIN_SHORT_SELLING_PROFIT {
-                                println!("> partial_filled: waited {} polls >> sell now for {}", polling_count, profit);
-
-                                match account.limit_sell(&final_ring[0], symbol_asset, tickers_symbol[1]) {
-                                    Ok(result) => {
-                                        polling_order(&account, &market, result.order_id, symbol_asset, &final_ring[0], origin_balance, &final_ring, &ring_component, &quantity_info, false, true);
-                                        println!("> partial_filled: sold {} {}", result.executed_qty, &final_ring[0]);
-
-                                        match account.limit_sell(&final_ring[2], bridge_asset, tickers_bridge[1]) {
-                                            Ok(result) => {
-                                                polling_order(&account, &market, result.order_id, symbol_asset, &final_ring[0], origin_balance, &final_ring, &ring_component, &quantity_info, false, true);
-                                                println!("> partial_filled: sold {} {}", result.executed_qty, &final_ring[2]);
-                                                return None; // break now.
-                                            },
-                                            Err(e) => format_error(e.0)
-                                        }
-                                    },
-                                    Err(e) => format_error(e.0)
-                                }
-                            } else { println!("> it's not profitable to sell now: {}", sum - origin_balance) }
-                        }
-                    }
-                    _ => {}//println!("> {} {} is {:?}", qty, &symbol ,answer.status)
-                }
-            },
-            Err(e) => format_error(e.0),
+/// A filled order, fed back into the fill & latency models and checked against balances.
+pub struct Fill {
+    pub symbol: String,
+    pub side: Side,
+    pub executed_qty: f64, // base
+    pub quote_qty: f64,
+    pub elapsed_ms: u128,
+    pub avg_price: f64
+}
+
+/// What every leg of one execution shares, fills of every leg are pushed to `fills`.
+pub struct Execution<'a> {
+    pub account: &'a Account,
+    pub user_data: &'a UserData,
+    pub quantity_info: &'a HashMap<String, QuantityInfo>,
+    pub tag: &'a RingTag,
+    pub retry: &'a RetryPolicy,
+    pub fills: &'a mut Vec<Fill>
+}
+
+impl<'a> Execution<'a> {
+    /// Place one leg and start tracking it.
+    fn send(&self, symbol: &str, side: Side, kind: OrderKind, qty: f64, price: f64, leg: char) -> Result<TrackedOrder> {
+        let order = NewOrder { side, kind, qty, price };
+        let order_id = orders::place(self.account, info(self.quantity_info, symbol)?, order, &self.tag.id(leg), self.retry)
+            .context(format!("leg {} {}", leg, symbol))?;
+        return Ok(TrackedOrder::new(symbol, side, order_id, qty));
+    }
+
+    /// Wait `order` to a final state, see `tracker::wait`, and keep its fill for the models.
+    fn wait(&mut self, order: &mut TrackedOrder, should_cancel: impl FnMut(&TrackedOrder) -> bool) -> Result<()> {
+        tracker::wait(self.account, self.user_data, order, self.retry, should_cancel)?;
+        println!("> executed qty: {}/{} {} {:?} after {} polls.", order.executed_qty.to_string().green(), order.qty, order.symbol, order.state, order.polls);
+        if order.executed_qty > 0.0 {
+            self.fills.push(Fill { symbol: order.symbol.clone(), side: order.side, executed_qty: order.executed_qty, quote_qty: order.quote_qty,
+                elapsed_ms: order.elapsed_ms(), avg_price: order.avg_price() });
         }
-        if polling_count > 0 { thread::sleep(POLLING_ORDER) }
-        polling_count += 1;
-    }            
+        return Ok(());
+    }
+
+    /// Sell `qty` of `symbol` base back at market ( GTC at the bid where MARKET isn't allowed ),
+    /// what's under LOT_SIZE stays as dust. Return the qty sold.
+    fn unwind(&mut self, market: &Market, symbol: &str, qty: f64, leg: char) -> Result<f64> {
+        let qty = correct_lots_qty(symbol, qty, self.quantity_info)?;
+        if qty <= 0.0 { println!("> unwind: {} left is dust.", symbol); return Ok(0.0); }
+        let bid = get_tickers_for(market, symbol)?[1];
+        println!("> unwind: {} {} {} at {}", "sell".red(), qty, symbol.green(), bid);
+        let mut order = self.send(symbol, Side::Sell, OrderKind::Market, qty, bid, leg)?;
+        self.wait(&mut order, |order| order.polls > DROP_ORDER_PARTIAL)?;
+        if order.remaining() > 0.0 { println!("> unwind: {} {} left unsold.", order.remaining(), symbol); }
+        return Ok(order.executed_qty);
+    }
+}
+
+/// 1st leg has nothing to unwind : drop it if it rests too long, keep what filled.
+fn is_stale(order: &TrackedOrder) -> bool {
+    return (order.state == OrderState::New && order.polls > DROP_ORDER) || order.polls > DROP_ORDER_PARTIAL;
 }
 
 /// return [ ask, bid ] prices of a single symbol.
-fn get_tickers_for(market: &Market, symbol: &str) -> Vec<f64> {
-    match market.get_book_ticker(symbol) {
-        Ok(result) => return vec![result.ask_price, result.bid_price],
-        Err(e) => { format_error(e.0); return vec![] }
-    };
+fn get_tickers_for(market: &Market, symbol: &str) -> Result<[f64;2]> {
+    let result = market.get_book_ticker(symbol).context(symbol)?;
+    return Ok([result.ask_price, result.bid_price]);
 }
 
 /// Get balance of any symbol in account.
-pub fn get_balance(account: &Account, symbol: &str) -> Option<f64>{
-    match account.get_balance(symbol) {
-        Ok(answer) => {
-            let qty = answer.free.parse::<f64>().unwrap();
-            println!("> balance: {} {}", qty, symbol);
-            return Some(qty);
-        },
-        Err(e) => { println!("{:?}", e); return None; }
+pub fn get_balance(account: &Account, user_data: &UserData, symbol: &str) -> Result<f64> {
+    if let Some(qty) = user_data.balance(symbol) {
+        println!("> balance: {} {} (stream)", qty, symbol);
+        return Ok(qty);
     }
+    let answer = account.get_balance(symbol).context(format!("balance of {}", symbol))?;
+    let qty = error::parse_f64(&answer.free, "free balance")?;
+    println!("> balance: {} {}", qty, symbol);
+    return Ok(qty);
 }
-fn correct_price_filter(symbol: &str, quantity_info: &HashMap<String, QuantityInfo>,  price: f64) -> f64 {
-    let move_price = quantity_info[symbol].move_price;
-    return f64::trunc(price  * move_price) / move_price;
+
+/// quantity info of a ring symbol.
+fn info<'a>(quantity_info: &'a HashMap<String, QuantityInfo>, symbol: &str) -> Result<&'a QuantityInfo> {
+    return quantity_info.get(symbol).ok_or_else(|| Error::MissingSymbol(symbol.to_string()));
 }
 
-fn correct_lots_qty(symbol: &str, qty: f64, quantity_info: &HashMap<String, QuantityInfo>) -> f64 {
-    let move_qty = quantity_info[symbol].move_qty;
-    let corrected_qty = f64::trunc(qty  * move_qty) / move_qty;
-    return corrected_qty;
+fn correct_price_filter(symbol: &str, quantity_info: &HashMap<String, QuantityInfo>,  price: f64) -> Result<f64> {
+    return Ok(info(quantity_info, symbol)?.round_price(price));
 }
 
-fn format_error(e: BinanceLibErrorKind){
-    match e {
-        BinanceLibErrorKind::BinanceError(response) => println!("> error: {}", response.msg),
-        _ => {}
-    }
+fn correct_lots_qty(symbol: &str, qty: f64, quantity_info: &HashMap<String, QuantityInfo>) -> Result<f64> {
+    return Ok(info(quantity_info, symbol)?.round_qty(qty));
+}
+
+
+fn format_error(e: impl Into<Error>){
+    println!("> error: {}", e.into());
 }
 
 fn format_result(balance_qty:f64, symbol: &str, benchmark: &SystemTime){
@@ -166,227 +123,338 @@ fn format_result(balance_qty:f64, symbol: &str, benchmark: &SystemTime){
         "> success: {:?} {} after {} ms.\n", 
         balance_qty, 
         symbol, 
-        benchmark.elapsed().unwrap().as_millis().to_string()).green());
+        benchmark.elapsed().map_or(0, |elapsed| elapsed.as_millis()).to_string()).green());
 }
 
-/// Execute best ring found in previous round result.
-pub fn execute_final_ring(account: &Account, market: &Market, ring_component: &RingComponent, final_ring: &Vec<String>, 
-    prices: &Vec<[f64;2]>, config_invest: f64, quantity_info: HashMap<String, QuantityInfo>) -> Option<f64> {
+/// Execute best ring found in previous round result :
+/// stablecoin after the 3rd leg, or None when the 1st leg wasn't filled and the ring was dropped.
+/// Each leg sells what the one before actually filled, leftovers are unwound back to stablecoin.
+pub fn execute_final_ring(run: &mut Execution, market: &Market, ring_component: &RingComponent, final_ring: &[String],
+    prices: &[f64;3], config_invest: f64, order_policy: &OrderPolicy) -> Result<Option<f64>> {
     
     let benchmark = SystemTime::now();
     println!("> -------------------------------------------------- <");
     //> for testing purpose.
-    if IS_TESTING { return Some(0.0); }
+    if IS_TESTING { return Ok(Some(0.0)); }
 
-    // states
-    let mut order_result:Option<f64> = None;
-
-    // correct lots + step_size
-    let mut symbol:&str;
-    // let mut step_qty:f64 = 0.0;
-    // let mut step_price:f64 = 0.0;
-    let mut balance_qty:f64 = 0.0;
-    let mut custom_price:f64 = 0.0;
-    
     // prepare balance 
-    let _current_balance = get_balance(&account, &ring_component.stablecoin).unwrap(); println!();
-    if _current_balance < 10.0 { return None; } // Break because this will be serious error.
+    let _current_balance = get_balance(run.account, run.user_data, &ring_component.stablecoin)?; println!();
+    if _current_balance < 10.0 { // Break because this will be serious error.
+        return Err(Error::Halt(format!("{} {} left, too low to trade", _current_balance, ring_component.stablecoin)));
+    }
     let optimal_invest = if config_invest > _current_balance { _current_balance } else { config_invest };
 
-
-    // step_qty = quantity_info[symbol].step_qty;
-    // step_price = quantity_info[symbol].step_price;
     //
     // 1. Buy OOKI-BUSD
     //
-    symbol = &final_ring[0];
-    let first_order = optimal_invest/(prices[0][0]);
-
-    balance_qty = correct_lots_qty(symbol, first_order, &quantity_info);
+    let symbol = &final_ring[0];
+    let qty = correct_lots_qty(symbol, optimal_invest/(prices[0]), run.quantity_info)?;
     println!("> limit_buy: {} {} at {}", 
-    &balance_qty.to_string().green(), symbol.green(), (optimal_invest/first_order).to_string().yellow());
-    match account.limit_buy(symbol, balance_qty, prices[0][0]) {
-    // match account.market_buy(symbol, balance_qty) {
-        Ok(answer) => order_result = polling_order(&account, &market, answer.order_id, balance_qty, symbol, _current_balance, &final_ring, &ring_component, &quantity_info, true, false),
-        Err(e) => { 
-            format_error(e.0); 
-            // return None; 
-        }
-    }
-    match order_result {
-        Some(executed_qty) => {
-            balance_qty = executed_qty;
-            format_result(balance_qty, &ring_component.symbol, &benchmark);
-        }
-        None => return Some(-1.0) 
-    }
+    &qty.to_string().green(), symbol.green(), prices[0].to_string().yellow());
+    let mut leg_a = run.send(symbol, Side::Buy, order_policy.legs[0], qty, prices[0], 'a')?;
+    run.wait(&mut leg_a, is_stale)?;
+    if leg_a.executed_qty <= 0.0 { return Ok(None); }
+    format_result(leg_a.executed_qty, &ring_component.symbol, &benchmark);
+
     //
-    // 2. Sell OOKI-BTC
+    // 2. Sell OOKI-BTC, what leg a bought.
+    // Cancelled once selling everything back to stablecoin is already worth it.
     //
-    symbol = &final_ring[1];
-    balance_qty = correct_lots_qty(symbol, balance_qty, &quantity_info); 
-    custom_price = correct_price_filter(symbol, &quantity_info, prices[1][1]);
-    println!("> limit_sell: {} {} at {}", 
-    &balance_qty.to_string().green(), symbol.green(), &prices[1][1].to_string().yellow());
-    match account.limit_sell(symbol, balance_qty, custom_price) {
-        Ok(answer) => order_result = polling_order(&account, &market, answer.order_id, balance_qty, symbol, _current_balance, &final_ring, &ring_component, &quantity_info, false, false),
-        Err(e) => { 
-            format_error(e.0);
-            return None
-        },
-    }
-    match order_result {
-        Some(_) => { // Have to refresh because it's no longer executed qty.
-            balance_qty = get_balance(&account, &ring_component.bridge).unwrap(); 
-            format_result(balance_qty, &ring_component.bridge, &benchmark);
-        }
-        None => return Some(-1.0)  // None can help to break + stop App.
+    let symbol = &final_ring[1];
+    // symbol received, less fees taken from it.
+    let symbol_qty = get_balance(run.account, run.user_data, &ring_component.symbol)?.min(leg_a.executed_qty);
+    let qty = correct_lots_qty(symbol, symbol_qty, run.quantity_info)?; 
+    let price = correct_price_filter(symbol, run.quantity_info, prices[1])?;
+    if qty <= 0.0 {
+        run.unwind(market, &final_ring[0], symbol_qty, 'x')?;
+        return Ok(None);
     }
+    println!("> limit_sell: {} {} at {}", 
+    &qty.to_string().green(), symbol.green(), &prices[1].to_string().yellow());
+    let mut leg_b = run.send(symbol, Side::Sell, order_policy.legs[1], qty, price, 'b')?;
+    run.wait(&mut leg_b, |order| {
+        if order.polls <= DROP_ORDER { return false; }
+        // symbol left at its stablecoin bid + bridge received at its stablecoin bid.
+        let profit = match (get_tickers_for(market, &final_ring[0]), get_tickers_for(market, &final_ring[2])) {
+            (Ok(symbol), Ok(bridge)) => order.remaining() * symbol[1] + order.quote_qty * bridge[1] - optimal_invest,
+            (Err(e), _) | (_, Err(e)) => { format_error(e); return false; }
+        };
+        if profit > MIN_SHORT_SELLING_PROFIT { println!("> {:?}: waited {} polls >> sell now for {}", order.state, order.polls, profit); }
+        return profit > MIN_SHORT_SELLING_PROFIT;
+    })?;
+    // what leg b didn't sell goes back to stablecoin.
+    if leg_b.remaining() > 0.0 { run.unwind(market, &final_ring[0], leg_b.remaining(), 'x')?; }
+    if leg_b.executed_qty <= 0.0 { return get_balance(run.account, run.user_data, &ring_component.stablecoin).map(Some); }
+    // bridge received, less fees taken from it.
+    let bridge_qty = get_balance(run.account, run.user_data, &ring_component.bridge)?.min(leg_b.quote_qty);
+    format_result(bridge_qty, &ring_component.bridge, &benchmark);
 
     //
-    // 3. Sell BTC-BUSD
+    // 3. Sell BTC-BUSD, what leg b got. Whatever rests too long is sold at market.
     //
-    symbol = &final_ring[2];
-    balance_qty = correct_lots_qty(symbol, balance_qty, &quantity_info);
+    let symbol = &final_ring[2];
+    let qty = correct_lots_qty(symbol, bridge_qty, run.quantity_info)?;
     println!("> limit_sell: {} {} at {}", 
-    &balance_qty.to_string().green(), symbol.green(), &prices[2][1].to_string().yellow());
-    match account.limit_sell(symbol, balance_qty, prices[2][1]) {
-    // match account.market_sell(symbol, balance_qty) {
-        Ok(answer) => order_result = polling_order(&account, &market, answer.order_id, balance_qty, symbol, _current_balance, &final_ring, &ring_component, &quantity_info, false, false),
-        Err(e) => { 
-            format_error(e.0); 
-            return None; // Error
-        }
-    }
-    match order_result {
-        Some(_) => {
-            balance_qty = get_balance(&account, &ring_component.stablecoin).unwrap();
-            format_result(balance_qty, &ring_component.stablecoin, &benchmark);
-        }
-        None => return Some(-1.0) // None can help to break + stop App. 
-    }
-
-    return Some(balance_qty);
+    &qty.to_string().green(), symbol.green(), &prices[2].to_string().yellow());
+    let mut leg_c = run.send(symbol, Side::Sell, order_policy.legs[2], qty, prices[2], 'c')?;
+    run.wait(&mut leg_c, |order| order.polls > DROP_ORDER_LAST)?;
+    if leg_c.remaining() > 0.0 { run.unwind(market, symbol, leg_c.remaining(), 'y')?; }
+
+    let balance_qty = get_balance(run.account, run.user_data, &ring_component.stablecoin)?;
+    format_result(balance_qty, &ring_component.stablecoin, &benchmark);
+    return Ok(Some(balance_qty));
 }
 
-/// Execute best ring found in previous round result.
-pub fn execute_final_ring_pallarel(account: &Account, market: &Market, ring_component: &RingComponent, final_ring: &Vec<String>, 
-    prices: &Vec<[f64;2]>, config_invest: f64, quantity_info: HashMap<String, QuantityInfo>) -> Option<f64> {
+/// Execute best ring found in previous round result, 1st leg then the 2 others at once.
+/// Legs 2 & 3 trade what leg 1 filled, what they leave unfilled is inventory drift for the rebalancer.
+pub fn execute_final_ring_pallarel(run: &mut Execution, _market: &Market, ring_component: &RingComponent, final_ring: &[String],
+    prices: &[f64;3], config_invest: f64, order_policy: &OrderPolicy) -> Result<Option<f64>> {
     
     let benchmark = SystemTime::now();
     println!("> -------------------------------------------------- <");
-    //> for testing purpose.
-    // return Some(0.0);
-
-    // states
-    let mut order_result:Vec<(&str, u64)> = vec![];
 
-    // correct lots + step_size
-    let mut symbol:&str;
-    // let mut step_qty:f64 = 0.0;
-    // let mut step_price:f64 = 0.0;
-    let mut balance_qty:f64 = 0.0;
-    let mut custom_price:f64 = 0.0;
-    
     // prepare balance 
-    let _current_balance = get_balance(&account, &ring_component.stablecoin).unwrap(); println!();
-    if _current_balance < config_invest / 5.0 { return None; } // Break because this will be serious error w/ only 20%
+    let _current_balance = get_balance(run.account, run.user_data, &ring_component.stablecoin)?; println!();
+    if _current_balance < config_invest / 5.0 { // Break because this will be serious error w/ only 20%
+        return Err(Error::Halt(format!("{} {} left, under 20% of investment", _current_balance, ring_component.stablecoin)));
+    }
     let optimal_invest = if config_invest > _current_balance { _current_balance } else { config_invest };
 
-
-    // step_qty = quantity_info[symbol].step_qty;
-    // step_price = quantity_info[symbol].step_price;
-    //
-    // 1. Sell OOKI-BTC
-    //
-    let order_qty_a = correct_lots_qty(&final_ring[0], optimal_invest/(prices[0][0]), &quantity_info); // which result in a (symbol)
-    let order_qty_b = correct_lots_qty(&final_ring[2], order_qty_a * prices[1][0], &quantity_info);    // which result in b (bridge)
-    let order_qty_c = correct_lots_qty(&final_ring[0], order_qty_b * prices[2][0], &quantity_info); // which result in c (stablecoin)
+    let order_qty_a = correct_lots_qty(&final_ring[0], optimal_invest/(prices[0]), run.quantity_info)?; // which result in a (symbol)
+    let order_qty_b = correct_lots_qty(&final_ring[2], order_qty_a * prices[1], run.quantity_info)?;    // which result in b (bridge)
+    let order_qty_c = correct_lots_qty(&final_ring[0], order_qty_b * prices[2], run.quantity_info)?; // which result in c (stablecoin)
     println!("> qty_result: {} -> {} -> {} -> {}", optimal_invest, order_qty_b, order_qty_a, order_qty_c);
 
-    if IS_TESTING { return Some(0.0); }
+    if IS_TESTING { return Ok(Some(0.0)); }
 
-    let mut first_order:Option<f64> = None;
-
-    symbol = &final_ring[1];
-    balance_qty = order_qty_a; 
     //
-    println!("> limit_sell: {} {} at {}", &balance_qty.to_string().green(), 
-    symbol.green(), &prices[1][0].to_string().yellow());
-    match account.limit_sell(symbol, balance_qty, prices[1][0]) {
-        Ok(answer) => {
-            first_order = polling_order(&account, &market, answer.order_id, balance_qty, 
-            symbol, _current_balance, &final_ring, &ring_component, &quantity_info, true, false);
-            },
-        Err(e) => format_error(e.0)
-    }
-    match first_order {
-        Some(executed_qty) => {
-            balance_qty = executed_qty;
-            format_result(balance_qty, &ring_component.symbol, &benchmark);
-        },
-        None => return Some(-1.0) 
-    }
+    // 1. Sell OOKI-BTC
+    //
+    let symbol = &final_ring[1];
+    println!("> limit_sell: {} {} at {}", &order_qty_a.to_string().green(), 
+    symbol.green(), &prices[1].to_string().yellow());
+    let mut leg_b = run.send(symbol, Side::Sell, order_policy.legs[1], order_qty_a, prices[1], 'b')?;
+    run.wait(&mut leg_b, is_stale)?;
+    if leg_b.executed_qty <= 0.0 { return Ok(None); }
+    format_result(leg_b.executed_qty, &ring_component.symbol, &benchmark);
     //
-    // 2. Buy OOKI-BUSD
+    // 2. Buy OOKI-BUSD, what leg 1 sold.
     //
-    symbol = &final_ring[0];
-    balance_qty = order_qty_a;
-    // 
+    let symbol = &final_ring[0];
+    let qty = correct_lots_qty(symbol, leg_b.executed_qty, run.quantity_info)?;
     println!("> limit_buy: {} {} at {}", 
-    &balance_qty.to_string().green(), symbol.green(), &prices[0][0].to_string().yellow());
-    match account.limit_buy(symbol, balance_qty, prices[0][0]) {
-        Ok(answer) => order_result.push((symbol, answer.order_id)),
-        Err(e) => { 
-            format_error(e.0);
-            return None
-        },
-    }
-
+    &qty.to_string().green(), symbol.green(), &prices[0].to_string().yellow());
+    let mut leg_a = run.send(symbol, Side::Buy, order_policy.legs[0], qty, prices[0], 'a')?;
     //
-    // 3. Sell BTC-BUSD
+    // 3. Sell BTC-BUSD, what leg 1 got.
     //
-    symbol = &final_ring[2];
-    balance_qty = order_qty_b;
+    let symbol = &final_ring[2];
+    let qty = correct_lots_qty(symbol, leg_b.quote_qty, run.quantity_info)?;
     println!("> limit_sell: {} {} at {}", 
-    &balance_qty.to_string().green(), symbol.green(), &prices[2][0].to_string().yellow());
-    match account.limit_sell(symbol, balance_qty, prices[2][0]) {
-        Ok(answer) => order_result.push((symbol, answer.order_id)),
-        Err(e) => { 
-            format_error(e.0); 
-            return None; // Error
-        }
-    }
+    &qty.to_string().green(), symbol.green(), &prices[2].to_string().yellow());
+    let mut leg_c = run.send(symbol, Side::Sell, order_policy.legs[2], qty, prices[2], 'c')?;
     // wait till all finished.
-    loop {
-        let mut new_order_result = order_result.clone();
-        let mut finish_count = 0;
-        let mut index = 0;
-        for result in &order_result {
-            match account.order_status(result.0, result.1) {
-                Ok(answer) => {
-                    match answer.status.as_str() {
-                        "FILLED" => {
-                            println!("> #{} finished with {} {}.", result.1, answer.executed_qty, result.0);
-                            new_order_result.remove(index);
-                            finish_count+= 1;
-                        },
-                        _ => {}
-                    };
-                },
-                Err(e) => format_error(e.0)
-            }
-            index+=1;
-        }
-        if finish_count == order_result.len() {break}
-        else { // reset counter + sleep
-            index = 0;
-            finish_count = 0;
-            order_result = new_order_result.clone();
-            thread::sleep(POLLING_ORDER);
+    run.wait(&mut leg_a, |order| order.polls > DROP_ORDER_LAST)?;
+    run.wait(&mut leg_c, |order| order.polls > DROP_ORDER_LAST)?;
+    if leg_a.is_filled() && leg_c.is_filled() { println!("> all finished."); }
+    else { println!("> finished with {:?} {} & {:?} {}, rebalancer takes the rest.", leg_a.state, leg_a.symbol, leg_c.state, leg_c.symbol); }
+
+    return get_balance(run.account, run.user_data, &ring_component.stablecoin).map(Some);
+}
+
+/// Execute one order : sent as its kind, cancelled once it rested more than `max_polls`, fills up to the cancel kept.
+/// Base qty executed, or None when nothing filled.
+pub fn execute_order(run: &mut Execution, intent: &OrderIntent) -> Result<Option<f64>> {
+
+    let benchmark = SystemTime::now();
+    println!("> -------------------------------------------------- <");
+    if IS_TESTING { return Ok(Some(0.0)); }
+    let symbol = &intent.symbol;
+    let qty = correct_lots_qty(symbol, intent.qty, run.quantity_info)?;
+    let price = correct_price_filter(symbol, run.quantity_info, intent.price)?;
+    if qty <= 0.0 { println!("> order: {} {} is under LOT_SIZE.", intent.qty, symbol); return Ok(None); }
+    println!("> {}: {} {} at {}", format!("{:?}", intent.side).to_lowercase(),
+    &qty.to_string().green(), symbol.green(), price.to_string().yellow());
+    let mut order = run.send(symbol, intent.side, intent.kind, qty, price, 'a')?;
+    let max_polls = intent.max_polls;
+    run.wait(&mut order, |order| order.polls > max_polls)?;
+    if order.executed_qty <= 0.0 { return Ok(None); }
+    format_result(order.executed_qty, symbol, &benchmark);
+    return Ok(Some(order.executed_qty));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use binance::api::Binance;
+    use binance::config::Config;
+    use configparser::ini::Ini;
+    use crate::error::Action;
+    use crate::exchangeinfo;
+    use crate::mock::{Fault, FillRule, MockExchange, MockOrder, Scenario};
+
+    const SCENARIO:&str = include_str!("../../tests/fixtures/scenario.json");
+    const RING:[&str;3] = ["XTZBUSD", "XTZBNB", "BNBBUSD"];
+    const PRICES:[f64;3] = [1.0, 0.0034, 300.0];
+
+    struct Exchange {
+        mock: MockExchange,
+        account: Account,
+        market: Market,
+        quantity_info: HashMap<String, QuantityInfo>
+    }
+
+    /// Mock started from the fixture scenario, `fills` rules, `faults` & `inventory` balances added.
+    fn exchange(fills: &[(&str, &str, f64)], faults: Vec<Fault>, inventory: &[(&str, f64)]) -> Exchange {
+        let mut scenario: Scenario = serde_json::from_str(SCENARIO).unwrap();
+        for (asset, qty) in inventory { scenario.balances.insert(asset.to_string(), *qty); }
+        for (symbol, mode, ratio) in fills {
+            scenario.fills.insert(symbol.to_string(), FillRule { mode: mode.to_string(), after_polls: 1, ratio: *ratio });
         }
+        scenario.faults = faults;
+        let mock = MockExchange::start(scenario).unwrap();
+        let endpoint = Config::default().set_rest_api_endpoint(mock.url.clone());
+        let account: Account = Binance::new_with_config(Some("key".into()), Some("secret".into()), &endpoint);
+        let market: Market = Binance::new_with_config(None, None, &endpoint);
+        let symbols: Vec<String> = RING.iter().map(|symbol| symbol.to_string()).collect();
+        let quantity_info = exchangeinfo::fetch_quantity_from(&mock.url, &symbols).unwrap();
+        return Exchange { mock, account, market, quantity_info };
+    }
+
+    /// Run the XTZ ring for 100 BUSD at the fixture prices.
+    fn execute(exchange: &Exchange, fills: &mut Vec<Fill>, is_parallel: bool) -> Result<Option<f64>> {
+        return execute_with(exchange, fills, is_parallel, &Ini::new());
     }
-    println!("> all finished.");
 
-    return Some(get_balance(&account, &ring_component.stablecoin).unwrap());
-}
\ No newline at end of file
+    /// Same, `[orders]` & `[retry]` taken from `config`.
+    fn execute_with(exchange: &Exchange, fills: &mut Vec<Fill>, is_parallel: bool, config: &Ini) -> Result<Option<f64>> {
+        let ring_component = RingComponent { symbol: String::from("XTZ"), bridge: String::from("BNB"), stablecoin: String::from("BUSD") };
+        let final_ring: Vec<String> = RING.iter().map(|symbol| symbol.to_string()).collect();
+        let (user_data, tag, retry) = (UserData::disabled(), RingTag::new("test", 0, "XTZ"), RetryPolicy::from_config(config));
+        let mut run = Execution { account: &exchange.account, user_data: &user_data, quantity_info: &exchange.quantity_info, tag: &tag, retry: &retry, fills };
+        let execute = if is_parallel { execute_final_ring_pallarel } else { execute_final_ring };
+        return execute(&mut run, &exchange.market, &ring_component, &final_ring, &PRICES, 100.0, &OrderPolicy::from_config(config));
+    }
+
+    fn order(orders: &[MockOrder], leg: char) -> &MockOrder {
+        return orders.iter().find(|order| order.client_order_id.ends_with(&format!("-{}", leg))).unwrap();
+    }
+
+    fn approx(a: f64, b: f64) -> bool { return (a - b).abs() < 1e-6; }
+
+    #[test]
+    fn ring_fills_all_legs() {
+        let exchange = exchange(&[], vec![], &[]);
+        let mut fills = vec![];
+        let balance = execute(&exchange, &mut fills, false).unwrap().unwrap();
+        // 100 XTZ, 99.9 after fee > 0.33966 BNB, 0.339 lots > 101.7 BUSD, 101.5983 after fee.
+        assert!(approx(balance, 1001.5983), "balance {}", balance);
+        assert_eq!(fills.len(), 3);
+        let orders = exchange.mock.orders();
+        assert_eq!(orders.len(), 3);
+        assert!(orders.iter().all(|order| order.status == "FILLED"));
+        assert_eq!(order(&orders, 'a').client_order_id, "rg-test-0-XTZ-a");
+        assert!(approx(order(&orders, 'b').qty, 99.9));
+        assert!(approx(order(&orders, 'c').qty, 0.339));
+    }
+
+    #[test]
+    fn resting_first_leg_drops_ring() {
+        let exchange = exchange(&[("XTZBUSD", "rest", 0.0)], vec![], &[]);
+        let mut fills = vec![];
+        assert!(execute(&exchange, &mut fills, false).unwrap().is_none());
+        assert!(fills.is_empty());
+        let orders = exchange.mock.orders();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].status, "CANCELED");
+        assert_eq!(exchange.mock.balance("BUSD"), 1000.0);
+    }
+
+    #[test]
+    fn partial_first_leg_goes_on_with_what_filled() {
+        let exchange = exchange(&[("XTZBUSD", "partial", 0.5)], vec![], &[]);
+        let mut fills = vec![];
+        assert!(execute(&exchange, &mut fills, false).unwrap().is_some());
+        let orders = exchange.mock.orders();
+        let leg_a = order(&orders, 'a');
+        assert_eq!(leg_a.status, "CANCELED");
+        assert!(approx(leg_a.executed_qty, 50.0));
+        // less fee
+        assert!(approx(order(&orders, 'b').qty, 49.95));
+        assert_eq!(fills.len(), 3);
+    }
+
+    #[test]
+    fn partial_second_leg_is_unwound() {
+        let exchange = exchange(&[("XTZBNB", "partial", 0.6)], vec![], &[]);
+        let mut fills = vec![];
+        assert!(execute(&exchange, &mut fills, false).unwrap().is_some());
+        let orders = exchange.mock.orders();
+        let leg_b = order(&orders, 'b');
+        assert_eq!(leg_b.status, "CANCELED");
+        assert!(approx(leg_b.executed_qty, 59.94));
+        let unwind = order(&orders, 'x');
+        assert_eq!((unwind.symbol.as_str(), unwind.side.as_str(), unwind.kind.as_str()), ("XTZBUSD", "SELL", "MARKET"));
+        assert!(approx(unwind.qty, 39.96));
+        assert_eq!(unwind.status, "FILLED");
+        assert!(exchange.mock.balance("XTZ") < 0.01);
+        assert_eq!(fills.len(), 4);
+    }
+
+    #[test]
+    fn rejected_leg_refreshes_balances() {
+        let exchange = exchange(&[("XTZBUSD", "reject", 0.0)], vec![], &[]);
+        let mut fills = vec![];
+        let error = execute(&exchange, &mut fills, false).unwrap_err();
+        assert_eq!(RetryPolicy::from_config(&Ini::new()).classify(&error), Action::RefreshBalances);
+        assert!(fills.is_empty());
+        assert!(exchange.mock.orders().is_empty());
+    }
+
+    #[test]
+    fn unsupported_kind_skips_ring() {
+        let mut exchange = exchange(&[], vec![], &[]);
+        exchange.quantity_info.get_mut("XTZBUSD").unwrap().order_types = vec![String::from("LIMIT")];
+        let mut config = Ini::new();
+        config.set("orders", "leg_a", Some(String::from("limit_maker")));
+        let mut fills = vec![];
+        let error = execute_with(&exchange, &mut fills, false, &config).unwrap_err();
+        assert!(matches!(error.root(), Error::Unsupported { .. }));
+        assert_eq!(RetryPolicy::from_config(&config).classify(&error), Action::SkipRing);
+        // never sent as GTC instead.
+        assert!(exchange.mock.orders().is_empty());
+    }
+
+    #[test]
+    fn order_placed_on_timeout_is_found_again() {
+        let timeout = Fault { method: String::from("POST"), path: String::from("/api/v3/order"), status: 400, code: -1007,
+            msg: String::from("Timeout waiting for response from backend server."), times: 1, delay_ms: 0, place_anyway: true };
+        let exchange = exchange(&[], vec![timeout], &[]);
+        let mut fills = vec![];
+        assert!(execute(&exchange, &mut fills, false).unwrap().is_some());
+        let orders = exchange.mock.orders();
+        assert_eq!(orders.iter().filter(|order| order.symbol == "XTZBUSD").count(), 1);
+        assert_eq!(orders.len(), 3);
+    }
+
+    #[test]
+    fn parallel_ring_trades_from_inventory() {
+        // leg b sells symbol held before leg a buys it back.
+        let exchange = exchange(&[], vec![], &[("XTZ", 100.0), ("BNB", 1.0)]);
+        let mut fills = vec![];
+        assert!(execute(&exchange, &mut fills, true).unwrap().is_some());
+        assert_eq!(fills.len(), 3);
+        assert!(exchange.mock.orders().iter().all(|order| order.status == "FILLED"));
+    }
+
+    #[test]
+    fn lots_and_prices_round_down() {
+        let exchange = exchange(&[], vec![], &[]);
+        let quantity_info = &exchange.quantity_info;
+        assert_eq!(correct_lots_qty("XTZBNB", 0.29, quantity_info).unwrap(), 0.29);
+        assert_eq!(correct_lots_qty("XTZBNB", 99.999, quantity_info).unwrap(), 99.99);
+        assert_eq!(correct_lots_qty("BNBBUSD", 0.33966, quantity_info).unwrap(), 0.339);
+        assert_eq!(correct_price_filter("XTZBUSD", quantity_info, 1.0049).unwrap(), 1.004);
+        assert!(matches!(correct_lots_qty("DOTBUSD", 1.0, quantity_info), Err(Error::MissingSymbol(_))));
+    }
+}
@@ -0,0 +1,279 @@
+use std::{
+    thread,
+    collections::BTreeMap,
+    time::Duration};
+
+use configparser::ini::Ini;
+use serde::Deserialize;
+
+use binance::account::Account;
+
+use crate::rest::SignedClient;
+use super::Side;
+use super::snapshot::{Ring, Snapshot, SymbolId};
+
+const COMMISSION_CACHE_FILE:&str = "commission.cache";
+const COMMISSION_PACING: Duration = Duration::from_millis(250); // weight 20 per symbol
+const DEFAULT_FEES:f64 = 0.1;        // percent, [configs] binance_fees when account is unknown
+const DEFAULT_BNB_DISCOUNT:f64 = 0.75; // pay 75% of the fee in BNB
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Rates {
+    maker: String,
+    taker: String
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Discount {
+    enabled_for_account: bool,
+    enabled_for_symbol: bool,
+    discount: String
+}
+
+/// GET /api/v3/account/commission
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Commission {
+    standard_commission: Rates,
+    tax_commission: Rates,
+    discount: Discount
+}
+
+/// Fees of a ring at given prices.
+pub struct RingFees {
+    pub out: f64,     // stablecoin back after the 3rd leg
+    pub fees: f64,    // all fees, valued in stablecoin
+    pub in_bnb: f64   // part of `fees` paid in BNB, not taken from `out`
+}
+
+/// Commission of every symbol of `snapshot`, as `commission.cache` keeps it, None when the endpoint fails.
+fn fetch_commissions(config: &Ini, snapshot: &Snapshot, recv_window: u64) -> Option<Ini> {
+    println!("> fees: fetching commission of {} symbols...", snapshot.symbols.len());
+    let client = match SignedClient::from_config(config, recv_window) {
+        Ok(client) => client,
+        Err(e) => { println!("> fees: no commission endpoint, use account rates: {}", e); return None; }
+    };
+    let mut cache_file = Ini::new();
+    for symbol in &snapshot.symbols {
+        let mut parameters = BTreeMap::new();
+        parameters.insert(String::from("symbol"), symbol.clone());
+        match client.get_signed::<Commission>("/api/v3/account/commission", parameters) {
+            Ok(commission) => {
+                let rate = |standard: &str, tax: &str| standard.parse::<f64>().unwrap_or(0.0) + tax.parse::<f64>().unwrap_or(0.0);
+                let maker = rate(&commission.standard_commission.maker, &commission.tax_commission.maker);
+                let taker = rate(&commission.standard_commission.taker, &commission.tax_commission.taker);
+                let discount = &commission.discount;
+                cache_file.set("commission", symbol, Some(format!("{},{}", maker, taker)));
+                cache_file.set("discount", "enabled", Some((discount.enabled_for_account && discount.enabled_for_symbol).to_string()));
+                cache_file.set("discount", "ratio", Some(discount.discount.clone()));
+            },
+            Err(e) => { println!("> fees: no commission endpoint, use account rates: {}", e); return None; }
+        }
+        thread::sleep(COMMISSION_PACING);
+    }
+    return Some(cache_file);
+}
+
+/// Maker / taker commission of every symbol, as fractions.
+pub struct FeeModel {
+    maker: Vec<f64>,
+    taker: Vec<f64>,
+    pay_with_bnb: bool,
+    bnb_discount: f64
+}
+
+impl FeeModel {
+    /// Account maker/taker rates, per-symbol commissions ( cached in `commission.cache` )
+    /// and the BNB discount. `[fees]` pay_with_bnb, bnb_discount and per_symbol override,
+    /// `[configs] binance_fees` is used when the account can't be read.
+    pub fn load(config: &Ini, account: &Account, snapshot: &Snapshot) -> FeeModel {
+        let fallback = config.getfloat("configs", "binance_fees").unwrap_or(None).unwrap_or(DEFAULT_FEES) / 100.0;
+        let (maker, taker) = match account.get_account() {
+            // commission in basis points, etc: 10 = 0.1%
+            Ok(answer) => (answer.maker_commission as f64 / 10_000.0, answer.taker_commission as f64 / 10_000.0),
+            Err(e) => { println!("> fees: can't read account, use {}%: {:?}", fallback * 100.0, e.0); (fallback, fallback) }
+        };
+        let mut model = FeeModel::flat(snapshot, maker, taker);
+        if config.getbool("fees", "per_symbol").unwrap_or(None).unwrap_or(true) {
+            model.load_commissions(config, snapshot, account.recv_window);
+        }
+        if let Ok(Some(pay_with_bnb)) = config.getbool("fees", "pay_with_bnb") { model.pay_with_bnb = pay_with_bnb; }
+        if let Ok(Some(bnb_discount)) = config.getfloat("fees", "bnb_discount") { model.bnb_discount = bnb_discount; }
+        println!("> fees: maker {:.4}% | taker {:.4}% | bnb {} x{}.", maker * 100.0, taker * 100.0, model.pay_with_bnb, model.bnb_discount);
+        return model;
+    }
+
+    /// Same `maker` & `taker` rates on every symbol of `snapshot`, nothing paid in BNB.
+    pub fn flat(snapshot: &Snapshot, maker: f64, taker: f64) -> FeeModel {
+        let count = snapshot.symbols.len();
+        return FeeModel { maker: vec![maker; count], taker: vec![taker; count], pay_with_bnb: false, bnb_discount: DEFAULT_BNB_DISCOUNT };
+    }
+
+    /// Per-symbol rates from cache, or from the account one symbol at a time.
+    fn load_commissions(&mut self, config: &Ini, snapshot: &Snapshot, recv_window: u64) {
+        let mut cache_file = Ini::new();
+        if cache_file.load(COMMISSION_CACHE_FILE).is_err() {
+            cache_file = match fetch_commissions(config, snapshot, recv_window) {
+                Some(fetched) => fetched,
+                None => return
+            };
+            if let Err(msg) = cache_file.write(COMMISSION_CACHE_FILE) {
+                println!("Error saving cache: {:?}", msg);
+            }
+        }
+        self.apply_commissions(&cache_file, snapshot);
+        println!("> loaded commissions.");
+    }
+
+    /// `[commission]` SYMBOL = maker,taker overrides account rates, `[discount]` enabled & ratio the BNB discount.
+    fn apply_commissions(&mut self, cache_file: &Ini, snapshot: &Snapshot) {
+        for (id, symbol) in snapshot.symbols.iter().enumerate() {
+            if let Some(rates) = cache_file.get("commission", symbol) {
+                let rates: Vec<f64> = rates.split(',').map(|rate| rate.parse::<f64>().unwrap_or(0.0)).collect();
+                if rates.len() == 2 { self.maker[id] = rates[0]; self.taker[id] = rates[1]; }
+            }
+        }
+        if let Ok(Some(enabled)) = cache_file.getbool("discount", "enabled") { self.pay_with_bnb = enabled; }
+        if let Ok(Some(ratio)) = cache_file.getfloat("discount", "ratio") { self.bnb_discount = ratio; }
+    }
+
+    /// Fraction charged on a fill of `id`, before BNB discount.
+    pub fn rate(&self, id: SymbolId, is_maker: bool) -> f64 {
+        return if is_maker { self.maker[id] } else { self.taker[id] };
+    }
+
+    /// Fee taken out of `received` by a fill of `id`, as taker ( the most it can be ), none when paid aside in BNB.
+    pub fn fill_fee(&self, id: SymbolId, received: f64) -> f64 {
+        return if self.pay_with_bnb { 0.0 } else { received * self.taker[id] };
+    }
+
+    /// Fees of one order worth `value` in stablecoin at `price`, maker when it rests in the book.
+    pub fn order(&self, id: SymbolId, book: &[f64;2], side: Side, price: f64, value: f64) -> RingFees {
+        let is_maker = if side == Side::Buy { price < book[1] } else { price > book[0] };
+        let rate = self.rate(id, is_maker);
+        if self.pay_with_bnb {
+            let fee = value * rate * self.bnb_discount;
+            return RingFees { out: value, fees: fee, in_bnb: fee };
+        }
+        return RingFees { out: value * (1.0 - rate), fees: value * rate, in_bnb: 0.0 };
+    }
+
+    /// Walk a ring from `invest` stablecoin at `prices`.
+    /// A leg resting in the book is maker, a leg crossing it ( or all when `is_taker` ) is taker.
+    /// Without BNB a fee comes out of what the leg receives, with BNB it's paid aside at a discount.
+    pub fn ring(&self, ring: &Ring, snapshot: &Snapshot, prices: &[f64;3], invest: f64, is_taker: bool) -> RingFees {
+        let mut qty = invest;   // what we hold before each leg
+        let mut value = invest; // same, in stablecoin
+        let mut fees = 0.0;
+        let mut in_bnb = 0.0;
+        for (leg, &id) in ring.legs.iter().enumerate() {
+            let book = &snapshot.books[id];
+            let is_buy = ring.sides[leg] == Side::Buy;
+            let is_maker = !is_taker && if is_buy { prices[leg] < book[1] } else { prices[leg] > book[0] };
+            let rate = self.rate(id, is_maker);
+            let received = if is_buy { qty / prices[leg] } else { qty * prices[leg] };
+            if self.pay_with_bnb {
+                let fee = value * rate * self.bnb_discount;
+                fees += fee;
+                in_bnb += fee;
+                qty = received;
+            } else {
+                fees += value * rate;
+                value *= 1.0 - rate;
+                qty = received * (1.0 - rate);
+            }
+        }
+        return RingFees { out: qty, fees, in_bnb };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::exchangeinfo;
+    use crate::analyzer::fixture::{self, BOOKS, EXCHANGE_INFO};
+    use crate::mock::{MockExchange, Scenario};
+
+    /// XTZ ring of the recorded exchangeInfo at `BOOKS`, maker 0.1% & taker 0.2% everywhere.
+    fn xtz() -> (FeeModel, Snapshot, Ring) {
+        let (snapshot, ring) = fixture::xtz(BOOKS, [1000.0; 3]);
+        return (FeeModel::flat(&snapshot, 0.001, 0.002), snapshot, ring);
+    }
+
+    #[test]
+    fn resting_orders_are_maker_crossing_ones_taker() {
+        let (model, snapshot, ring) = xtz();
+        let id = ring.legs[0];
+        let book = &snapshot.books[id];
+        assert_eq!(model.order(id, book, Side::Buy, 0.999, 100.0).fees, 100.0 * 0.001);
+        assert_eq!(model.order(id, book, Side::Buy, 1.0, 100.0).fees, 100.0 * 0.002);
+        assert_eq!(model.order(id, book, Side::Sell, 1.0, 100.0).fees, 100.0 * 0.001);
+        assert_eq!(model.order(id, book, Side::Sell, 0.999, 100.0).fees, 100.0 * 0.002);
+
+        // every leg resting : 3 maker fees, every leg crossing or `is_taker` : 3 taker fees.
+        let maker = model.ring(&ring, &snapshot, &[0.999, 0.003401, 300.1], 100.0, false);
+        let taker = model.ring(&ring, &snapshot, &[1.0, 0.0034, 300.0], 100.0, false);
+        let forced = model.ring(&ring, &snapshot, &[0.999, 0.003401, 300.1], 100.0, true);
+        assert!((maker.fees - 100.0 * (1.0 - 0.999_f64.powi(3))).abs() < 1e-9);
+        assert!((taker.fees - 100.0 * (1.0 - 0.998_f64.powi(3))).abs() < 1e-9);
+        assert!((forced.fees - taker.fees).abs() < 1e-9);
+        assert_eq!(maker.in_bnb, 0.0);
+    }
+
+    #[test]
+    fn per_symbol_commission_overrides_account_rates() {
+        let (mut model, snapshot, ring) = xtz();
+        let mut cache_file = Ini::new();
+        cache_file.set("commission", "XTZBNB", Some(String::from("0.0005,0.0007")));
+        model.apply_commissions(&cache_file, &snapshot);
+        assert_eq!((model.rate(ring.legs[1], true), model.rate(ring.legs[1], false)), (0.0005, 0.0007));
+        assert_eq!((model.rate(ring.legs[0], true), model.rate(ring.legs[0], false)), (0.001, 0.002));
+        assert!(!model.pay_with_bnb);
+    }
+
+    #[test]
+    fn commissions_are_fetched_per_symbol() {
+        let scenario: Scenario = serde_json::from_value(json!({ "fee_rate": 0.00075 })).unwrap();
+        let mock = MockExchange::start(scenario).unwrap();
+        let mut config = Ini::new();
+        config.set("endpoint", "preset", Some(String::from("custom")));
+        config.set("endpoint", "rest_endpoint", Some(mock.url.clone()));
+        config.set("endpoint", "ws_endpoint", Some(String::from("ws://127.0.0.1:1/ws")));
+        config.set("keys", "api_key", Some(String::from("key")));
+        config.set("keys", "secret_key", Some(String::from("secret")));
+        let mut snapshot = Snapshot::empty();
+        let quantity_info = exchangeinfo::quantity_from(EXCHANGE_INFO, &[String::from("XTZBUSD")]).unwrap();
+        snapshot.add_symbol("XTZBUSD", &quantity_info).unwrap();
+
+        let cache_file = fetch_commissions(&config, &snapshot, 10_000).unwrap();
+        assert_eq!(cache_file.get("commission", "XTZBUSD").unwrap(), "0.00075,0.00075");
+        assert_eq!(cache_file.getbool("discount", "enabled").unwrap(), Some(false));
+    }
+
+    #[test]
+    fn bnb_pays_fees_aside_at_a_discount() {
+        let (mut model, snapshot, ring) = xtz();
+        let mut cache_file = Ini::new();
+        cache_file.set("discount", "enabled", Some(String::from("true")));
+        cache_file.set("discount", "ratio", Some(String::from("0.75")));
+        model.apply_commissions(&cache_file, &snapshot);
+
+        let id = ring.legs[0];
+        let order = model.order(id, &snapshot.books[id], Side::Buy, 1.0, 100.0);
+        assert_eq!(order.out, 100.0);
+        assert!((order.fees - 100.0 * 0.002 * 0.75).abs() < 1e-12);
+        assert_eq!(order.in_bnb, order.fees);
+        assert_eq!(model.fill_fee(id, 100.0), 0.0);
+
+        // nothing comes out of the ring, fees of the 3 legs are paid in BNB.
+        let prices = [1.0, 0.0034, 300.0];
+        let with_bnb = model.ring(&ring, &snapshot, &prices, 100.0, true);
+        let without = FeeModel::flat(&snapshot, 0.001, 0.002).ring(&ring, &snapshot, &prices, 100.0, true);
+        assert!((with_bnb.in_bnb - with_bnb.fees).abs() < 1e-12);
+        assert!(with_bnb.out > without.out);
+        assert!(with_bnb.fees < without.fees);
+    }
+}
@@ -0,0 +1,24 @@
+use std::{
+    fs::OpenOptions,
+    io::Write};
+
+use crate::quarantine::now_ms;
+use super::strategy::Expected;
+
+const JOURNAL_FILE:&str = "journal.csv";
+const JOURNAL_HEADER:&str = "time_ms,symbol,client_order_id,mode,invest,expected_profit,fees,fees_in_bnb,realized_profit";
+
+/// Append one executed trade, expected against realized, to `journal.csv`.
+/// `client_order_id` is the prefix shared by all its orders, legs add `-a`, `-b`, `-c`.
+pub fn record(symbol: &str, client_order_id: &str, mode: &str, expected: &Expected, realized_profit: f64) {
+    let now = now_ms();
+    let is_new = !std::path::Path::new(JOURNAL_FILE).exists();
+    match OpenOptions::new().create(true).append(true).open(JOURNAL_FILE) {
+        Ok(mut file) => {
+            if is_new { let _ = writeln!(file, "{}", JOURNAL_HEADER); }
+            let _ = writeln!(file, "{},{},{},{},{:.4},{:.6},{:.6},{:.6},{:.6}",
+                now, symbol, client_order_id, mode, expected.invest, expected.profit, expected.fees, expected.fees_in_bnb, realized_profit);
+        },
+        Err(e) => println!("Error saving journal: {:?}", e)
+    }
+}
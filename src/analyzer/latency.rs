@@ -0,0 +1,183 @@
+use configparser::ini::Ini;
+
+use super::Side;
+use super::executor::Fill;
+use super::scoring::FillModel;
+use super::snapshot::{Ring, Snapshot};
+
+const LATENCY_CACHE_FILE:&str = "latency.cache";
+
+// default of [latency]
+const DEFAULT_LATENCY_MS:f64 = 500.0; // prices age until the 1st order is out
+const CONFIDENCE:f64 = 1.0;           // how many sigmas of drift the margin must survive
+const DRIFT_SMOOTHING:f64 = 0.1;      // EWMA, new block weight
+const LATENCY_SMOOTHING:f64 = 0.3;    // EWMA, new ring weight
+const SLIPPAGE_SMOOTHING:f64 = 0.3;   // EWMA, new fill weight
+
+/// How far prices drift while our orders are on their way, per symbol,
+/// estimated from blocks & fills recorded so far and kept in `latency.cache`.
+pub struct LatencyModel {
+    last_mid: Vec<f64>,
+    last_ms: Vec<u128>,
+    variance: Vec<f64>,   // EWMA of (relative mid change)^2 per ms, random walk
+    slippage: Vec<f64>,   // EWMA of adverse fill vs planned price, relative
+    latency_ms: f64,      // EWMA of prices age when the ring starts
+    confidence: f64
+}
+
+impl LatencyModel {
+    /// `[latency]` default_ms, confidence, sized for `snapshot` with history loaded from cache.
+    pub fn new(config: &Ini, snapshot: &Snapshot) -> LatencyModel {
+        let mut model = LatencyModel::with(snapshot,
+            config.getfloat("latency", "default_ms").unwrap_or(None).unwrap_or(DEFAULT_LATENCY_MS),
+            config.getfloat("latency", "confidence").unwrap_or(None).unwrap_or(CONFIDENCE));
+        let mut cache_file = Ini::new();
+        if cache_file.load(LATENCY_CACHE_FILE).is_ok() {
+            if let Ok(Some(latency_ms)) = cache_file.getfloat("latency", "ms") { model.latency_ms = latency_ms; }
+            for (id, symbol) in snapshot.symbols.iter().enumerate() {
+                if let Ok(Some(variance)) = cache_file.getfloat("variance", symbol) { model.variance[id] = variance; }
+                if let Ok(Some(slippage)) = cache_file.getfloat("slippage", symbol) { model.slippage[id] = slippage; }
+            }
+            println!("> loaded latency model.");
+        }
+        return model;
+    }
+
+    /// Model sized for `snapshot` expecting `latency_ms` before the 1st order, no history.
+    pub fn with(snapshot: &Snapshot, latency_ms: f64, confidence: f64) -> LatencyModel {
+        let count = snapshot.symbols.len();
+        return LatencyModel { last_mid: vec![0.0; count], last_ms: vec![0; count], variance: vec![0.0; count],
+            slippage: vec![0.0; count], latency_ms, confidence };
+    }
+
+    /// Track price drift per ms, once per block after tickers are updated.
+    pub fn observe(&mut self, snapshot: &Snapshot, now_ms: u128) {
+        for id in 0..snapshot.symbols.len() {
+            if !snapshot.updated[id] { continue; }
+            let book = &snapshot.books[id];
+            let mid = (book[0] + book[1]) / 2.0;
+            if self.last_mid[id] > 0.0 && now_ms > self.last_ms[id] {
+                let change = (mid - self.last_mid[id]) / self.last_mid[id];
+                let variance = change * change / (now_ms - self.last_ms[id]) as f64;
+                self.variance[id] = self.variance[id] * (1.0 - DRIFT_SMOOTHING) + variance * DRIFT_SMOOTHING;
+            }
+            self.last_mid[id] = mid;
+            self.last_ms[id] = now_ms;
+        }
+    }
+
+    /// Record how old prices were when a ring went out, and how far each leg filled from its planned price.
+    pub fn record(&mut self, ring: &Ring, snapshot: &Snapshot, prices: &[f64;3], latency_ms: f64, fills: &[Fill]) {
+        self.latency_ms = self.latency_ms * (1.0 - LATENCY_SMOOTHING) + latency_ms * LATENCY_SMOOTHING;
+        for (leg, price) in prices.iter().enumerate() {
+            // 1st fill of each pair is the leg, later ones are short-selling.
+            let fill = match fills.iter().find(|fill| fill.symbol == ring.pairs[leg]) {
+                Some(fill) if fill.avg_price > 0.0 => fill,
+                _ => continue
+            };
+            let id = ring.legs[leg];
+            let slippage = match ring.sides[leg] {
+                Side::Buy => (fill.avg_price - price) / price,
+                Side::Sell => (price - fill.avg_price) / price
+            };
+            println!("> slippage: {} {:.4}%", snapshot.symbols[id], slippage * 100.0);
+            self.slippage[id] = self.slippage[id] * (1.0 - SLIPPAGE_SMOOTHING) + slippage * SLIPPAGE_SMOOTHING;
+        }
+        self.save(snapshot);
+    }
+
+    fn save(&self, snapshot: &Snapshot) {
+        let mut cache_file = Ini::new();
+        cache_file.set("latency", "ms", Some(format!("{:.0}", self.latency_ms)));
+        for (id, symbol) in snapshot.symbols.iter().enumerate() {
+            if self.variance[id] > 0.0 { cache_file.set("variance", symbol, Some(format!("{:e}", self.variance[id]))); }
+            if self.slippage[id] != 0.0 { cache_file.set("slippage", symbol, Some(format!("{:e}", self.slippage[id]))); }
+        }
+        if let Err(msg) = cache_file.write(LATENCY_CACHE_FILE) {
+            println!("Error saving cache: {:?}", msg);
+        }
+    }
+
+    /// Expected cost in stablecoin of a ring by the time each leg is placed :
+    /// legs go out one after another, so leg N waits for the fills before it.
+    pub fn cost(&self, ring: &Ring, fill_model: &FillModel, invest: f64) -> f64 {
+        let mut delay_ms = self.latency_ms;
+        let mut cost = 0.0;
+        for &id in &ring.legs {
+            let drift = self.confidence * (self.variance[id] * delay_ms).sqrt();
+            cost += invest * (drift + self.slippage[id]);
+            delay_ms += fill_model.fill_ms(id);
+        }
+        return cost;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::{analyze_ring, RingModels};
+    use crate::analyzer::fees::FeeModel;
+    use crate::analyzer::fixture::{self, BOOKS};
+    use crate::analyzer::pricing::PricingPolicy;
+
+    /// XTZ ring of the recorded exchangeInfo at the recorded books.
+    fn xtz() -> (Snapshot, Ring) {
+        return fixture::xtz(BOOKS, [1e9; 3]);
+    }
+
+    /// Drift of `symbol` when its mid moved by `change` ( relative ) in 1 s.
+    fn observe_move(model: &mut LatencyModel, snapshot: &mut Snapshot, symbol: &str, change: f64) {
+        let books = snapshot.books.clone();
+        model.observe(snapshot, 1_000);
+        let book = snapshot.books[snapshot.ids[symbol]];
+        let mid = (book[0] + book[1]) / 2.0;
+        snapshot.reset();
+        snapshot.set_book(symbol, [book[0] + mid * change, book[1] + mid * change], [1e9, 1e9]);
+        model.observe(snapshot, 2_000);
+        for (symbol, book) in snapshot.symbols.clone().iter().zip(books) { snapshot.set_book(symbol, book, [1e9, 1e9]); }
+    }
+
+    #[test]
+    fn no_history_costs_nothing() {
+        let (snapshot, ring) = xtz();
+        let model = LatencyModel::with(&snapshot, 500.0, 1.0);
+        assert_eq!(model.cost(&ring, &FillModel::empty(&snapshot), 100.0), 0.0);
+    }
+
+    #[test]
+    fn later_legs_drift_while_earlier_ones_fill() {
+        let (mut snapshot, ring) = xtz();
+        let mut model = LatencyModel::with(&snapshot, 500.0, 2.0);
+        // variance 0.1 x 0.02² / 1000 ms on leg c only.
+        observe_move(&mut model, &mut snapshot, "BNBBUSD", 0.02);
+        let variance:f64 = 0.1 * 0.0004 / 1000.0;
+        let fill_model = FillModel::empty(&snapshot);
+        assert!((model.cost(&ring, &fill_model, 100.0) - 100.0 * 2.0 * (variance * 500.0).sqrt()).abs() < 1e-9);
+        // leg c goes out once leg a filled.
+        let mut fill_model = FillModel::empty(&snapshot);
+        fill_model.record_fill(&snapshot, "XTZBUSD", 1500.0);
+        assert!((model.cost(&ring, &fill_model, 100.0) - 100.0 * 2.0 * (variance * 2000.0).sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn drift_is_deducted_down_to_the_minimum_margin() {
+        let (mut snapshot, ring) = xtz();
+        let policy = PricingPolicy::preset("market").unwrap();
+        let fee_model = FeeModel::flat(&snapshot, 0.001, 0.001);
+        let fill_model = FillModel::empty(&snapshot);
+        let analyze = |snapshot: &Snapshot, latency: &LatencyModel|
+            analyze_ring(0, &ring, 100.0, snapshot, RingModels { policy: &policy, fill: &fill_model, fees: &fee_model, latency });
+        let calm = LatencyModel::with(&snapshot, 500.0, 1.0);
+        let profit = analyze(&snapshot, &calm).unwrap().profit;
+        // 2% moves : 0.45 of the 1.69 margin goes to drift.
+        let mut moving = LatencyModel::with(&snapshot, 500.0, 1.0);
+        observe_move(&mut moving, &mut snapshot, "XTZBUSD", 0.02);
+        let cost = moving.cost(&ring, &fill_model, 100.0);
+        assert!((cost - 100.0 * (0.1 * 0.0004 / 1000.0 * 500.0f64).sqrt()).abs() < 1e-9);
+        assert!((analyze(&snapshot, &moving).unwrap().profit - (profit - cost)).abs() < 1e-9);
+        // 10% moves : under 0.5% left, not a trade.
+        let mut wild = LatencyModel::with(&snapshot, 500.0, 1.0);
+        observe_move(&mut wild, &mut snapshot, "XTZBUSD", 0.1);
+        assert!(analyze(&snapshot, &wild).is_none());
+    }
+}
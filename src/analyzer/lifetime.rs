@@ -0,0 +1,169 @@
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    collections::HashMap};
+
+use configparser::ini::Ini;
+
+use crate::quarantine::now_ms;
+use super::RingResult;
+use super::snapshot::Ring;
+
+const LIFETIME_FILE:&str = "lifetimes.csv";
+const LIFETIME_HEADER:&str = "symbol,started_ms,ended_ms,duration_ms,blocks,peak_profit,avg_profit,peak_percentage,avg_percentage";
+
+/// One profitable streak of a ring, from the block it became profitable until it's not.
+#[derive(Clone)]
+pub struct Opportunity {
+    pub started_ms: u128,
+    pub last_ms: u128,
+    pub blocks: u32,
+    pub peak_profit: f64,
+    pub peak_percentage: f64,
+    sum_profit: f64,
+    sum_percentage: f64
+}
+
+impl Opportunity {
+    pub fn duration_ms(&self) -> u128 { return self.last_ms - self.started_ms; }
+    pub fn avg_profit(&self) -> f64 { return self.sum_profit / self.blocks as f64; }
+    pub fn avg_percentage(&self) -> f64 { return self.sum_percentage / self.blocks as f64; }
+}
+
+/// Track every ring's opportunity lifetime across blocks,
+/// gate execution on it and export finished streaks to `lifetimes.csv`.
+pub struct LifetimeTracker {
+    alive: Vec<Option<Opportunity>>, // indexed by ring
+    min_ms: u128,
+    ring_min_blocks: Vec<u32>        // [ring_lifetime] overrides
+}
+
+impl LifetimeTracker {
+    /// `[lifetime]` min_blocks / min_ms apply to every ring,
+    /// `[ring_lifetime]` SYMBOL = blocks overrides min_blocks for one ring.
+    pub fn new(config: &Ini, rings: &[Ring], default_min_blocks: u32) -> LifetimeTracker {
+        let min_blocks = config.getuint("lifetime", "min_blocks").unwrap_or(None).map(|v| v as u32).unwrap_or(default_min_blocks);
+        let min_ms = config.getuint("lifetime", "min_ms").unwrap_or(None).unwrap_or(0) as u128;
+        let ring_min_blocks = rings.iter().map(|ring| {
+            config.getuint("ring_lifetime", &ring.symbol).unwrap_or(None).map(|v| v as u32).unwrap_or(min_blocks)
+        }).collect();
+        println!("> lifetime: require {} blocks & {} ms per ring.", min_blocks, min_ms);
+        return LifetimeTracker { alive: vec![None; rings.len()], min_ms, ring_min_blocks };
+    }
+
+    /// Feed profitable rings of this block : open, extend or close every streak.
+    pub fn update(&mut self, rings: &[Ring], round_result: &[RingResult]) {
+        let now = now_ms();
+        let mut seen: HashMap<usize, &RingResult> = HashMap::new();
+        for result in round_result { seen.insert(result.ring, result); }
+
+        let mut finished: Vec<(usize, Opportunity)> = vec![];
+        for (index, alive) in self.alive.iter_mut().enumerate() {
+            match (seen.get(&index), alive.as_mut()) {
+                (Some(result), Some(opportunity)) => {
+                    opportunity.last_ms = now;
+                    opportunity.blocks += 1;
+                    opportunity.sum_profit += result.profit;
+                    opportunity.sum_percentage += result.percentage;
+                    if result.profit > opportunity.peak_profit { opportunity.peak_profit = result.profit; }
+                    if result.percentage > opportunity.peak_percentage { opportunity.peak_percentage = result.percentage; }
+                },
+                (Some(result), None) => {
+                    *alive = Some(Opportunity {
+                        started_ms: now, last_ms: now, blocks: 1,
+                        peak_profit: result.profit, peak_percentage: result.percentage,
+                        sum_profit: result.profit, sum_percentage: result.percentage
+                    });
+                },
+                (None, Some(_)) => finished.push((index, alive.take().unwrap())),
+                (None, None) => {}
+            }
+        }
+        if !finished.is_empty() { export(rings, &finished, now); }
+    }
+
+    /// Current streak of a ring, None if it's not profitable now.
+    pub fn get(&self, ring: usize) -> Option<&Opportunity> {
+        return self.alive[ring].as_ref();
+    }
+
+    /// Has this ring been profitable long enough to trade ?
+    pub fn is_safe(&self, ring: usize) -> bool {
+        match &self.alive[ring] {
+            Some(opportunity) => opportunity.blocks > self.ring_min_blocks[ring] && opportunity.duration_ms() >= self.min_ms,
+            None => false
+        }
+    }
+}
+
+/// Append finished streaks to the lifetime distribution file.
+fn export(rings: &[Ring], finished: &[(usize, Opportunity)], now: u128) {
+    let is_new = !std::path::Path::new(LIFETIME_FILE).exists();
+    match OpenOptions::new().create(true).append(true).open(LIFETIME_FILE) {
+        Ok(mut file) => {
+            if is_new { let _ = writeln!(file, "{}", LIFETIME_HEADER); }
+            for (index, opportunity) in finished {
+                let _ = writeln!(file, "{},{},{},{},{},{:.6},{:.6},{:.4},{:.4}",
+                    rings[*index].symbol, opportunity.started_ms, now, now - opportunity.started_ms, opportunity.blocks,
+                    opportunity.peak_profit, opportunity.avg_profit(), opportunity.peak_percentage, opportunity.avg_percentage());
+            }
+        },
+        Err(e) => println!("Error saving lifetimes: {:?}", e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::Side;
+
+    fn ring(symbol: &str) -> Ring {
+        let asset = |base: &str, quote: &str| (base.to_string(), quote.to_string());
+        return Ring { symbol: symbol.to_string(), pairs: vec![format!("{}BUSD", symbol), format!("{}BNB", symbol), String::from("BNBBUSD")],
+            legs: [0, 1, 2], sides: [Side::Buy, Side::Sell, Side::Sell],
+            assets: [asset(symbol, "BUSD"), asset(symbol, "BNB"), asset("BNB", "BUSD")] };
+    }
+
+    fn profitable(ring: usize) -> RingResult {
+        return RingResult { ring, percentage: 1.0, profit: 1.0, probability: 1.0, expected_value: 1.0, is_anomaly: false,
+            fees: 0.0, fees_in_bnb: 0.0, qty: 0.0, optimal_invest: 100.0 };
+    }
+
+    fn config(settings: &[(&str, &str, &str)]) -> Ini {
+        let mut config = Ini::new();
+        for (section, key, value) in settings { config.set(section, key, Some(value.to_string())); }
+        return config;
+    }
+
+    #[test]
+    fn ring_is_safe_past_min_blocks() {
+        let rings = [ring("XTZ"), ring("ADA")];
+        let mut tracker = LifetimeTracker::new(&config(&[("ring_lifetime", "ADA", "3")]), &rings, 1);
+        let mut safe = vec![];
+        for _ in 0..4 {
+            tracker.update(&rings, &[profitable(0), profitable(1)]);
+            safe.push((tracker.is_safe(0), tracker.is_safe(1)));
+        }
+        assert_eq!(safe, [(false, false), (true, false), (true, false), (true, true)]);
+        assert_eq!(tracker.get(1).unwrap().blocks, 4);
+    }
+
+    #[test]
+    fn young_streak_is_not_safe_before_min_ms() {
+        let rings = [ring("XTZ")];
+        let mut tracker = LifetimeTracker::new(&config(&[("lifetime", "min_blocks", "0"), ("lifetime", "min_ms", "60000")]), &rings, 1);
+        tracker.update(&rings, &[profitable(0)]);
+        tracker.update(&rings, &[profitable(0)]);
+        // enough blocks, not old enough.
+        assert_eq!(tracker.get(0).unwrap().blocks, 2);
+        assert!(!tracker.is_safe(0));
+    }
+
+    #[test]
+    fn unprofitable_ring_is_never_safe() {
+        let rings = [ring("XTZ")];
+        let tracker = LifetimeTracker::new(&config(&[("lifetime", "min_blocks", "0")]), &rings, 1);
+        assert!(!tracker.is_safe(0));
+        assert!(tracker.get(0).is_none());
+    }
+}
@@ -0,0 +1,253 @@
+use std::{
+    fmt,
+    thread,
+    collections::BTreeMap};
+
+use configparser::ini::Ini;
+
+use binance::api::API;
+use binance::api::Spot;
+use binance::account::{Account, OrderSide, OrderType, TimeInForce};
+use binance::model::{Order, Transaction};
+use binance::util::build_signed_request;
+
+use crate::error::{Action, Error, Result};
+use crate::exchangeinfo::QuantityInfo;
+use crate::retry::RetryPolicy;
+use super::Side;
+
+const CLIENT_ID_PREFIX:&str = "rg";
+const MAX_RING_LEN:usize = 12; // client id is 36 chars at most
+
+/// Client order id of one order of a ring execution : `rg-RUN-BLOCK-RING-LEG`.
+/// Same execution gives same id, so a resend can't double an order,
+/// and an order can be found again after a network error or a crash.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClientOrderId {
+    pub run: String,  // start time of this run, base 36
+    pub block: u64,
+    pub ring: String, // ring symbol, etc: XTZ
+    pub leg: char     // a, b, c for the 3 legs, x, y for selling back
+}
+
+impl ClientOrderId {
+    pub fn parse(text: &str) -> Option<ClientOrderId> {
+        let parts: Vec<&str> = text.split('-').collect();
+        if parts.len() != 5 || parts[0] != CLIENT_ID_PREFIX { return None; }
+        return Some(ClientOrderId {
+            run: parts[1].to_string(),
+            block: parts[2].parse::<u64>().ok()?,
+            ring: parts[3].to_string(),
+            leg: parts[4].chars().next()?
+        });
+    }
+}
+
+impl fmt::Display for ClientOrderId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}-{}-{}-{}", CLIENT_ID_PREFIX, self.run, self.block, self.ring, self.leg)
+    }
+}
+
+/// One ring execution, hands out a client id per leg.
+pub struct RingTag {
+    pub run: String,
+    pub block: u64,
+    pub ring: String
+}
+
+impl RingTag {
+    pub fn new(run: &str, block: u64, ring: &str) -> RingTag {
+        let ring: String = ring.chars().filter(|c| c.is_ascii_alphanumeric()).take(MAX_RING_LEN).collect();
+        return RingTag { run: run.to_string(), block, ring };
+    }
+
+    pub fn id(&self, leg: char) -> ClientOrderId {
+        return ClientOrderId { run: self.run.clone(), block: self.block, ring: self.ring.clone(), leg };
+    }
+
+    /// Common part of every id of this execution, as written to the journal.
+    pub fn prefix(&self) -> String {
+        return format!("{}-{}-{}-{}", CLIENT_ID_PREFIX, self.run, self.block, self.ring);
+    }
+}
+
+/// Id of this run : start time in seconds, base 36.
+pub fn run_id(now_ms: u128) -> String {
+    let mut secs = (now_ms / 1000) as u64;
+    let mut text = vec![];
+    while secs > 0 {
+        text.push(std::char::from_digit((secs % 36) as u32, 36).unwrap_or('0'));
+        secs /= 36;
+    }
+    return text.iter().rev().collect();
+}
+
+/// How one leg is sent to the exchange.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OrderKind {
+    Gtc,         // LIMIT, rests until filled or cancelled
+    Ioc,         // LIMIT, fills what it can right away, rest expires
+    Fok,         // LIMIT, fills all right away or expires
+    LimitMaker,  // LIMIT_MAKER, rejected if it would cross the book
+    Market,      // MARKET by base qty
+    MarketQuote  // MARKET by quote qty ( quoteOrderQty )
+}
+
+impl OrderKind {
+    /// Parse a leg kind from config : gtc, ioc, fok, limit_maker, market, market_quote.
+    pub fn parse(text: &str) -> Option<OrderKind> {
+        match text.trim().to_lowercase().as_str() {
+            "gtc" | "limit" => Some(OrderKind::Gtc),
+            "ioc" => Some(OrderKind::Ioc),
+            "fok" => Some(OrderKind::Fok),
+            "limit_maker" | "maker" => Some(OrderKind::LimitMaker),
+            "market" => Some(OrderKind::Market),
+            "market_quote" => Some(OrderKind::MarketQuote),
+            _ => None
+        }
+    }
+
+    /// `orderTypes` name the exchange must list for this kind.
+    fn exchange_type(&self) -> &'static str {
+        match self {
+            OrderKind::Gtc | OrderKind::Ioc | OrderKind::Fok => "LIMIT",
+            OrderKind::LimitMaker => "LIMIT_MAKER",
+            OrderKind::Market | OrderKind::MarketQuote => "MARKET"
+        }
+    }
+
+    /// Does the symbol accept this kind ? old caches have no `orderTypes`, only LIMIT is assumed then.
+    pub fn is_supported(&self, info: &QuantityInfo) -> bool {
+        let exchange_type = self.exchange_type();
+        if info.order_types.is_empty() { return exchange_type == "LIMIT"; }
+        if !info.order_types.iter().any(|order_type| order_type == exchange_type) { return false; }
+        return *self != OrderKind::MarketQuote || info.quote_order_qty_market_allowed;
+    }
+}
+
+/// Order kind of each leg, loaded from `[orders]` leg_a, leg_b, leg_c ( default gtc ).
+#[derive(Clone, Debug)]
+pub struct OrderPolicy {
+    pub legs: [OrderKind;3]
+}
+
+impl OrderPolicy {
+    pub fn from_config(config: &Ini) -> OrderPolicy {
+        let mut legs = [OrderKind::Gtc;3];
+        for (index, key) in ["leg_a", "leg_b", "leg_c"].iter().enumerate() {
+            if let Some(text) = config.get("orders", key) {
+                match OrderKind::parse(&text) {
+                    Some(kind) => legs[index] = kind,
+                    None => println!("> orders: invalid {} = {}, use gtc.", key, text)
+                }
+            }
+        }
+        println!("> orders: {:?} > {:?} > {:?}", legs[0], legs[1], legs[2]);
+        return OrderPolicy { legs };
+    }
+}
+
+/// One leg to send : `price` is the limit price, and values qty in quote for MARKET by quote qty.
+#[derive(Clone, Copy, Debug)]
+pub struct NewOrder {
+    pub side: Side,
+    pub kind: OrderKind,
+    pub qty: f64, // base
+    pub price: f64
+}
+
+/// Send one leg as its kind with `client_id`, a kind the symbol doesn't support is an `Unsupported` error :
+/// sending it as GTC instead would change how it fills ( a maker leg crossing, an IOC leg resting ).
+/// When we can't tell whether it was placed ( timeout, duplicate, unknown status ), look it up by client id first.
+/// Errors `retry` classifies as transient are resent with the same id after a backoff. Return the exchange order id.
+pub fn place(account: &Account, info: &QuantityInfo, order: NewOrder, client_id: &ClientOrderId, retry: &RetryPolicy) -> Result<u64> {
+    let symbol = info.symbol.as_str();
+    let NewOrder { side, kind, qty, price } = order;
+    if !kind.is_supported(info) {
+        return Err(Error::Unsupported { symbol: symbol.to_string(), kind: format!("{:?}", kind) });
+    }
+    let client_id = client_id.to_string();
+    let mut attempt = 1;
+    loop {
+        let error = match send(account, info, side, kind, qty, price, &client_id) {
+            Ok(order_id) => return Ok(order_id),
+            Err(e) => e
+        };
+        let is_unknown = match error.root() {
+            Error::Network(_) => true,
+            Error::Exchange { code, msg } => *code == -1007 || msg.contains("Duplicate"), // -1007 : send status unknown
+            _ => false
+        };
+        if is_unknown {
+            if let Some(order) = find(account, symbol, &client_id) {
+                println!("> orders: {} was placed as #{}.", client_id, order.order_id);
+                return Ok(order.order_id);
+            }
+        }
+        if attempt >= retry.max_attempts || !(is_unknown || retry.classify(&error) == Action::Retry) {
+            return Err(error);
+        }
+        let wait = retry.backoff(attempt, &error);
+        println!("> orders: {} not placed, resend ({}/{}) in {} ms: {}", client_id, attempt, retry.max_attempts - 1, wait.as_millis(), error);
+        thread::sleep(wait);
+        attempt += 1;
+    }
+}
+
+fn send(account: &Account, info: &QuantityInfo, side: Side, kind: OrderKind, qty: f64, price: f64, client_id: &str) -> Result<u64> {
+    let symbol = info.symbol.as_str();
+    let order_side = match side { Side::Buy => OrderSide::Buy, Side::Sell => OrderSide::Sell };
+    let client_order_id = client_id;
+    let client_id = Some(client_id.to_string());
+    let result = match kind {
+        OrderKind::Gtc => account.custom_order(symbol, qty, price, None, order_side, OrderType::Limit, TimeInForce::GTC, client_id),
+        OrderKind::Ioc => account.custom_order(symbol, qty, price, None, order_side, OrderType::Limit, TimeInForce::IOC, client_id),
+        OrderKind::Fok => account.custom_order(symbol, qty, price, None, order_side, OrderType::Limit, TimeInForce::FOK, client_id),
+        // no price : binance-rs leaves price & timeInForce out.
+        OrderKind::Market => account.custom_order(symbol, qty, 0.0, None, order_side, OrderType::Market, TimeInForce::GTC, client_id),
+        // binance-rs has no LIMIT_MAKER type nor client id on quote qty orders, build them by hand.
+        OrderKind::MarketQuote | OrderKind::LimitMaker => {
+            let mut parameters: BTreeMap<String, String> = BTreeMap::new();
+            parameters.insert("symbol".into(), symbol.into());
+            parameters.insert("side".into(), String::from(match side { Side::Buy => "BUY", Side::Sell => "SELL" }));
+            if kind == OrderKind::MarketQuote {
+                let quote_qty = info.round_quote(qty * price);
+                parameters.insert("type".into(), "MARKET".into());
+                parameters.insert("quoteOrderQty".into(), quote_qty.to_string());
+            } else {
+                parameters.insert("type".into(), "LIMIT_MAKER".into());
+                parameters.insert("quantity".into(), qty.to_string());
+                parameters.insert("price".into(), price.to_string());
+            }
+            parameters.insert("newClientOrderId".into(), client_order_id.to_string());
+            let request = build_signed_request(parameters, account.recv_window)?;
+            account.client.post_signed(API::Spot(Spot::Order), request)
+        }
+    };
+    return result.map(|transaction: Transaction| transaction.order_id).map_err(Error::from);
+}
+
+/// Look an order up by client id, None if the exchange doesn't know it.
+pub fn find(account: &Account, symbol: &str, client_id: &str) -> Option<Order> {
+    let mut parameters: BTreeMap<String, String> = BTreeMap::new();
+    parameters.insert("symbol".into(), symbol.into());
+    parameters.insert("origClientOrderId".into(), client_id.into());
+    let request = build_signed_request(parameters, account.recv_window).ok()?;
+    return account.client.get_signed::<Order>(API::Spot(Spot::Order), Some(request)).ok();
+}
+
+/// Open orders left by an earlier run, matched back to their ring & leg by client id.
+pub fn report_open_orders(account: &Account) {
+    match account.get_all_open_orders() {
+        Ok(open_orders) => {
+            for order in open_orders {
+                if let Some(id) = ClientOrderId::parse(&order.client_order_id) {
+                    println!("> recovery: #{} {} {} {}/{} is leg {} of ring {} ( block {}, run {} ).",
+                        order.order_id, order.side, order.symbol, order.executed_qty, order.orig_qty, id.leg, id.ring, id.block, id.run);
+                }
+            }
+        },
+        Err(e) => println!("> recovery: can't list open orders: {:?}", e.0)
+    }
+}
@@ -0,0 +1,366 @@
+use std::collections::HashMap;
+
+use colored::*;
+use configparser::ini::Ini;
+
+use crate::error::{Error, Result};
+use crate::exchangeinfo::QuantityInfo;
+use super::Side;
+use super::orders::OrderKind;
+use super::pricing::LegPrice;
+use super::snapshot::{Snapshot, SymbolId};
+use super::strategy::{AccountView, Expected, Intent, MarketView, OrderIntent, Report, Strategy};
+
+// default of [peg]
+const BAND:f64 = 0.1;               // percent off 1.0 before the reversion is traded
+const DEPEG:f64 = 2.0;              // percent off 1.0 where a pair is halted
+const COOLDOWN_BLOCKS:u64 = 150;    // blocks a depegged pair stays halted once back in range
+const ORDER_SIZE:f64 = 100.0;       // base per order
+const MAX_INVENTORY:f64 = 1000.0;   // base held, no buy past it
+const MIN_INVENTORY:f64 = 0.0;      // base kept, no sell under it
+const MAX_POLLS:u32 = 10;           // a resting order is cancelled after this
+const BUY_AT:&str = "bid+1";
+const SELL_AT:&str = "ask-1";
+const ORDER_KIND:&str = "gtc";
+
+/// One stable pair, with what the strategy bought on it and hasn't sold yet.
+struct PegPair {
+    symbol: String,
+    base: String,
+    quote: String,
+    id: Option<SymbolId>, // None when exchangeInfo doesn't list it
+    halted_until: u64,    // block
+    is_depegged: bool,
+    position: f64,        // base bought, after fees
+    cost: f64             // quote spent on `position`
+}
+
+impl PegPair {
+    /// What held base cost, inventory held before the 1st buy counts at par.
+    fn avg_cost(&self) -> f64 {
+        return if self.position > 0.0 { self.cost / self.position } else { 1.0 };
+    }
+}
+
+/// Stablecoin peg : stable-to-stable pairs ( etc: USDC/BUSD ) drift around 1.0 and come back.
+/// Buy base under par, sell it over par, stop a pair that drifts too far to be a peg anymore.
+pub struct Peg {
+    pairs: Vec<PegPair>,
+    band: f64,   // fraction
+    depeg: f64,  // fraction
+    cooldown: u64,
+    order_size: f64,
+    max_inventory: f64,
+    min_inventory: f64,
+    buy_at: LegPrice,
+    sell_at: LegPrice,
+    kind: OrderKind,
+    max_polls: u32
+}
+
+/// `[peg] symbols` ( BASE/QUOTE ) as ( symbol, base, quote ).
+fn pairs(config: &Ini) -> Vec<(String, String, String)> {
+    let mut pairs: Vec<(String, String, String)> = vec![];
+    for pair in config.get("peg", "symbols").unwrap_or_default().split(',') {
+        let mut assets = pair.trim().split('/');
+        if let (Some(base), Some(quote)) = (assets.next(), assets.next()) {
+            if base.is_empty() || quote.is_empty() { continue; }
+            let (base, quote) = (base.to_uppercase(), quote.to_uppercase());
+            let symbol = format!("{}{}", base, quote);
+            if !pairs.iter().any(|pair| pair.0 == symbol) { pairs.push((symbol, base, quote)); }
+        }
+    }
+    return pairs;
+}
+
+/// Pairs to fetch quantity info for.
+pub fn symbols(config: &Ini) -> Vec<String> {
+    return pairs(config).into_iter().map(|pair| pair.0).collect();
+}
+
+impl Peg {
+    /// `[peg]` symbols ( BASE/QUOTE ), band & depeg ( percent off 1.0 ), cooldown_blocks, order_size,
+    /// max_inventory & min_inventory ( base ), buy_at & sell_at ( etc: `bid+1` ), order ( see `[orders]` ), max_polls.
+    pub fn new(config: &Ini) -> Result<Peg> {
+        let pairs: Vec<PegPair> = pairs(config).into_iter()
+            .map(|(symbol, base, quote)| PegPair { symbol, base, quote, id: None, halted_until: 0, is_depegged: false, position: 0.0, cost: 0.0 })
+            .collect();
+        if pairs.is_empty() { return Err(Error::config("peg", "symbols", "no pair, etc: USDC/BUSD")); }
+        let float = |key: &str, default: f64| config.getfloat("peg", key).unwrap_or(None).unwrap_or(default);
+        let price = |key: &str, default: &str| {
+            let text = config.get("peg", key).unwrap_or(String::from(default));
+            return LegPrice::parse(&text).ok_or_else(|| Error::config("peg", key, &format!("bad price {}", text)));
+        };
+        let kind = config.get("peg", "order").unwrap_or(String::from(ORDER_KIND));
+        let peg = Peg {
+            pairs,
+            band: float("band", BAND) / 100.0,
+            depeg: float("depeg", DEPEG) / 100.0,
+            cooldown: config.getuint("peg", "cooldown_blocks").unwrap_or(None).unwrap_or(COOLDOWN_BLOCKS),
+            order_size: float("order_size", ORDER_SIZE),
+            max_inventory: float("max_inventory", MAX_INVENTORY),
+            min_inventory: float("min_inventory", MIN_INVENTORY),
+            buy_at: price("buy_at", BUY_AT)?,
+            sell_at: price("sell_at", SELL_AT)?,
+            kind: OrderKind::parse(&kind).ok_or_else(|| Error::config("peg", "order", &format!("unknown order {}", kind)))?,
+            max_polls: config.getuint("peg", "max_polls").unwrap_or(None).unwrap_or(MAX_POLLS as u64) as u32
+        };
+        if peg.band >= peg.depeg { return Err(Error::config("peg", "band", "must be under depeg")); }
+        return Ok(peg);
+    }
+}
+
+impl Strategy for Peg {
+    fn name(&self) -> &str { return "peg"; }
+
+    fn intern(&mut self, snapshot: &mut Snapshot, quantity_info: &HashMap<String, QuantityInfo>) {
+        for pair in self.pairs.iter_mut() {
+            pair.id = snapshot.add_symbol(&pair.symbol, quantity_info);
+            if pair.id.is_none() { println!("> peg: {} isn't listed, skipped.", pair.symbol.red()); }
+        }
+    }
+
+    fn start(&mut self, _config: &Ini, _account: &AccountView, _snapshot: &Snapshot) -> Result<()> {
+        println!("> peg: {} pairs, trade off 1.0 by {}%, halt past {}%.",
+            self.pairs.iter().filter(|pair| pair.id.is_some()).count(), self.band * 100.0, self.depeg * 100.0);
+        return Ok(());
+    }
+
+    fn assets(&self) -> Vec<String> {
+        let mut assets: Vec<String> = vec![];
+        for pair in &self.pairs {
+            for asset in [&pair.base, &pair.quote] {
+                if !assets.contains(asset) { assets.push(asset.clone()); }
+            }
+        }
+        return assets;
+    }
+
+    fn on_block(&mut self, market: &MarketView, account: &AccountView) -> Vec<Intent> {
+        let snapshot = market.snapshot;
+        // quote spent by earlier buys of this block, they all share it.
+        let mut balances = account.balances.clone();
+        let mut intents = vec![];
+        for pair in self.pairs.iter_mut() {
+            let id = match pair.id { Some(id) => id, None => continue };
+            let book = snapshot.books[id];
+            if !snapshot.updated[id] || book[0] <= 0.0 || book[1] <= 0.0 { continue; }
+            let mid = (book[0] + book[1]) / 2.0;
+            // DEPEG: that far off par it may not come back, out until it stayed in range for cooldown.
+            if (mid - 1.0).abs() > self.depeg {
+                if !pair.is_depegged { println!("> peg: {} depegged at {:.4}, halted.", pair.symbol.red(), mid); }
+                pair.is_depegged = true;
+                pair.halted_until = market.block + self.cooldown;
+                continue;
+            }
+            if market.block < pair.halted_until { continue; }
+            if pair.is_depegged {
+                pair.is_depegged = false;
+                println!("> peg: {} back at {:.4}, trading again.", pair.symbol.green(), mid);
+            }
+            let info = &snapshot.quantity[id];
+            let held = balances.get(&pair.base).copied().unwrap_or(0.0);
+            let cash = balances.get(&pair.quote).copied().unwrap_or(0.0);
+            let buy_price = info.round_price(self.buy_at.price(&book, info.step_price));
+            let sell_price = info.round_price(self.sell_at.price(&book, info.step_price));
+            let (side, price, qty) = if buy_price > 0.0 && buy_price <= 1.0 - self.band && held < self.max_inventory {
+                (Side::Buy, buy_price, self.order_size.min(self.max_inventory - held).min(cash / buy_price))
+            } else if sell_price >= 1.0 + self.band && held > self.min_inventory {
+                (Side::Sell, sell_price, self.order_size.min(held - self.min_inventory))
+            } else {
+                continue;
+            };
+            let qty = info.round_qty(qty);
+            if qty <= 0.0 || qty < info.min_qty.parse::<f64>().unwrap_or(0.0) { continue; }
+            let fees = market.fee_model.order(id, &book, side, price, qty * price);
+            // back to par, from what held base cost for a sell.
+            let profit = match side {
+                Side::Buy => qty * (1.0 - price),
+                Side::Sell => qty * (price - pair.avg_cost())
+            } - fees.fees;
+            if side == Side::Buy { balances.insert(pair.quote.clone(), cash - qty * price); }
+            println!("> peg: {} {} {} at {} | mid {:.4} | held {} | ev ${:.4}", format!("{:?}", side).to_lowercase(),
+                qty.to_string().green(), pair.symbol.green(), price.to_string().yellow(), mid, held, profit);
+            intents.push(Intent::Order(OrderIntent {
+                symbol: pair.symbol.clone(),
+                id,
+                base: pair.base.clone(),
+                quote: pair.quote.clone(),
+                side,
+                kind: self.kind,
+                price,
+                qty,
+                max_polls: self.max_polls,
+                expected: Expected { invest: qty * price, profit, fees: fees.fees, fees_in_bnb: fees.in_bnb }
+            }));
+        }
+        intents.sort_by(|a, b| b.expected().profit.partial_cmp(&a.expected().profit).unwrap_or(std::cmp::Ordering::Equal));
+        return intents;
+    }
+
+    fn on_result(&mut self, market: &MarketView, _account: &AccountView, intent: &Intent, report: &Report) -> Result<f64> {
+        let intent = match intent { Intent::Order(intent) => intent, Intent::Ring(_) => return Ok(0.0) };
+        let pair = match self.pairs.iter_mut().find(|pair| pair.symbol == intent.symbol) { Some(pair) => pair, None => return Ok(0.0) };
+        let mut realized = 0.0;
+        for fill in report.fills.iter().filter(|fill| fill.symbol == intent.symbol) {
+            match fill.side {
+                Side::Buy => {
+                    pair.position += fill.executed_qty - market.fee_model.fill_fee(intent.id, fill.executed_qty);
+                    pair.cost += fill.quote_qty;
+                },
+                Side::Sell => {
+                    let avg_cost = pair.avg_cost();
+                    realized += fill.quote_qty - market.fee_model.fill_fee(intent.id, fill.quote_qty) - fill.executed_qty * avg_cost;
+                    let sold = fill.executed_qty.min(pair.position);
+                    pair.position -= sold;
+                    pair.cost -= sold * avg_cost;
+                }
+            }
+        }
+        if intent.side == Side::Sell && !report.fills.is_empty() {
+            println!("#{}: peg {} realized ${}", market.block.to_string().yellow(),
+                intent.symbol.green(), format!("{:.4}", realized).yellow());
+        }
+        return Ok(realized);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use binance::account::Account;
+    use binance::api::Binance;
+    use binance::config::Config;
+    use crate::exchangeinfo;
+    use crate::mock::{MockExchange, Scenario};
+    use crate::retry::RetryPolicy;
+    use crate::userdata::UserData;
+    use crate::analyzer::executor::{self, Fill};
+    use crate::analyzer::fees::FeeModel;
+    use crate::analyzer::orders::RingTag;
+
+    const EXCHANGE_INFO:&str = include_str!("../../tests/fixtures/exchange_info.json");
+
+    /// Peg on USDC/BUSD ( tick 0.0001, lot 0.01 ) with `[peg]` `settings`.
+    fn peg(settings: &[(&str, &str)]) -> (Peg, Snapshot) {
+        let mut config = Ini::new();
+        config.set("peg", "symbols", Some(String::from("USDC/BUSD")));
+        for (key, value) in settings { config.set("peg", key, Some(value.to_string())); }
+        let mut peg = Peg::new(&config).unwrap();
+        let quantity_info = exchangeinfo::quantity_from(EXCHANGE_INFO, &symbols(&config)).unwrap();
+        let mut snapshot = Snapshot::empty();
+        peg.intern(&mut snapshot, &quantity_info);
+        return (peg, snapshot);
+    }
+
+    /// Orders of one block at `book`, holding `usdc` & 1000 BUSD.
+    fn block(peg: &mut Peg, snapshot: &mut Snapshot, block: u64, book: [f64;2], usdc: f64) -> Vec<(Side, f64, f64)> {
+        snapshot.reset();
+        snapshot.set_book("USDCBUSD", book, [1000.0, 1000.0]);
+        let fee_model = FeeModel::flat(snapshot, 0.001, 0.001);
+        let market = MarketView { block, now_ms: 0, update_ms: 0, snapshot, fee_model: &fee_model };
+        let account: Account = Binance::new(None, None);
+        let user_data = UserData::disabled();
+        let balances = HashMap::from([(String::from("USDC"), usdc), (String::from("BUSD"), 1000.0)]);
+        let account = AccountView { account: &account, user_data: &user_data, balances };
+        return peg.on_block(&market, &account).iter().map(|intent| match intent {
+            Intent::Order(order) => (order.side, order.price, order.qty),
+            Intent::Ring(_) => panic!("peg only sends orders")
+        }).collect();
+    }
+
+    #[test]
+    fn buys_under_band_up_to_max_inventory() {
+        let (mut peg, mut snapshot) = peg(&[("max_inventory", "1000")]);
+        assert_eq!(block(&mut peg, &mut snapshot, 0, [0.997, 0.9975], 0.0), [(Side::Buy, 0.9971, 100.0)]);
+        assert_eq!(block(&mut peg, &mut snapshot, 1, [0.997, 0.9975], 950.0), [(Side::Buy, 0.9971, 50.0)]);
+        assert!(block(&mut peg, &mut snapshot, 2, [0.997, 0.9975], 1000.0).is_empty());
+    }
+
+    #[test]
+    fn sells_over_band_down_to_min_inventory() {
+        let (mut peg, mut snapshot) = peg(&[("min_inventory", "100")]);
+        assert_eq!(block(&mut peg, &mut snapshot, 0, [1.0025, 1.003], 130.0), [(Side::Sell, 1.0029, 30.0)]);
+        assert!(block(&mut peg, &mut snapshot, 1, [1.0025, 1.003], 100.0).is_empty());
+    }
+
+    #[test]
+    fn stays_out_inside_band() {
+        let (mut peg, mut snapshot) = peg(&[]);
+        assert!(block(&mut peg, &mut snapshot, 0, [0.9995, 1.0005], 500.0).is_empty());
+    }
+
+    #[test]
+    fn depegged_pair_is_halted_for_cooldown() {
+        let (mut peg, mut snapshot) = peg(&[("cooldown_blocks", "2")]);
+        assert!(block(&mut peg, &mut snapshot, 0, [0.95, 0.951], 0.0).is_empty());
+        assert!(block(&mut peg, &mut snapshot, 1, [0.997, 0.9975], 0.0).is_empty());
+        assert_eq!(block(&mut peg, &mut snapshot, 2, [0.997, 0.9975], 0.0).len(), 1);
+    }
+
+    #[test]
+    fn bad_settings_are_config_errors() {
+        let mut config = Ini::new();
+        assert!(Peg::new(&config).is_err());
+        config.set("peg", "symbols", Some(String::from("USDC/BUSD")));
+        config.set("peg", "band", Some(String::from("3")));
+        assert!(Peg::new(&config).is_err());
+        config.set("peg", "band", None);
+        config.set("peg", "buy_at", Some(String::from("last")));
+        assert!(Peg::new(&config).is_err());
+    }
+
+    #[test]
+    fn realizes_profit_against_what_it_paid() {
+        let (mut peg, snapshot) = peg(&[]);
+        let fee_model = FeeModel::flat(&snapshot, 0.001, 0.001);
+        let market = MarketView { block: 0, now_ms: 0, update_ms: 0, snapshot: &snapshot, fee_model: &fee_model };
+        let account: Account = Binance::new(None, None);
+        let user_data = UserData::disabled();
+        let account = AccountView { account: &account, user_data: &user_data, balances: HashMap::new() };
+        let mut realize = |side: Side, qty: f64, price: f64| {
+            let intent = Intent::Order(OrderIntent { symbol: String::from("USDCBUSD"), id: 0, base: String::from("USDC"), quote: String::from("BUSD"),
+                side, kind: OrderKind::Gtc, price, qty, max_polls: 1, expected: Expected::default() });
+            let fills = [Fill { symbol: String::from("USDCBUSD"), side, executed_qty: qty, quote_qty: qty * price, elapsed_ms: 0, avg_price: price }];
+            let result = Ok(Some(qty));
+            return peg.on_result(&market, &account, &intent, &Report { fills: &fills, result: &result, action: None, latency_ms: 0.0 }).unwrap();
+        };
+        assert_eq!(realize(Side::Buy, 100.0, 0.998), 0.0);
+        // 99.9 USDC after fee cost 99.8, sold for 100.0998 less 0.1001 fee.
+        assert!((realize(Side::Sell, 99.9, 1.002) - 0.1997002).abs() < 1e-9);
+        // what was held before counts at par.
+        assert!((realize(Side::Sell, 10.0, 1.002) - (10.02 - 0.01002 - 10.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn resting_order_is_cancelled_with_what_filled() {
+        let scenario: Scenario = serde_json::from_value(serde_json::json!({
+            "symbols": [{ "symbol": "USDCBUSD", "base": "USDC", "quote": "BUSD", "step_size": "0.01000000", "tick_size": "0.00010000" }],
+            "balances": { "BUSD": 1000.0 },
+            "prices": [{ "USDCBUSD": [0.997, 0.9975] }],
+            "fills": { "USDCBUSD": { "mode": "partial", "after_polls": 1, "ratio": 0.5 } }
+        })).unwrap();
+        let mock = MockExchange::start(scenario).unwrap();
+        let (mut peg, mut snapshot) = peg(&[("max_polls", "2")]);
+        snapshot.set_book("USDCBUSD", [0.997, 0.9975], [1000.0, 1000.0]);
+        let fee_model = FeeModel::flat(&snapshot, 0.001, 0.001);
+        let market = MarketView { block: 0, now_ms: 0, update_ms: 0, snapshot: &snapshot, fee_model: &fee_model };
+        let endpoint = Config::default().set_rest_api_endpoint(mock.url.clone());
+        let account: Account = Binance::new_with_config(Some("key".into()), Some("secret".into()), &endpoint);
+        let user_data = UserData::disabled();
+        let account = AccountView { account: &account, user_data: &user_data, balances: HashMap::from([(String::from("BUSD"), 1000.0)]) };
+        let intents = peg.on_block(&market, &account);
+        let intent = match &intents[..] { [Intent::Order(intent)] => intent, _ => panic!("one order expected") };
+        let quantity_info = exchangeinfo::fetch_quantity_from(&mock.url, &[String::from("USDCBUSD")]).unwrap();
+        let mut fills = vec![];
+        let (tag, retry) = (RingTag::new("test", 0, "USDCBUSD"), RetryPolicy::from_config(&Ini::new()));
+        let mut run = executor::Execution { account: account.account, user_data: account.user_data, quantity_info: &quantity_info, tag: &tag, retry: &retry, fills: &mut fills };
+        let result = executor::execute_order(&mut run, intent);
+        assert_eq!(result.as_ref().unwrap(), &Some(50.0));
+        let orders = mock.orders();
+        assert_eq!((orders.len(), orders[0].status.as_str(), orders[0].price), (1, "CANCELED", 0.9971));
+        let report = Report { fills: &fills, result: &result, action: None, latency_ms: 0.0 };
+        assert_eq!(peg.on_result(&market, &account, &intents[0], &report).unwrap(), 0.0);
+        assert!((peg.pairs[0].position - 49.95).abs() < 1e-9);
+    }
+}
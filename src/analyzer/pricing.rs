@@ -0,0 +1,166 @@
+use configparser::ini::Ini;
+
+/// Which side of the book a leg is priced from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Quote {
+    Bid,
+    Ask,
+    Mid
+}
+
+/// Price of one leg : a quote moved by N ticks ( + is up, - is down ).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LegPrice {
+    pub quote: Quote,
+    pub ticks: f64
+}
+
+/// How we price the 3 legs : buy stable-symbol > sell symbol-bridge > sell bridge-stable.
+/// Tuning this completely changes which rings are found, see NOTE.md.
+#[derive(Clone, Debug)]
+pub struct PricingPolicy {
+    pub name: String,
+    pub legs: [LegPrice;3]
+}
+
+const DEFAULT_PRESET:&str = "limit";
+
+// how aggressive we create new orderbooks,
+// best  = where profit around 0.6% ~ 0.3%
+// risk  = where profit could be > 5%
+// limit = 1 tick inside the spread on every leg, the old default.
+const PRESETS:[(&str, [LegPrice;3]);5] = [
+    ("limit",   [LegPrice { quote: Quote::Bid, ticks: 1.0 }, LegPrice { quote: Quote::Ask, ticks: -1.0 }, LegPrice { quote: Quote::Ask, ticks: -1.0 }]),
+    ("best",    [LegPrice { quote: Quote::Bid, ticks: 2.0 }, LegPrice { quote: Quote::Ask, ticks: 2.0 },  LegPrice { quote: Quote::Ask, ticks: 100.0 }]),
+    ("risk",    [LegPrice { quote: Quote::Ask, ticks: -2.0 }, LegPrice { quote: Quote::Bid, ticks: 2.0 }, LegPrice { quote: Quote::Ask, ticks: 0.0 }]),
+    ("market",  [LegPrice { quote: Quote::Ask, ticks: 0.0 }, LegPrice { quote: Quote::Bid, ticks: 0.0 },  LegPrice { quote: Quote::Bid, ticks: 0.0 }]),
+    ("average", [LegPrice { quote: Quote::Mid, ticks: 0.0 }, LegPrice { quote: Quote::Mid, ticks: 0.0 },  LegPrice { quote: Quote::Mid, ticks: 0.0 }]),
+];
+
+impl LegPrice {
+    /// Parse "bid", "ask", "mid", "bid+2", "ask-1", "mid+0.5"...
+    pub fn parse(text: &str) -> Option<LegPrice> {
+        let text = text.trim().to_lowercase();
+        let (quote, offset) = match (text.get(..3), text.get(3..)) {
+            (Some("bid"), Some(offset)) => (Quote::Bid, offset),
+            (Some("ask"), Some(offset)) => (Quote::Ask, offset),
+            (Some("mid"), Some(offset)) => (Quote::Mid, offset),
+            _ => return None
+        };
+        let offset = offset.trim().replace(' ', "");
+        let ticks = if offset.is_empty() { 0.0 } else {
+            match offset.trim_start_matches('+').parse::<f64>() {
+                Ok(ticks) => ticks,
+                _ => return None
+            }
+        };
+        return Some(LegPrice { quote, ticks });
+    }
+
+    /// Price from top of book [ bid, ask ], not corrected by tick size yet.
+    pub fn price(&self, book: &[f64;2], step_price: f64) -> f64 {
+        let base = match self.quote {
+            Quote::Bid => book[0],
+            Quote::Ask => book[1],
+            Quote::Mid => (book[0] + book[1]) / 2.0
+        };
+        return base + self.ticks * step_price;
+    }
+}
+
+impl std::fmt::Display for LegPrice {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let quote = match self.quote { Quote::Bid => "bid", Quote::Ask => "ask", Quote::Mid => "mid" };
+        if self.ticks == 0.0 { return write!(f, "{}", quote); }
+        return write!(f, "{}{:+}", quote, self.ticks);
+    }
+}
+
+impl PricingPolicy {
+    pub fn preset(name: &str) -> Option<PricingPolicy> {
+        let name = name.trim().to_lowercase();
+        return PRESETS.iter()
+            .find(|preset| preset.0 == name)
+            .map(|preset| PricingPolicy { name: name.clone(), legs: preset.1 });
+    }
+
+    /// Load from `[pricing]` :
+    /// - preset = limit | best | risk | market | average
+    /// - leg_a, leg_b, leg_c = bid/ask/mid +- N ticks, override the preset per leg.
+    pub fn from_config(config: &Ini) -> PricingPolicy {
+        let preset = config.get("pricing", "preset").unwrap_or(String::from(DEFAULT_PRESET));
+        let mut policy = match PricingPolicy::preset(&preset) {
+            Some(policy) => policy,
+            None => {
+                println!("> pricing: unknown preset {}, using {}.", preset, DEFAULT_PRESET);
+                PricingPolicy::preset(DEFAULT_PRESET).unwrap()
+            }
+        };
+        for (index, key) in ["leg_a", "leg_b", "leg_c"].iter().enumerate() {
+            if let Some(text) = config.get("pricing", key) {
+                match LegPrice::parse(&text) {
+                    Some(leg) => {
+                        policy.legs[index] = leg;
+                        policy.name = String::from("custom");
+                    },
+                    None => println!("> pricing: can't parse {} = {}, keep {}.", key, text, policy.legs[index])
+                }
+            }
+        }
+        println!("> pricing: {} = buy {} > sell {} > sell {}", policy.name, policy.legs[0], policy.legs[1], policy.legs[2]);
+        return policy;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leg(quote: Quote, ticks: f64) -> LegPrice { return LegPrice { quote, ticks }; }
+
+    #[test]
+    fn parses_quote_and_ticks() {
+        assert_eq!(LegPrice::parse("bid"), Some(leg(Quote::Bid, 0.0)));
+        assert_eq!(LegPrice::parse(" ASK-1 "), Some(leg(Quote::Ask, -1.0)));
+        assert_eq!(LegPrice::parse("bid + 2"), Some(leg(Quote::Bid, 2.0)));
+        assert_eq!(LegPrice::parse("mid+0.5"), Some(leg(Quote::Mid, 0.5)));
+    }
+
+    #[test]
+    fn bad_prices_dont_parse() {
+        for text in ["", "bi", "last", "bid+", "ask-x", "bi€+1", "€€€", "bidding"] {
+            assert_eq!(LegPrice::parse(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn ticks_move_the_quote() {
+        let book = [0.99, 1.01];
+        assert!((LegPrice::parse("bid+1").unwrap().price(&book, 0.001) - 0.991).abs() < 1e-12);
+        assert!((LegPrice::parse("ask-2").unwrap().price(&book, 0.001) - 1.008).abs() < 1e-12);
+        assert!((LegPrice::parse("mid").unwrap().price(&book, 0.001) - 1.0).abs() < 1e-12);
+        assert_eq!(LegPrice::parse("ask-1").unwrap().to_string(), "ask-1");
+    }
+
+    #[test]
+    fn presets_price_every_leg() {
+        let legs = |name: &str| PricingPolicy::preset(name).unwrap().legs;
+        assert_eq!(legs("limit"), [leg(Quote::Bid, 1.0), leg(Quote::Ask, -1.0), leg(Quote::Ask, -1.0)]);
+        assert_eq!(legs("best"), [leg(Quote::Bid, 2.0), leg(Quote::Ask, 2.0), leg(Quote::Ask, 100.0)]);
+        assert_eq!(legs("risk"), [leg(Quote::Ask, -2.0), leg(Quote::Bid, 2.0), leg(Quote::Ask, 0.0)]);
+        assert_eq!(legs(" Market "), [leg(Quote::Ask, 0.0), leg(Quote::Bid, 0.0), leg(Quote::Bid, 0.0)]);
+        assert_eq!(legs("average"), [leg(Quote::Mid, 0.0); 3]);
+        assert!(PricingPolicy::preset("fast").is_none());
+    }
+
+    #[test]
+    fn legs_override_the_preset() {
+        let mut config = Ini::new();
+        config.set("pricing", "preset", Some(String::from("market")));
+        config.set("pricing", "leg_b", Some(String::from("ask-1")));
+        config.set("pricing", "leg_c", Some(String::from("nonsense")));
+        let policy = PricingPolicy::from_config(&config);
+        assert_eq!(policy.name, "custom");
+        assert_eq!(policy.legs, [leg(Quote::Ask, 0.0), leg(Quote::Ask, -1.0), leg(Quote::Bid, 0.0)]);
+    }
+}
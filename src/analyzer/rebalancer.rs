@@ -0,0 +1,215 @@
+use std::time::Duration;
+
+use colored::*;
+use configparser::ini::Ini;
+
+use binance::account::Account;
+
+use crate::error::{Context, Result};
+use crate::quarantine;
+use crate::retry::RetryPolicy;
+use crate::userdata::UserData;
+use super::{IS_TESTING, Side};
+use super::orders::{self, NewOrder, OrderKind, RingTag};
+use super::reconcile;
+use super::snapshot::{Ring, Snapshot, SymbolId};
+use super::tracker::{self, TrackedOrder};
+
+// default of [rebalance]
+const DRIFT_THRESHOLD:f64 = 20.0;   // percent away from target before we trade
+const MIN_TRADE:f64 = 10.0;         // in stablecoin, smaller drifts aren't worth the fee
+const ORDER_TIMEOUT_MS:u64 = 5000;  // passive order is cancelled after this
+const CLIENT_ID_LEG:char = 'r';     // rg-RUN-BLOCK-ASSET-r
+
+/// One asset held for parallel mode, valued through its stablecoin pair.
+struct Holding {
+    asset: String,
+    pair: Option<SymbolId>, // None for the stablecoin itself
+    target: f64,            // value in stablecoin
+    qty: f64,               // at last mark
+    price: f64              // at last mark
+}
+
+/// Keep stablecoin, bridge & ring symbols near their target value for parallel arbitrage.
+/// PnL is split into arbitrage ( qty gained by rings ), inventory ( price drift of what we hold )
+/// and rebalance ( qty lost to rebalancing ).
+pub struct Rebalancer {
+    holdings: Vec<Holding>,
+    threshold: f64,
+    min_trade: f64,
+    timeout: Duration,
+    retry: RetryPolicy,
+    run_id: String,
+    arbitrage_pnl: f64,
+    inventory_pnl: f64,
+    rebalance_pnl: f64
+}
+
+impl Rebalancer {
+    /// `[rebalance]` enabled, threshold ( percent ), min_trade, timeout_ms and target ( default `default_target` ),
+    /// `[rebalance_targets]` ASSET = value overrides target for one asset.
+    pub fn new(config: &Ini, rings: &[Ring], snapshot: &Snapshot, stablecoin: &str, bridge: &str, default_target: f64) -> Option<Rebalancer> {
+        if !config.getbool("rebalance", "enabled").unwrap_or(None).unwrap_or(true) { return None; }
+        let target = config.getfloat("rebalance", "target").unwrap_or(None).unwrap_or(default_target);
+        let target_of = |asset: &str| config.getfloat("rebalance_targets", asset).unwrap_or(None).unwrap_or(target);
+
+        let mut holdings = vec![Holding { asset: stablecoin.to_string(), pair: None, target: target_of(stablecoin), qty: 0.0, price: 1.0 }];
+        let mut add = |asset: &str, pair: &str| {
+            if holdings.iter().any(|holding| holding.asset == asset) { return; }
+            if let Some(&id) = snapshot.ids.get(pair) {
+                holdings.push(Holding { asset: asset.to_string(), pair: Some(id), target: target_of(asset), qty: 0.0, price: 0.0 });
+            }
+        };
+        for ring in rings {
+            add(bridge, &ring.pairs[2]);      // bridge-stable
+            add(&ring.symbol, &ring.pairs[0]); // symbol-stable
+        }
+        let rebalancer = Rebalancer {
+            holdings,
+            threshold: config.getfloat("rebalance", "threshold").unwrap_or(None).unwrap_or(DRIFT_THRESHOLD),
+            min_trade: config.getfloat("rebalance", "min_trade").unwrap_or(None).unwrap_or(MIN_TRADE),
+            timeout: Duration::from_millis(config.getuint("rebalance", "timeout_ms").unwrap_or(None).unwrap_or(ORDER_TIMEOUT_MS)),
+            retry: RetryPolicy::from_config(config),
+            run_id: orders::run_id(quarantine::now_ms()),
+            arbitrage_pnl: 0.0,
+            inventory_pnl: 0.0,
+            rebalance_pnl: 0.0
+        };
+        println!("> rebalance: {} assets, drift threshold {}%.", rebalancer.holdings.len(), rebalancer.threshold);
+        return Some(rebalancer);
+    }
+
+    /// First mark, taken before any ring so PnL starts from zero.
+    pub fn start(&mut self, account: &Account, user_data: &UserData, snapshot: &Snapshot) {
+        self.mark(account, user_data, snapshot);
+    }
+
+    /// Measure drift after a parallel ring of `block`, then rebalance what went past the threshold.
+    /// An order we couldn't cancel is an error, never left on the book unseen.
+    pub fn after_ring(&mut self, account: &Account, user_data: &UserData, snapshot: &Snapshot, block: u64) -> Result<()> {
+        self.arbitrage_pnl += self.mark(account, user_data, snapshot);
+        let mut traded = false;
+        for index in 0..self.holdings.len() {
+            traded |= self.rebalance(account, user_data, index, snapshot, block)?;
+        }
+        if traded { self.rebalance_pnl += self.mark(account, user_data, snapshot); }
+        self.report();
+        return Ok(());
+    }
+
+    /// Refresh qty & price of every holding.
+    /// Price drift of what we held goes to inventory PnL, qty change is returned to the caller.
+    fn mark(&mut self, account: &Account, user_data: &UserData, snapshot: &Snapshot) -> f64 {
+        let assets: Vec<String> = self.holdings.iter().map(|holding| holding.asset.clone()).collect();
+        let balances = reconcile::balances(account, user_data, &assets);
+        let mut qty_pnl = 0.0;
+        for holding in &mut self.holdings {
+            let price = match holding.pair {
+                Some(id) if snapshot.books[id][0] > 0.0 => (snapshot.books[id][0] + snapshot.books[id][1]) / 2.0,
+                Some(_) => holding.price, // no ticker yet
+                None => 1.0
+            };
+            let qty = *balances.get(&holding.asset).unwrap_or(&holding.qty);
+            if holding.price > 0.0 { self.inventory_pnl += holding.qty * (price - holding.price); }
+            qty_pnl += (qty - holding.qty) * price;
+            holding.qty = qty;
+            holding.price = price;
+        }
+        return qty_pnl;
+    }
+
+    /// Bring one asset back to its target with a passive order at the touch, true if it traded.
+    fn rebalance(&self, account: &Account, user_data: &UserData, index: usize, snapshot: &Snapshot, block: u64) -> Result<bool> {
+        let holding = &self.holdings[index];
+        let id = match holding.pair { Some(id) => id, None => return Ok(false) }; // stablecoin takes the rest
+        let value = holding.qty * holding.price;
+        let drift = value - holding.target;
+        if holding.target <= 0.0 || holding.price <= 0.0 || (drift.abs() / holding.target * 100.0) < self.threshold || drift.abs() < self.min_trade {
+            return Ok(false);
+        }
+        let pair = &snapshot.symbols[id];
+        let info = &snapshot.quantity[id];
+        let book = &snapshot.books[id];
+        let qty = info.round_qty(drift.abs() / holding.price);
+        if qty <= 0.0 { return Ok(false); }
+        let side = if drift < 0.0 { Side::Buy } else { Side::Sell };
+        let price = if side == Side::Buy { book[0] } else { book[1] }; // maker at the touch, no spread paid
+        println!("> rebalance: {} {:.2}% off target, {} {} {} at {}", holding.asset.bold(), drift / holding.target * 100.0,
+            if side == Side::Buy { "buy".green() } else { "sell".red() }, qty, pair, price);
+        if IS_TESTING { return Ok(false); }
+
+        let client_id = RingTag::new(&self.run_id, block, &holding.asset).id(CLIENT_ID_LEG);
+        let order_id = match orders::place(account, info, NewOrder { side, kind: OrderKind::Gtc, qty, price }, &client_id, &self.retry) {
+            Ok(order_id) => order_id,
+            // nothing placed, next ring tries again.
+            Err(e) => { println!("> rebalance: {} failed: {}", pair, e); return Ok(false); }
+        };
+        let mut order = TrackedOrder::new(pair, side, order_id, qty);
+        let timeout_ms = self.timeout.as_millis();
+        tracker::wait(account, user_data, &mut order, &self.retry, |order| order.elapsed_ms() >= timeout_ms)
+            .context(format!("rebalance {}", holding.asset))?;
+        if !order.is_filled() {
+            println!("> rebalance: #{} {} {:?} with {}/{} filled in {} ms.", order_id, pair, order.state, order.executed_qty, qty, timeout_ms);
+        }
+        return Ok(order.executed_qty > 0.0);
+    }
+
+    fn report(&self) {
+        let format_pnl = |pnl: f64| if pnl >= 0.0 { format!("${:.4}", pnl).green() } else { format!("${:.4}", pnl).red() };
+        println!("> pnl: arbitrage {} | inventory {} | rebalance {}",
+            format_pnl(self.arbitrage_pnl), format_pnl(self.inventory_pnl), format_pnl(self.rebalance_pnl));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use binance::api::Binance;
+    use binance::config::Config;
+    use crate::exchangeinfo;
+    use crate::mock::{MockExchange, Scenario};
+
+    const EXCHANGE_INFO:&str = include_str!("../../tests/fixtures/exchange_info.json");
+
+    #[test]
+    fn resting_order_is_cancelled() {
+        let scenario: Scenario = serde_json::from_value(json!({
+            "symbols": [{ "symbol": "USDCBUSD", "base": "USDC", "quote": "BUSD", "step_size": "0.01", "tick_size": "0.0001" }],
+            "balances": { "USDC": 1000.0 },
+            "prices": [{ "USDCBUSD": [0.9999, 1.0] }],
+            "fills": { "USDCBUSD": { "mode": "rest" } },
+            "faults": [{ "method": "GET", "path": "/api/v3/order", "code": -1000, "times": 2 }]
+        })).unwrap();
+        let mock = MockExchange::start(scenario).unwrap();
+        let quantity_info = exchangeinfo::quantity_from(EXCHANGE_INFO, &[String::from("USDCBUSD")]).unwrap();
+        let mut snapshot = Snapshot::empty();
+        let id = snapshot.add_symbol("USDCBUSD", &quantity_info).unwrap();
+        snapshot.set_book("USDCBUSD", [0.9999, 1.0], [1000.0, 1000.0]);
+        // 200 USDC against a target of 100 : sell 100 at the ask.
+        let rebalancer = Rebalancer {
+            holdings: vec![
+                Holding { asset: String::from("BUSD"), pair: None, target: 100.0, qty: 0.0, price: 1.0 },
+                Holding { asset: String::from("USDC"), pair: Some(id), target: 100.0, qty: 200.0, price: 1.0 }
+            ],
+            threshold: DRIFT_THRESHOLD,
+            min_trade: MIN_TRADE,
+            timeout: Duration::from_millis(600),
+            retry: RetryPolicy::from_config(&Ini::new()),
+            run_id: String::from("test"),
+            arbitrage_pnl: 0.0,
+            inventory_pnl: 0.0,
+            rebalance_pnl: 0.0
+        };
+        let endpoint = Config::default().set_rest_api_endpoint(mock.url.clone());
+        let account: Account = Binance::new_with_config(Some("key".into()), Some("secret".into()), &endpoint);
+
+        // status errors are polled through, then it's cancelled & seen cancelled.
+        assert!(!rebalancer.rebalance(&account, &UserData::disabled(), 1, &snapshot, 3).unwrap());
+        let orders = mock.orders();
+        assert_eq!(orders.len(), 1);
+        assert_eq!((orders[0].side.as_str(), orders[0].qty, orders[0].price), ("SELL", 100.0, 1.0));
+        assert_eq!(orders[0].client_order_id, "rg-test-3-USDC-r");
+        assert_eq!(orders[0].status, "CANCELED");
+    }
+}
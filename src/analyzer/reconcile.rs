@@ -0,0 +1,173 @@
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    collections::HashMap};
+
+use colored::*;
+use configparser::ini::Ini;
+
+use binance::account::Account;
+
+use crate::quarantine;
+use crate::userdata::UserData;
+use super::Side;
+use super::executor::Fill;
+use super::fees::FeeModel;
+use super::orders::RingTag;
+use super::snapshot::{Ring, Snapshot, SymbolId};
+use super::strategy::MarketView;
+
+const RECONCILE_FILE:&str = "reconcile.csv";
+const RECONCILE_HEADER:&str = "time_ms,client_order_id,asset,before,after,expected,actual,drift,drift_value,flagged";
+
+// default of [reconcile]
+const DRIFT_TOLERANCE:f64 = 0.05; // in stablecoin, per asset : fee rounding & maker/taker guess
+
+/// Free balances of `assets` : user data stream when connected, else one REST call.
+pub fn balances(account: &Account, user_data: &UserData, assets: &[String]) -> HashMap<String, f64> {
+    let mut balances: HashMap<String, f64> = HashMap::new();
+    if user_data.is_connected() {
+        for asset in assets {
+            if let Some(qty) = user_data.balance(asset) { balances.insert(asset.clone(), qty); }
+        }
+        if balances.len() == assets.len() { return balances; }
+    }
+    match account.get_account() {
+        Ok(answer) => {
+            for balance in answer.balances {
+                balances.insert(balance.asset, balance.free.parse::<f64>().unwrap_or(0.0));
+            }
+        },
+        Err(e) => println!("> balances: can't read: {:?}", e.0)
+    }
+    return balances;
+}
+
+/// Pairs one trade went through, a ring or a single order, with the base & quote of each.
+pub struct Traded {
+    pub symbol: String, // etc: XTZ for its ring, USDCBUSD for one order
+    pub pairs: Vec<String>,
+    pub legs: Vec<SymbolId>,
+    pub assets: Vec<(String, String)>
+}
+
+impl Traded {
+    pub fn ring(ring: &Ring) -> Traded {
+        return Traded { symbol: ring.symbol.clone(), pairs: ring.pairs.clone(), legs: ring.legs.to_vec(), assets: ring.assets.to_vec() };
+    }
+}
+
+/// Balances of a trade's assets, taken right before it runs.
+pub struct Before {
+    traded: Traded,
+    assets: Vec<String>,
+    balances: HashMap<String, f64>
+}
+
+/// Check every trade against the balances it moved : what fills explain is expected,
+/// dust left in symbol & bridge is kept apart, anything else is drift ( etc: a manual trade, a missed fill ).
+pub struct Reconciler {
+    enabled: bool,
+    stablecoin: String,
+    tolerance: f64,
+    dust_value: f64,  // in stablecoin, left in symbols & bridges by lot truncation
+    drift_value: f64, // in stablecoin, unexplained so far
+    flagged: u32
+}
+
+impl Reconciler {
+    /// `[reconcile]` enabled ( default true ) and tolerance, in `stablecoin` per asset.
+    pub fn from_config(config: &Ini, stablecoin: &str) -> Reconciler {
+        let reconciler = Reconciler {
+            enabled: config.getbool("reconcile", "enabled").unwrap_or(None).unwrap_or(true),
+            stablecoin: stablecoin.to_string(),
+            tolerance: config.getfloat("reconcile", "tolerance").unwrap_or(None).unwrap_or(DRIFT_TOLERANCE),
+            dust_value: 0.0,
+            drift_value: 0.0,
+            flagged: 0
+        };
+        if reconciler.enabled { println!("> reconcile: drift tolerance ${}.", reconciler.tolerance); }
+        return reconciler;
+    }
+
+    /// Snapshot balances of every asset `traded` moves, None when disabled.
+    pub fn before(&self, account: &Account, user_data: &UserData, traded: Traded) -> Option<Before> {
+        if !self.enabled { return None; }
+        let mut assets: Vec<String> = vec![];
+        for (base, quote) in &traded.assets {
+            for asset in [base, quote] {
+                if !assets.contains(asset) { assets.push(asset.clone()); }
+            }
+        }
+        let balances = balances(account, user_data, &assets);
+        return Some(Before { traded, assets, balances });
+    }
+
+    /// Compare balances now with `before` and with what `fills` should have moved,
+    /// append every asset to `reconcile.csv` and flag drift over tolerance.
+    pub fn after(&mut self, account: &Account, user_data: &UserData, before: Before, fills: &[Fill], market: &MarketView, tag: &RingTag) {
+        let after = balances(account, user_data, &before.assets);
+        let expected = expected_deltas(&before.traded, fills, market.fee_model);
+        let now = quarantine::now_ms();
+        let is_new = !std::path::Path::new(RECONCILE_FILE).exists();
+        let mut file = match OpenOptions::new().create(true).append(true).open(RECONCILE_FILE) {
+            Ok(file) => Some(file),
+            Err(e) => { println!("Error saving reconcile: {:?}", e); None }
+        };
+        if let (true, Some(file)) = (is_new, file.as_mut()) { let _ = writeln!(file, "{}", RECONCILE_HEADER); }
+
+        for asset in &before.assets {
+            let (qty_before, qty_after) = match (before.balances.get(asset), after.get(asset)) {
+                (Some(qty_before), Some(qty_after)) => (*qty_before, *qty_after),
+                _ => { println!("> reconcile: no balance of {}, skipped.", asset); continue; }
+            };
+            let price = value_of(asset, &before.traded, market.snapshot, &self.stablecoin);
+            let actual = qty_after - qty_before;
+            let expected = *expected.get(asset).unwrap_or(&0.0);
+            let drift = actual - expected;
+            let drift_value = drift * price;
+            let flagged = drift_value.abs() > self.tolerance;
+            // what fills left in symbol & bridge, stablecoin is where profit goes.
+            if *asset != self.stablecoin && expected > 0.0 { self.dust_value += expected * price; }
+            self.drift_value += drift_value;
+            if flagged {
+                self.flagged += 1;
+                println!("> reconcile: {} {} moved {:+} but fills explain {:+}, {} unexplained.",
+                    "drift:".red(), asset.bold(), actual, expected, format!("${:+.4}", drift_value).red());
+            }
+            if let Some(file) = file.as_mut() {
+                let _ = writeln!(file, "{},{},{},{:.8},{:.8},{:.8},{:.8},{:.8},{:.6},{}",
+                    now, tag.prefix(), asset, qty_before, qty_after, expected, actual, drift, drift_value, flagged);
+            }
+        }
+        println!("> reconcile: dust ${:.4} | drift ${:.4} | {} flagged.", self.dust_value, self.drift_value, self.flagged);
+    }
+}
+
+/// Change of every asset `fills` explain, fees taken from what each leg received included.
+fn expected_deltas(traded: &Traded, fills: &[Fill], fee_model: &FeeModel) -> HashMap<String, f64> {
+    let mut deltas: HashMap<String, f64> = HashMap::new();
+    for fill in fills {
+        let leg = match traded.pairs.iter().position(|pair| *pair == fill.symbol) {
+            Some(leg) => leg,
+            None => { println!("> reconcile: {} is not a pair of {}.", fill.symbol, traded.symbol); continue; }
+        };
+        let (base, quote) = &traded.assets[leg];
+        let (paid, paid_qty, received, received_qty) = match fill.side {
+            Side::Buy => (quote, fill.quote_qty, base, fill.executed_qty),
+            Side::Sell => (base, fill.executed_qty, quote, fill.quote_qty)
+        };
+        let fee = fee_model.fill_fee(traded.legs[leg], received_qty);
+        *deltas.entry(paid.clone()).or_insert(0.0) -= paid_qty;
+        *deltas.entry(received.clone()).or_insert(0.0) += received_qty - fee;
+    }
+    return deltas;
+}
+
+/// Mid price of `asset` in stablecoin from the trade's own pairs, 0 when it has none.
+fn value_of(asset: &str, traded: &Traded, snapshot: &Snapshot, stablecoin: &str) -> f64 {
+    if asset == stablecoin { return 1.0; }
+    return traded.assets.iter().zip(traded.legs.iter())
+        .find(|((base, quote), _)| base == asset && quote == stablecoin)
+        .map_or(0.0, |(_, id)| (snapshot.books[*id][0] + snapshot.books[*id][1]) / 2.0);
+}
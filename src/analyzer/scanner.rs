@@ -0,0 +1,159 @@
+use std::{
+    cmp::Ordering,
+    collections::HashMap};
+
+use crate::exchangeinfo::QuantityInfo;
+use super::{RingLeg, RingModels, RingResult, Side};
+use super::fees::FeeModel;
+use super::latency::LatencyModel;
+use super::pricing::PricingPolicy;
+use super::scoring::FillModel;
+use super::snapshot::{Ring, Snapshot};
+
+/// What the bot does every block, without config, caches nor binance-rs :
+/// feed it books, get profitable rings and the orders to trade them.
+pub struct Scanner {
+    snapshot: Snapshot,
+    rings: Vec<Ring>,
+    policy: PricingPolicy,
+    fee_model: FeeModel,
+    fill_model: FillModel,
+    latency: LatencyModel
+}
+
+/// One order of an execution plan, price & qty already on PRICE_FILTER & LOT_SIZE.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlannedOrder {
+    pub symbol: String,
+    pub side: Side,
+    pub price: f64,
+    pub qty: f64
+}
+
+/// Orders of a ring, each leg trading what the one before received after fees.
+#[derive(Clone, Debug)]
+pub struct ExecutionPlan {
+    pub symbol: String,
+    pub invest: f64,               // in stablecoin
+    pub orders: Vec<PlannedOrder>, // a, b, c
+    pub out: f64                   // stablecoin back if all 3 fill as planned
+}
+
+impl Scanner {
+    /// Scan `rings` ( see `discover_rings` ) priced by `policy`, `maker` & `taker` as fractions ( 0.001 = 0.1% ),
+    /// `latency_ms` is how old prices are when the 1st order goes out.
+    pub fn new(rings: &HashMap<String, Vec<RingLeg>>, quantity_info: &HashMap<String, QuantityInfo>, policy: PricingPolicy,
+        maker: f64, taker: f64, latency_ms: f64) -> Scanner {
+        let (snapshot, rings) = Snapshot::new(rings, quantity_info);
+        return Scanner {
+            fee_model: FeeModel::flat(&snapshot, maker, taker),
+            fill_model: FillModel::empty(&snapshot),
+            latency: LatencyModel::with(&snapshot, latency_ms, 1.0),
+            snapshot,
+            rings,
+            policy
+        };
+    }
+
+    pub fn rings(&self) -> &[Ring] { return &self.rings; }
+
+    pub fn snapshot(&self) -> &Snapshot { return &self.snapshot; }
+
+    /// Start a new block, books of the last one don't count anymore.
+    pub fn begin_block(&mut self) {
+        self.snapshot.reset();
+    }
+
+    /// Top of book [ bid, ask ] & [ bid_qty, ask_qty ] of `symbol`, false when no ring trades it.
+    pub fn set_book(&mut self, symbol: &str, book: [f64;2], size: [f64;2]) -> bool {
+        return self.snapshot.set_book(symbol, book, size);
+    }
+
+    /// Profitable rings of this block for `invest`, best expected value first.
+    /// `now_ms` dates the block for the drift model.
+    pub fn scan(&mut self, invest: f64, now_ms: u128) -> Vec<RingResult> {
+        self.fill_model.observe(&self.snapshot);
+        self.latency.observe(&self.snapshot, now_ms);
+        let models = RingModels { policy: &self.policy, fill: &self.fill_model, fees: &self.fee_model, latency: &self.latency };
+        let mut results = super::compute_rings(&self.rings, invest, &self.snapshot, models, &|_| false);
+        results.sort_by(|a, b| b.expected_value.partial_cmp(&a.expected_value).unwrap_or(Ordering::Equal));
+        return results;
+    }
+
+    /// Orders to trade `result` at this block's policy prices.
+    pub fn plan(&self, result: &RingResult) -> ExecutionPlan {
+        let ring = &self.rings[result.ring];
+        let prices = super::build_ring(ring, &self.snapshot, &self.policy);
+        let mut held = result.optimal_invest;
+        let mut orders = vec![];
+        for (leg, &id) in ring.legs.iter().enumerate() {
+            let info = &self.snapshot.quantity[id];
+            // qty is always base : BUY spends quote, SELL spends base.
+            let (qty, received) = match ring.sides[leg] {
+                Side::Buy => { let qty = info.round_qty(held / prices[leg]); (qty, qty) },
+                Side::Sell => { let qty = info.round_qty(held); (qty, qty * prices[leg]) }
+            };
+            held = received - self.fee_model.fill_fee(id, received);
+            orders.push(PlannedOrder { symbol: ring.pairs[leg].clone(), side: ring.sides[leg], price: prices[leg], qty });
+        }
+        return ExecutionPlan { symbol: ring.symbol.clone(), invest: result.optimal_invest, orders, out: held };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchangeinfo;
+    use crate::analyzer::discover_rings;
+
+    const EXCHANGE_INFO:&str = include_str!("../../tests/fixtures/exchange_info.json");
+    const BOOK_TICKERS:&str = include_str!("../../tests/fixtures/book_tickers.json");
+
+    /// Scanner on the recorded exchangeInfo, fed the recorded book tickers.
+    fn scanner(preset: &str) -> Scanner {
+        let symbols = exchangeinfo::symbols_from(EXCHANGE_INFO).unwrap();
+        let rings = discover_rings(&symbols, "BUSD", "BNB", &[]);
+        let pairs: Vec<String> = rings.values().flatten().map(|leg| leg.symbol.clone()).collect();
+        let quantity_info = exchangeinfo::quantity_from(EXCHANGE_INFO, &pairs).unwrap();
+        let mut scanner = Scanner::new(&rings, &quantity_info, PricingPolicy::preset(preset).unwrap(), 0.001, 0.001, 500.0);
+        let tickers: Vec<serde_json::Value> = serde_json::from_str(BOOK_TICKERS).unwrap();
+        let value = |ticker: &serde_json::Value, key: &str| ticker[key].as_str().unwrap().parse::<f64>().unwrap();
+        scanner.begin_block();
+        for ticker in &tickers {
+            scanner.set_book(ticker["symbol"].as_str().unwrap(),
+                [value(ticker, "bidPrice"), value(ticker, "askPrice")], [value(ticker, "bidQty"), value(ticker, "askQty")]);
+        }
+        return scanner;
+    }
+
+    #[test]
+    fn scans_recorded_books() {
+        let mut scanner = scanner("market");
+        assert!(!scanner.set_book("DOTBUSD", [5.0, 5.01], [1.0, 1.0]));
+        let results = scanner.scan(100.0, 0);
+        assert_eq!(results.len(), 1);
+        assert_eq!(scanner.rings()[results[0].ring].symbol, "XTZ");
+        let out = 100.0 * 0.999 * 0.0034 * 0.999 * 300.0 * 0.999;
+        assert!((results[0].profit - (out - 100.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn plans_orders_leg_after_leg() {
+        let mut scanner = scanner("market");
+        let results = scanner.scan(100.0, 0);
+        let plan = scanner.plan(&results[0]);
+        let orders: Vec<(&str, Side, f64, f64)> = plan.orders.iter()
+            .map(|order| (order.symbol.as_str(), order.side, order.price, order.qty)).collect();
+        // 100 XTZ, 99.9 after fee > 0.33966 BNB, 0.339 on lots.
+        assert_eq!(orders, [("XTZBUSD", Side::Buy, 1.0, 100.0), ("XTZBNB", Side::Sell, 0.0034, 99.9), ("BNBBUSD", Side::Sell, 300.0, 0.339)]);
+        assert!((plan.out - 0.339 * 300.0 * 0.999).abs() < 1e-9);
+    }
+
+    #[test]
+    fn stale_books_are_not_scanned() {
+        let mut scanner = scanner("market");
+        scanner.begin_block();
+        scanner.set_book("XTZBUSD", [0.999, 1.0], [1.0, 1.0]);
+        assert!(scanner.scan(100.0, 1000).is_empty());
+    }
+}
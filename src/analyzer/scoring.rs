@@ -0,0 +1,182 @@
+use configparser::ini::Ini;
+
+use super::snapshot::{Ring, Snapshot, SymbolId};
+
+const FILLS_CACHE_FILE:&str = "fills.cache";
+
+// Weights of the fill model, each factor is in 0..1 :
+// - spread : every tick of gap beyond 1 makes a passive order slower to fill.
+// - depth  : our qty against the qty on the side we trade with.
+// - moving : a price moving fast runs away from a resting order.
+// - time   : what we measured filling this symbol before.
+const SPREAD_WEIGHT:f64 = 0.1;    // per extra tick of gap
+const MOVE_WEIGHT:f64 = 0.25;     // per tick moved / block
+const MOVE_SMOOTHING:f64 = 0.2;   // EWMA, new block weight
+const FILL_SMOOTHING:f64 = 0.3;   // EWMA, new fill weight
+const FILL_HORIZON_MS:f64 = 10_000.0; // fill time where probability drops to ~37%
+
+/// Per-symbol history used to estimate how likely a ring fills at its policy prices.
+pub struct FillModel {
+    last_mid: Vec<f64>,      // mid of previous block
+    movement: Vec<f64>,      // EWMA of |mid change| in ticks / block
+    fill_ms: Vec<f64>,       // EWMA of measured fill time, 0 = never traded
+}
+
+/// Fill probability of a ring and what it's worth.
+pub struct RingScore {
+    pub probability: f64,
+    pub expected_value: f64
+}
+
+impl FillModel {
+    /// New model sized for `snapshot`, with fill times loaded from cache.
+    pub fn new(snapshot: &Snapshot) -> FillModel {
+        let mut model = FillModel::empty(snapshot);
+        let mut cache_file = Ini::new();
+        if cache_file.load(FILLS_CACHE_FILE).is_ok() {
+            for (id, symbol) in snapshot.symbols.iter().enumerate() {
+                if let Ok(Some(fill_ms)) = cache_file.getfloat("fills", symbol) {
+                    model.fill_ms[id] = fill_ms;
+                }
+            }
+            println!("> loaded fill times.");
+        }
+        return model;
+    }
+
+    /// New model sized for `snapshot`, no history.
+    pub fn empty(snapshot: &Snapshot) -> FillModel {
+        let count = snapshot.symbols.len();
+        return FillModel { last_mid: vec![0.0; count], movement: vec![0.0; count], fill_ms: vec![0.0; count] };
+    }
+
+    /// Track price movement, once per block after tickers are updated.
+    pub fn observe(&mut self, snapshot: &Snapshot) {
+        for id in 0..snapshot.symbols.len() {
+            if !snapshot.updated[id] { continue; }
+            let book = &snapshot.books[id];
+            let mid = (book[0] + book[1]) / 2.0;
+            if self.last_mid[id] > 0.0 {
+                let moved = (mid - self.last_mid[id]).abs() / snapshot.quantity[id].step_price;
+                self.movement[id] = self.movement[id] * (1.0 - MOVE_SMOOTHING) + moved * MOVE_SMOOTHING;
+            }
+            self.last_mid[id] = mid;
+        }
+    }
+
+    /// Record how long an order on `symbol` took to fill.
+    pub fn record_fill(&mut self, snapshot: &Snapshot, symbol: &str, elapsed_ms: f64) {
+        if let Some(&id) = snapshot.ids.get(symbol) {
+            self.fill_ms[id] = if self.fill_ms[id] > 0.0 {
+                self.fill_ms[id] * (1.0 - FILL_SMOOTHING) + elapsed_ms * FILL_SMOOTHING
+            } else { elapsed_ms };
+        }
+    }
+
+    /// Measured fill time of a symbol, 0 when never traded.
+    pub fn fill_ms(&self, id: SymbolId) -> f64 {
+        return self.fill_ms[id];
+    }
+
+    /// Save measured fill times so history survives a restart.
+    pub fn save(&self, snapshot: &Snapshot) {
+        let mut cache_file = Ini::new();
+        for (id, symbol) in snapshot.symbols.iter().enumerate() {
+            if self.fill_ms[id] > 0.0 {
+                cache_file.set("fills", symbol, Some(format!("{:.0}", self.fill_ms[id])));
+            }
+        }
+        if let Err(msg) = cache_file.write(FILLS_CACHE_FILE) {
+            println!("Error saving cache: {:?}", msg);
+        }
+    }
+
+    /// Probability that one leg fills at `price`, buying or selling `qty`.
+    fn leg_probability(&self, id: SymbolId, snapshot: &Snapshot, price: f64, qty: f64, is_buy: bool) -> f64 {
+        let book = &snapshot.books[id];
+        let sizes = &snapshot.sizes[id];
+        let step_price = snapshot.quantity[id].step_price;
+        // crossing the book fills right away, otherwise wait in the gap.
+        let is_crossing = if is_buy { price >= book[1] } else { price <= book[0] };
+        let spread = if is_crossing { 1.0 } else {
+            let gap_ticks = ((book[1] - book[0]) / step_price).round();
+            1.0 / (1.0 + (gap_ticks - 1.0).max(0.0) * SPREAD_WEIGHT)
+        };
+        let book_qty = if is_buy { sizes[1] } else { sizes[0] };
+        let depth = if book_qty + qty > 0.0 { book_qty / (book_qty + qty) } else { 0.0 };
+        let moving = 1.0 / (1.0 + self.movement[id] * MOVE_WEIGHT);
+        let time = if self.fill_ms[id] > 0.0 { (-self.fill_ms[id] / FILL_HORIZON_MS).exp() } else { 1.0 };
+        return (spread * depth * moving * time).clamp(0.0, 1.0);
+    }
+
+    /// Score a ring : all 3 legs must fill, otherwise we assume unwinding at the worst case.
+    pub fn score(&self, ring: &Ring, snapshot: &Snapshot, prices: &[f64;3],
+        invest: f64, profit: f64, profit_worst: f64) -> RingScore {
+        let qty_a = invest / prices[0];         // symbol bought
+        let qty_b = qty_a;                      // symbol sold for bridge
+        let qty_c = qty_a * prices[1];          // bridge sold for stablecoin
+        let probability =
+            self.leg_probability(ring.legs[0], snapshot, prices[0], qty_a, true) *
+            self.leg_probability(ring.legs[1], snapshot, prices[1], qty_b, false) *
+            self.leg_probability(ring.legs[2], snapshot, prices[2], qty_c, false);
+        let expected_value = probability * profit + (1.0 - probability) * profit_worst.min(0.0);
+        return RingScore { probability, expected_value };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::fixture::{xtz, BOOKS};
+
+    #[test]
+    fn crossing_legs_only_depend_on_depth() {
+        let (snapshot, ring) = xtz(BOOKS, [300.0, 300.0, 300.0]);
+        let model = FillModel::empty(&snapshot);
+        assert_eq!(model.leg_probability(ring.legs[0], &snapshot, 1.0, 100.0, true), 0.75);
+        assert_eq!(model.leg_probability(ring.legs[0], &snapshot, 1.0, 0.0, true), 1.0);
+        assert_eq!(model.leg_probability(ring.legs[2], &snapshot, 300.0, 900.0, false), 0.25);
+    }
+
+    #[test]
+    fn wider_gap_fills_a_resting_leg_slower() {
+        let (mut snapshot, ring) = xtz(BOOKS, [1e9; 3]);
+        let model = FillModel::empty(&snapshot);
+        let id = ring.legs[0];
+        // 1 tick of gap, resting at the bid.
+        assert!((model.leg_probability(id, &snapshot, 0.999, 0.0, true) - 1.0).abs() < 1e-9);
+        // 11 ticks : 1 / ( 1 + 10 x 0.1 )
+        snapshot.set_book("XTZBUSD", [0.989, 1.0], [1e9, 1e9]);
+        assert!((model.leg_probability(id, &snapshot, 0.990, 0.0, true) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn moving_prices_and_slow_fills_lower_probability() {
+        let (mut snapshot, ring) = xtz(BOOKS, [1e9; 3]);
+        let mut model = FillModel::empty(&snapshot);
+        let id = ring.legs[0];
+        model.observe(&snapshot);
+        // mid up by 2 ticks in a block : movement 0.2 x 2
+        snapshot.set_book("XTZBUSD", [1.001, 1.002], [1e9, 1e9]);
+        model.observe(&snapshot);
+        assert!((model.movement[id] - 0.4).abs() < 1e-9);
+        assert!((model.leg_probability(id, &snapshot, 1.002, 0.0, true) - 1.0 / 1.1).abs() < 1e-9);
+        model.record_fill(&snapshot, "XTZBUSD", 1000.0);
+        model.record_fill(&snapshot, "XTZBUSD", 2000.0);
+        assert!((model.fill_ms(id) - 1300.0).abs() < 1e-9);
+        assert!((model.leg_probability(id, &snapshot, 1.002, 0.0, true) - (-0.13f64).exp() / 1.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn expected_value_ranks_likely_rings_first() {
+        let prices = [0.999, 0.003401, 300.1];
+        let (snapshot, ring) = xtz(BOOKS, [1e9; 3]);
+        let likely = FillModel::empty(&snapshot).score(&ring, &snapshot, &prices, 100.0, 1.0, -2.0);
+        let (snapshot, ring) = xtz([[0.989, 1.0], BOOKS[1], BOOKS[2]], [1e9; 3]);
+        let unlikely = FillModel::empty(&snapshot).score(&ring, &snapshot, &prices, 100.0, 1.0, -2.0);
+        // ev = p x profit + ( 1 - p ) x worst
+        assert!((likely.expected_value - (likely.probability - (1.0 - likely.probability) * 2.0)).abs() < 1e-9);
+        assert!((unlikely.probability - likely.probability * 0.5).abs() < 1e-9);
+        assert!(likely.expected_value > unlikely.expected_value);
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+use crate::exchangeinfo::QuantityInfo;
+use super::{RingLeg, Side};
+
+/// Interned symbol, index into every per-symbol table of a `Snapshot`.
+pub type SymbolId = usize;
+
+/// A ring with its 3 pairs interned once at start-up.
+#[derive(Clone)]
+pub struct Ring {
+    pub symbol: String,      // coin name, etc: XTZ
+    pub pairs: Vec<String>,  // [ XTZ-BUSD, XTZ-BNB, BNB-BUSD ]
+    pub legs: [SymbolId; 3], // same pairs as symbol ids
+    pub sides: [Side; 3],    // BUY > SELL > SELL
+    pub assets: [(String, String); 3] // base & quote of each pair
+}
+
+/// One block of market data, shared by reference between all ring workers.
+/// Built once, then tickers are overwritten in place every block,
+/// so evaluating a ring is only a few array reads.
+pub struct Snapshot {
+    pub symbols: Vec<String>,
+    pub ids: HashMap<String, SymbolId>,
+    pub quantity: Vec<QuantityInfo>,
+    pub books: Vec<[f64;2]>,     // top of book [ bid, ask ], priced per leg by PricingPolicy
+    pub sizes: Vec<[f64;2]>,     // top of book [ bid_qty, ask_qty ]
+    pub updated: Vec<bool>       // got a ticker in this block ?
+}
+
+impl Snapshot {
+    /// Intern all ring symbols and build the ring list on top of them.
+    /// Rings with a pair missing from `quantity_info` are dropped.
+    pub fn new(rings: &HashMap<String, Vec<RingLeg>>, quantity_info: &HashMap<String, QuantityInfo>) -> (Snapshot, Vec<Ring>) {
+        let mut snapshot = Snapshot::empty();
+        let rings = snapshot.add_rings(rings, quantity_info);
+        return (snapshot, rings);
+    }
+
+    /// Nothing interned yet, strategies add what they read.
+    pub fn empty() -> Snapshot {
+        return Snapshot { symbols: vec![], ids: HashMap::new(), quantity: vec![], books: vec![], sizes: vec![], updated: vec![] };
+    }
+
+    /// Intern the pairs of `rings` and build the ring list on top of them,
+    /// rings with a pair missing from `quantity_info` are dropped.
+    pub fn add_rings(&mut self, rings: &HashMap<String, Vec<RingLeg>>, quantity_info: &HashMap<String, QuantityInfo>) -> Vec<Ring> {
+        let mut interned_rings: Vec<Ring> = vec![];
+        for (symbol, ring_legs) in rings {
+            let pairs: Vec<String> = ring_legs.iter().map(|leg| leg.symbol.clone()).collect();
+            if pairs.len() != 3 || pairs.iter().any(|pair| !quantity_info.contains_key(pair)) {
+                println!("> skip ring {}: missing quantity info.", symbol);
+                continue;
+            }
+            let legs = [
+                self.intern(&pairs[0], quantity_info),
+                self.intern(&pairs[1], quantity_info),
+                self.intern(&pairs[2], quantity_info)
+            ];
+            let sides = [ring_legs[0].side, ring_legs[1].side, ring_legs[2].side];
+            let assets = [0, 1, 2].map(|leg| (ring_legs[leg].base.clone(), ring_legs[leg].quote.clone()));
+            interned_rings.push(Ring { symbol: symbol.clone(), pairs, legs, sides, assets });
+        }
+        println!("> interned {} symbols for {} rings.", self.symbols.len(), interned_rings.len());
+        return interned_rings;
+    }
+
+    /// Intern one pair, None when `quantity_info` doesn't know it.
+    pub fn add_symbol(&mut self, symbol: &str, quantity_info: &HashMap<String, QuantityInfo>) -> Option<SymbolId> {
+        if !quantity_info.contains_key(symbol) { return None; }
+        return Some(self.intern(symbol, quantity_info));
+    }
+
+    fn intern(&mut self, symbol: &str, quantity_info: &HashMap<String, QuantityInfo>) -> SymbolId {
+        if let Some(id) = self.ids.get(symbol) { return *id; }
+        let id = self.symbols.len();
+        self.symbols.push(symbol.to_string());
+        self.ids.insert(symbol.to_string(), id);
+        self.quantity.push(quantity_info[symbol].clone());
+        self.books.push([0.0, 0.0]);
+        self.sizes.push([0.0, 0.0]);
+        self.updated.push(false);
+        return id;
+    }
+
+    /// Mark every ticker as stale before a new block.
+    pub fn reset(&mut self) {
+        for updated in self.updated.iter_mut() { *updated = false; }
+    }
+
+    /// Top of book of `symbol` for this block, false when no ring trades it.
+    pub fn set_book(&mut self, symbol: &str, book: [f64;2], size: [f64;2]) -> bool {
+        let id = match self.ids.get(symbol) { Some(id) => *id, None => return false };
+        self.books[id] = book;
+        self.sizes[id] = size;
+        self.updated[id] = true;
+        return true;
+    }
+
+    /// All 3 legs got fresh tickers in this block ?
+    pub fn is_complete(&self, ring: &Ring) -> bool {
+        return ring.legs.iter().all(|id| self.updated[*id]);
+    }
+}
+
+/// Rings of the recorded exchangeInfo, shared by the tests of every model.
+#[cfg(test)]
+pub mod fixture {
+    use std::collections::HashMap;
+    use crate::exchangeinfo::{self, SymbolInfo};
+    use crate::analyzer::{discover_rings, RingLeg};
+    use super::{Ring, Snapshot};
+
+    pub const EXCHANGE_INFO:&str = include_str!("../../tests/fixtures/exchange_info.json");
+    /// [ bid, ask ] of XTZBUSD, XTZBNB & BNBBUSD.
+    pub const BOOKS:[[f64;2];3] = [[0.999, 1.0], [0.0034, 0.003401], [300.0, 300.1]];
+
+    /// XTZ ring of `symbols`, BUSD as stablecoin & BNB as bridge.
+    pub fn xtz_legs(symbols: &HashMap<String, SymbolInfo>) -> HashMap<String, Vec<RingLeg>> {
+        return discover_rings(symbols, "BUSD", "BNB", &[]).into_iter().filter(|(symbol, _)| symbol == "XTZ").collect();
+    }
+
+    /// XTZ ring of the recorded exchangeInfo, books [ bid, ask ] & sizes of its 3 legs set.
+    pub fn xtz(books: [[f64;2];3], sizes: [f64;3]) -> (Snapshot, Ring) {
+        let legs = xtz_legs(&exchangeinfo::symbols_from(EXCHANGE_INFO).unwrap());
+        let pairs: Vec<String> = legs.values().flatten().map(|leg| leg.symbol.clone()).collect();
+        let (mut snapshot, rings) = Snapshot::new(&legs, &exchangeinfo::quantity_from(EXCHANGE_INFO, &pairs).unwrap());
+        let ring = rings.into_iter().next().unwrap();
+        for (leg, pair) in ring.pairs.iter().enumerate() { snapshot.set_book(pair, books[leg], [sizes[leg], sizes[leg]]); }
+        return (snapshot, ring);
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use configparser::ini::Ini;
+
+use binance::account::Account;
+
+use crate::error::{Action, Result};
+use crate::exchangeinfo::QuantityInfo;
+use crate::userdata::UserData;
+use super::Side;
+use super::executor::Fill;
+use super::fees::FeeModel;
+use super::orders::{OrderKind, OrderPolicy};
+use super::reconcile::Traded;
+use super::snapshot::{Ring, Snapshot, SymbolId};
+
+/// What a strategy expects from an intent, journaled against what it realized.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Expected {
+    pub invest: f64, // in stablecoin
+    pub profit: f64,
+    pub fees: f64,
+    pub fees_in_bnb: f64
+}
+
+/// A ring, traded leg after leg by the executor ( or leg b then a & c at once when parallel ).
+pub struct RingIntent {
+    pub ring: Ring,
+    pub prices: [f64;3],
+    pub invest: f64,
+    pub orders: OrderPolicy,
+    pub is_parallel: bool,
+    pub expected: Expected
+}
+
+/// One order, cancelled when it rests more than `max_polls`.
+pub struct OrderIntent {
+    pub symbol: String,
+    pub id: SymbolId,
+    pub base: String,
+    pub quote: String,
+    pub side: Side,
+    pub kind: OrderKind,
+    pub price: f64,
+    pub qty: f64, // base
+    pub max_polls: u32,
+    pub expected: Expected
+}
+
+/// What a strategy wants traded this block, the runner executes it.
+pub enum Intent {
+    Ring(RingIntent),
+    Order(OrderIntent)
+}
+
+impl Intent {
+    /// Ring symbol or order pair, client ids & journal are keyed by it.
+    pub fn symbol(&self) -> &str {
+        match self {
+            Intent::Ring(intent) => &intent.ring.symbol,
+            Intent::Order(intent) => &intent.symbol
+        }
+    }
+
+    pub fn expected(&self) -> &Expected {
+        match self {
+            Intent::Ring(intent) => &intent.expected,
+            Intent::Order(intent) => &intent.expected
+        }
+    }
+
+    /// Pairs & assets it moves, for reconcile.
+    pub fn traded(&self) -> Traded {
+        match self {
+            Intent::Ring(intent) => Traded::ring(&intent.ring),
+            Intent::Order(intent) => Traded { symbol: intent.symbol.clone(), pairs: vec![intent.symbol.clone()],
+                legs: vec![intent.id], assets: vec![(intent.base.clone(), intent.quote.clone())] }
+        }
+    }
+}
+
+/// This block's books, shared by every strategy.
+pub struct MarketView<'a> {
+    pub block: u64,
+    pub now_ms: u128,
+    pub update_ms: u128, // spent fetching books
+    pub snapshot: &'a Snapshot,
+    pub fee_model: &'a FeeModel
+}
+
+/// Account as strategies see it : balances of the assets they asked for,
+/// and the clients for what they keep up themselves ( etc: parallel inventory ).
+pub struct AccountView<'a> {
+    pub account: &'a Account,
+    pub user_data: &'a UserData,
+    pub balances: HashMap<String, f64>
+}
+
+/// What an intent did : fills, the executor's answer and what the runner made of an error.
+pub struct Report<'a> {
+    pub fills: &'a [Fill],
+    pub result: &'a Result<Option<f64>>,
+    pub action: Option<Action>,
+    pub latency_ms: f64 // from block start to the 1st order
+}
+
+/// One way of trading the market. The runner refreshes books every block, hands them over
+/// and executes what comes back; execution, retries, reconcile & journal are shared by all strategies.
+pub trait Strategy {
+    /// Name in `[strategies] enabled` and in the journal's mode column.
+    fn name(&self) -> &str;
+
+    /// Intern the pairs it reads, once before the 1st block.
+    fn intern(&mut self, snapshot: &mut Snapshot, quantity_info: &HashMap<String, QuantityInfo>);
+
+    /// Every strategy has interned, size per-symbol models and take first balances.
+    fn start(&mut self, _config: &Ini, _account: &AccountView, _snapshot: &Snapshot) -> Result<()> { return Ok(()); }
+
+    /// Assets whose balances it needs every block, fetched once for all strategies.
+    fn assets(&self) -> Vec<String> { return vec![]; }
+
+    /// Look at this block, return what to trade, best first.
+    fn on_block(&mut self, market: &MarketView, account: &AccountView) -> Vec<Intent>;
+
+    /// What one of its intents did, return the realized profit in stablecoin.
+    fn on_result(&mut self, market: &MarketView, account: &AccountView, intent: &Intent, report: &Report) -> Result<f64>;
+}
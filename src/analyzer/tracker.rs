@@ -0,0 +1,255 @@
+use std::{
+    thread,
+    time::{Duration, Instant}};
+
+use colored::*;
+
+use binance::account::Account;
+
+use crate::error::{self, Action, Context, Error, Result};
+use crate::retry::RetryPolicy;
+use crate::userdata::{OrderUpdate, UserData};
+use super::Side;
+
+/// Wait between polls when the stream is down.
+const POLLING_ORDER: Duration = Duration::from_millis(500);
+/// Polls after a cancel before we stop waiting for its final state.
+const CANCEL_POLLS:u32 = 10;
+
+/// Where an order is in its life, as the exchange reports it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OrderState {
+    PendingNew, // accepted, not on the book yet ( etc: order list legs )
+    New,
+    PartiallyFilled,
+    PendingCancel,
+    Filled,     // final : all of it
+    Canceled,   // final : by us, may be partially filled
+    Expired,    // final : IOC / FOK leftovers, or expired in match
+    Rejected    // final : never reached the book
+}
+
+impl OrderState {
+    pub fn parse(status: &str) -> Option<OrderState> {
+        match status {
+            "PENDING_NEW" => Some(OrderState::PendingNew),
+            "NEW" => Some(OrderState::New),
+            "PARTIALLY_FILLED" => Some(OrderState::PartiallyFilled),
+            "PENDING_CANCEL" => Some(OrderState::PendingCancel),
+            "FILLED" => Some(OrderState::Filled),
+            "CANCELED" => Some(OrderState::Canceled),
+            "EXPIRED" | "EXPIRED_IN_MATCH" => Some(OrderState::Expired),
+            "REJECTED" => Some(OrderState::Rejected),
+            _ => None
+        }
+    }
+
+    pub fn is_final(&self) -> bool {
+        return matches!(self, OrderState::Filled | OrderState::Canceled | OrderState::Expired | OrderState::Rejected);
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            OrderState::PendingNew => 0,
+            OrderState::New => 1,
+            OrderState::PartiallyFilled => 2,
+            OrderState::PendingCancel => 3,
+            _ => 4
+        }
+    }
+
+    /// Orders only move forward : a final state never changes, a fill never goes back to NEW.
+    pub fn can_move_to(&self, next: OrderState) -> bool {
+        return !self.is_final() && next.rank() >= self.rank();
+    }
+}
+
+/// One order followed from placement to a final state, with what it filled so far.
+#[derive(Clone, Debug)]
+pub struct TrackedOrder {
+    pub symbol: String,
+    pub side: Side,
+    pub order_id: u64,
+    pub qty: f64,          // asked
+    pub state: OrderState,
+    pub executed_qty: f64, // cumulative base filled
+    pub quote_qty: f64,    // cumulative quote filled
+    pub polls: u32,
+    started: Instant,
+    seen_seq: u64
+}
+
+impl TrackedOrder {
+    pub fn new(symbol: &str, side: Side, order_id: u64, qty: f64) -> TrackedOrder {
+        println!("> order: #{} for {} {}", order_id.to_string().yellow(), qty.to_string().green(), symbol.green());
+        return TrackedOrder { symbol: symbol.to_string(), side, order_id, qty, state: OrderState::New,
+            executed_qty: 0.0, quote_qty: 0.0, polls: 0, started: Instant::now(), seen_seq: 0 };
+    }
+
+    /// Apply an update, false when it's older than what we know ( etc: REST behind the stream ).
+    pub fn apply(&mut self, update: &OrderUpdate) -> Result<bool> {
+        let next = OrderState::parse(&update.status)
+            .ok_or_else(|| Error::Halt(format!("#{} {} has unknown status {}", self.order_id, self.symbol, update.status)))?;
+        if !self.state.can_move_to(next) || update.executed_qty < self.executed_qty { return Ok(false); }
+        if next != self.state {
+            println!("> order: #{} {:?} > {:?} ( {}/{} {} )", self.order_id, self.state, next, update.executed_qty, self.qty, self.symbol);
+        }
+        self.state = next;
+        self.executed_qty = update.executed_qty;
+        self.quote_qty = self.quote_qty.max(update.quote_qty);
+        return Ok(true);
+    }
+
+    pub fn is_filled(&self) -> bool { return self.state == OrderState::Filled; }
+
+    /// What was asked but not filled, only meaningful once final.
+    pub fn remaining(&self) -> f64 { return (self.qty - self.executed_qty).max(0.0); }
+
+    pub fn avg_price(&self) -> f64 {
+        return if self.executed_qty > 0.0 { self.quote_qty / self.executed_qty } else { 0.0 };
+    }
+
+    pub fn elapsed_ms(&self) -> u128 { return self.started.elapsed().as_millis(); }
+}
+
+/// Latest state of an order: pushed by user data stream, or REST when stream has nothing.
+fn fetch(account: &Account, user_data: &UserData, order: &mut TrackedOrder, wait: Duration) -> Result<()> {
+    if let Some(update) = user_data.wait_order(order.order_id, order.seen_seq, wait) {
+        order.seen_seq = update.seq;
+        if order.apply(&update)? { return Ok(()); }
+    }
+    let answer = account.order_status(&order.symbol, order.order_id)?;
+    order.apply(&OrderUpdate {
+        orig_qty: error::parse_f64(&answer.orig_qty, "origQty")?,
+        executed_qty: error::parse_f64(&answer.executed_qty, "executedQty")?,
+        quote_qty: answer.cummulative_quote_qty.parse::<f64>().unwrap_or(0.0),
+        symbol: answer.symbol,
+        order_id: answer.order_id,
+        client_order_id: answer.client_order_id,
+        status: answer.status,
+        event_time: answer.update_time,
+        seq: 0
+    })?;
+    return Ok(());
+}
+
+/// Follow `order` until it reaches a final state. `should_cancel` is asked after every poll,
+/// true cancels what's left and waits for the final state, so a partial fill is never lost.
+pub fn wait(account: &Account, user_data: &UserData, order: &mut TrackedOrder, retry: &RetryPolicy,
+    mut should_cancel: impl FnMut(&TrackedOrder) -> bool) -> Result<()> {
+    loop {
+        // with stream connected, waiting on it replaces the sleep between polls.
+        let wait = if order.polls > 0 { POLLING_ORDER } else { Duration::from_millis(0) };
+        match fetch(account, user_data, order, wait) {
+            Ok(_) => {},
+            // keep polling through transient errors, give up on the rest.
+            Err(e) if retry.classify(&e) == Action::Retry => println!("> error: {}", e),
+            Err(e) => return Err(e).context(format!("order #{} {}", order.order_id, order.symbol))
+        }
+        if order.state.is_final() { return Ok(()); }
+        if should_cancel(order) { return cancel(account, user_data, order, retry); }
+        if order.polls > 0 && !user_data.is_connected() { thread::sleep(POLLING_ORDER); }
+        order.polls += 1;
+    }
+}
+
+/// Cancel what's left of `order` and wait for its final state, fills up to the cancel included.
+pub fn cancel(account: &Account, user_data: &UserData, order: &mut TrackedOrder, retry: &RetryPolicy) -> Result<()> {
+    match account.cancel_order(&order.symbol, order.order_id).map_err(Error::from) {
+        Ok(_) => println!("> cancelled #{} after {} polls.", order.order_id.to_string().yellow(), order.polls),
+        // -2011 : already final ( filled in between ), its state tells.
+        Err(Error::Exchange { code: -2011, .. }) => {},
+        Err(e) if retry.classify(&e) == Action::Retry => println!("> error: cancel #{}: {}", order.order_id, e),
+        Err(e) => return Err(e).context(format!("cancel #{} {}", order.order_id, order.symbol))
+    }
+    for _ in 0..CANCEL_POLLS {
+        fetch(account, user_data, order, POLLING_ORDER).context(format!("order #{} {}", order.order_id, order.symbol))?;
+        if order.state.is_final() { return Ok(()); }
+        if !user_data.is_connected() { thread::sleep(POLLING_ORDER); }
+    }
+    return Err(Error::Halt(format!("#{} {} still {:?} after cancel", order.order_id, order.symbol, order.state)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use configparser::ini::Ini;
+    use serde_json::json;
+    use binance::api::Binance;
+    use binance::config::Config;
+    use crate::mock::{MockExchange, Scenario};
+
+    fn update(status: &str, executed_qty: f64, quote_qty: f64) -> OrderUpdate {
+        return OrderUpdate { symbol: String::from("XTZBNB"), order_id: 7, client_order_id: String::from("rg-test-0-XTZ-b"),
+            status: status.to_string(), orig_qty: 100.0, executed_qty, quote_qty, event_time: 0, seq: 0 };
+    }
+
+    #[test]
+    fn states_only_move_forward() {
+        assert!(OrderState::New.can_move_to(OrderState::PartiallyFilled));
+        assert!(OrderState::PartiallyFilled.can_move_to(OrderState::Canceled));
+        assert!(OrderState::New.can_move_to(OrderState::Rejected));
+        assert!(!OrderState::PartiallyFilled.can_move_to(OrderState::New));
+        assert!(!OrderState::Filled.can_move_to(OrderState::Canceled));
+        assert!(!OrderState::Canceled.can_move_to(OrderState::Filled));
+        assert_eq!(OrderState::parse("EXPIRED_IN_MATCH"), Some(OrderState::Expired));
+        assert_eq!(OrderState::parse("PENDING_NEW"), Some(OrderState::PendingNew));
+        assert!(OrderState::PendingNew.can_move_to(OrderState::New));
+        assert!(!OrderState::New.can_move_to(OrderState::PendingNew));
+    }
+
+    #[test]
+    fn partial_fills_accumulate_until_cancel() {
+        let mut order = TrackedOrder::new("XTZBNB", Side::Sell, 7, 100.0);
+        assert!(order.apply(&update("PARTIALLY_FILLED", 20.0, 0.068)).unwrap());
+        assert!(order.apply(&update("PARTIALLY_FILLED", 60.0, 0.204)).unwrap());
+        assert!(order.apply(&update("CANCELED", 60.0, 0.204)).unwrap());
+        assert_eq!(order.state, OrderState::Canceled);
+        assert_eq!(order.executed_qty, 60.0);
+        assert!((order.remaining() - 40.0).abs() < 1e-9);
+        assert!((order.avg_price() - 0.0034).abs() < 1e-12);
+    }
+
+    #[test]
+    fn stale_updates_are_ignored() {
+        let mut order = TrackedOrder::new("XTZBNB", Side::Sell, 7, 100.0);
+        order.apply(&update("PARTIALLY_FILLED", 60.0, 0.204)).unwrap();
+        // REST behind the stream
+        assert!(!order.apply(&update("NEW", 0.0, 0.0)).unwrap());
+        assert!(!order.apply(&update("PARTIALLY_FILLED", 20.0, 0.068)).unwrap());
+        assert_eq!(order.executed_qty, 60.0);
+        order.apply(&update("FILLED", 100.0, 0.34)).unwrap();
+        assert!(!order.apply(&update("CANCELED", 100.0, 0.34)).unwrap());
+        assert!(order.is_filled());
+    }
+
+    #[test]
+    fn unknown_status_halts() {
+        let mut order = TrackedOrder::new("XTZBNB", Side::Sell, 7, 100.0);
+        // known, only behind the NEW we start from.
+        assert!(!order.apply(&update("PENDING_NEW", 0.0, 0.0)).unwrap());
+        assert!(matches!(order.apply(&update("PENDING_SOMETHING", 0.0, 0.0)), Err(Error::Halt(_))));
+    }
+
+    #[test]
+    fn silent_stream_falls_back_to_rest() {
+        let scenario: Scenario = serde_json::from_value(json!({
+            "symbols": [{ "symbol": "XTZBNB", "base": "XTZ", "quote": "BNB" }],
+            "balances": { "XTZ": 100.0 },
+            "prices": [{ "XTZBNB": [0.0034, 0.0035] }],
+            "fills": { "XTZBNB": { "mode": "fill", "after_polls": 1 } }
+        })).unwrap();
+        let mock = MockExchange::start(scenario).unwrap();
+        let endpoint = Config::default().set_rest_api_endpoint(mock.url.clone());
+        let account: Account = Binance::new_with_config(Some("key".into()), Some("secret".into()), &endpoint);
+        let order_id = account.limit_sell("XTZBNB", 100.0, 0.0035).unwrap().order_id;
+        // stream saw NEW, then missed the fill ( reconnect, keepalive gap ).
+        let user_data = UserData::fed(vec![OrderUpdate { order_id, ..update("NEW", 0.0, 0.0) }]);
+
+        let mut order = TrackedOrder::new("XTZBNB", Side::Sell, order_id, 100.0);
+        wait(&account, &user_data, &mut order, &RetryPolicy::from_config(&Ini::new()), |order| order.polls > 5).unwrap();
+        assert!(order.is_filled());
+        assert_eq!(order.executed_qty, 100.0);
+        assert_eq!(mock.orders()[0].status, "FILLED");
+    }
+}
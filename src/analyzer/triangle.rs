@@ -0,0 +1,181 @@
+use std::{
+    cmp::Ordering,
+    collections::HashMap};
+
+use colored::*;
+use configparser::ini::Ini;
+
+use crate::error::{Action, Context, Result};
+use crate::exchangeinfo::QuantityInfo;
+use crate::quarantine::Quarantine;
+use super::{IS_DEBUG, MAX_INVEST, SAFE_LIFETIME, RingLeg, RingModels};
+use super::executor;
+use super::latency::LatencyModel;
+use super::lifetime::LifetimeTracker;
+use super::orders::OrderPolicy;
+use super::pricing::PricingPolicy;
+use super::rebalancer::Rebalancer;
+use super::scoring::FillModel;
+use super::snapshot::{Ring, Snapshot};
+use super::strategy::{AccountView, Expected, Intent, MarketView, Report, RingIntent, Strategy};
+
+/// Triangular arbitrage : buy SYMBOL/stablecoin > sell SYMBOL/bridge > sell bridge/stablecoin,
+/// the best ring that stayed profitable long enough is traded every block.
+pub struct Triangle {
+    legs: HashMap<String, Vec<RingLeg>>, // discovered, interned into `rings`
+    rings: Vec<Ring>,
+    quarantine: Quarantine,
+    policy: PricingPolicy,
+    order_policy: OrderPolicy,
+    is_parallel: bool,
+    stablecoin: String,
+    // sized in start, once every strategy interned its pairs.
+    fill_model: FillModel,
+    latency: LatencyModel,
+    lifetimes: Option<LifetimeTracker>,
+    rebalancer: Option<Rebalancer>,
+    is_rebalancer_started: bool,
+    virtual_account: f64
+}
+
+impl Triangle {
+    /// `[pricing]`, `[orders]`, `[configs] mode = linear | parallel`, parallel holds inventory of every asset.
+    pub fn new(config: &Ini, quarantine: Quarantine, legs: HashMap<String, Vec<RingLeg>>) -> Result<Triangle> {
+        let (stablecoin, _) = super::bridges(config)?;
+        let empty = Snapshot::empty();
+        return Ok(Triangle {
+            legs,
+            rings: vec![],
+            quarantine,
+            policy: PricingPolicy::from_config(config),
+            order_policy: OrderPolicy::from_config(config),
+            is_parallel: config.get("configs", "mode").is_some_and(|mode| mode.to_lowercase() == "parallel"),
+            stablecoin,
+            fill_model: FillModel::empty(&empty),
+            latency: LatencyModel::with(&empty, 0.0, 0.0),
+            lifetimes: None,
+            rebalancer: None,
+            is_rebalancer_started: false,
+            virtual_account: 0.0
+        });
+    }
+}
+
+impl Strategy for Triangle {
+    fn name(&self) -> &str { return "triangle"; }
+
+    fn intern(&mut self, snapshot: &mut Snapshot, quantity_info: &HashMap<String, QuantityInfo>) {
+        self.rings = snapshot.add_rings(&self.legs, quantity_info);
+    }
+
+    fn start(&mut self, config: &Ini, account: &AccountView, snapshot: &Snapshot) -> Result<()> {
+        self.fill_model = FillModel::new(snapshot);
+        self.latency = LatencyModel::new(config, snapshot);
+        // as blocks, longer a trade last, more stable price is.
+        self.lifetimes = Some(LifetimeTracker::new(config, &self.rings, SAFE_LIFETIME));
+        let (stablecoin, bridge) = super::bridges(config)?;
+        if self.is_parallel {
+            self.rebalancer = Rebalancer::new(config, &self.rings, snapshot, &stablecoin, &bridge, MAX_INVEST);
+        }
+        self.virtual_account = executor::get_balance(account.account, account.user_data, &self.stablecoin).context("initial balance")?;
+        return Ok(());
+    }
+
+    fn on_block(&mut self, market: &MarketView, account: &AccountView) -> Vec<Intent> {
+        let snapshot = market.snapshot;
+        self.fill_model.observe(snapshot);
+        self.latency.observe(snapshot, market.now_ms);
+        if let Some(rebalancer) = self.rebalancer.as_mut() {
+            if !self.is_rebalancer_started { rebalancer.start(account.account, account.user_data, snapshot); self.is_rebalancer_started = true; }
+        }
+        // Get computed result
+        let quarantine = &self.quarantine;
+        let models = RingModels { policy: &self.policy, fill: &self.fill_model, fees: market.fee_model, latency: &self.latency };
+        let mut round_result = super::compute_rings(&self.rings, self.virtual_account, snapshot, models, &|symbol| quarantine.contains(symbol));
+        // QUARANTINE: anomalous rings are out until cooldown.
+        self.quarantine.purge();
+        for result in round_result.iter().filter(|result| result.is_anomaly) {
+            let ring = &self.rings[result.ring];
+            self.quarantine.add(&ring.symbol, super::build_evidence(ring, snapshot, &self.policy, result));
+        }
+        round_result.retain(|result| !result.is_anomaly);
+        let arbitrage_count = round_result.len();
+        let lifetimes = match self.lifetimes.as_mut() { Some(lifetimes) => lifetimes, None => return vec![] };
+        lifetimes.update(&self.rings, &round_result);
+
+        if arbitrage_count == 0 {
+            if IS_DEBUG { println!("> no arbitrage chances."); }
+            return vec![];
+        }
+        println!("\n> ===================[ Block {} ]=================== <", market.block.to_string().yellow());
+        // tickers time
+        println!("#{}: updated orderbooks in {} ms",
+        market.block.to_string().yellow(), market.update_ms.to_string().yellow());
+        // Sort by Expected Value ( profit x fill probability )
+        round_result.sort_by(|a, b| b.expected_value.partial_cmp(&a.expected_value).unwrap_or(Ordering::Equal));
+        println!("> found {} arbitrages.", arbitrage_count);
+        println!("_____________________________________________");
+        for result in &round_result {
+            println!("| {:.2}% = ${:.2} | fill {:>5.1}% | ev ${:.4} | {}",
+            result.percentage, result.profit, result.probability * 100.0, result.expected_value, self.rings[result.ring].symbol);
+        };
+        println!("_____________________________________________");
+        println!();
+        // best ring which lived long enough
+        let safe_trade = round_result.iter()
+            .filter(|result| lifetimes.is_safe(result.ring))
+            .find_map(|result| lifetimes.get(result.ring).map(|alive| (result, alive)));
+        let (trade, alive) = match safe_trade { Some(safe_trade) => safe_trade, None => return vec![] };
+        let trade_ring = &self.rings[trade.ring];
+        println!("> best: {} | {:.2}% = ${:.2} | alive: {} blocks / {} ms.",
+        trade_ring.symbol, trade.percentage, trade.profit, alive.blocks, alive.duration_ms());
+        // Build ring prices
+        let ring_prices = super::build_ring(trade_ring, snapshot, &self.policy);
+        println!("> best: {} > {} > {}", trade_ring.symbol, trade_ring.assets[1].1, trade_ring.assets[0].1);
+        println!("> best: buy {} > sell {} > sell {}", ring_prices[0], ring_prices[1], ring_prices[2]);
+        return vec![Intent::Ring(RingIntent {
+            ring: trade_ring.clone(),
+            prices: ring_prices,
+            invest: trade.optimal_invest,
+            orders: self.order_policy.clone(),
+            is_parallel: self.is_parallel,
+            expected: Expected { invest: trade.optimal_invest, profit: trade.profit, fees: trade.fees, fees_in_bnb: trade.fees_in_bnb }
+        })];
+    }
+
+    fn on_result(&mut self, market: &MarketView, account: &AccountView, intent: &Intent, report: &Report) -> Result<f64> {
+        let intent = match intent { Intent::Ring(intent) => intent, Intent::Order(_) => return Ok(0.0) };
+        let snapshot = market.snapshot;
+        // drift of parallel inventory, rebalanced apart from arbitrage PnL.
+        if let Some(rebalancer) = self.rebalancer.as_mut() { rebalancer.after_ring(account.account, account.user_data, snapshot, market.block)?; }
+        for fill in report.fills { self.fill_model.record_fill(snapshot, &fill.symbol, fill.elapsed_ms as f64); }
+        if !report.fills.is_empty() {
+            self.fill_model.save(snapshot);
+            self.latency.record(&intent.ring, snapshot, &intent.prices, report.latency_ms, report.fills);
+        }
+        match report.result {
+            // None : a leg wasn't filled and the ring was dropped.
+            Ok(new_balance) => {
+                let mut final_profit = 0.0;
+                if let Some(balance) = new_balance.filter(|balance| *balance > 0.0) {
+                    final_profit = balance - self.virtual_account;
+                    self.virtual_account = balance;
+                    println!("#{}: ${} - trade ${} {} for ${}/${}",
+                        market.block.to_string().yellow(),
+                        format!("{:.2}", self.virtual_account).green(),
+                        format!("{:.2}", intent.invest).green(),
+                        intent.ring.symbol.green(),
+                        format!("{:.2}", final_profit).yellow(),
+                        format!("{:.2}", intent.expected.profit).yellow());
+                }
+                return Ok(final_profit);
+            },
+            Err(_) => {
+                if report.action == Some(Action::RefreshBalances) {
+                    self.virtual_account = executor::get_balance(account.account, account.user_data, &self.stablecoin).context("refresh balances")?;
+                }
+                return Ok(0.0);
+            }
+        }
+    }
+}
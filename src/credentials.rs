@@ -0,0 +1,274 @@
+use std::{
+    fmt, fs, env,
+    io::{self, Write},
+    process::{Command, Stdio},
+    sync::OnceLock};
+
+use colored::*;
+use configparser::ini::Ini;
+use serde::{Deserialize, Serialize};
+
+use openssl::hash::MessageDigest;
+use openssl::pkcs5::pbkdf2_hmac;
+use openssl::rand::rand_bytes;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+
+use crate::rest::SignedClient;
+
+const ENV_PREFIX:&str = "RAILGUN";
+const PASSPHRASE_ENV:&str = "RAILGUN_KEYSTORE_PASSPHRASE";
+const KDF:&str = "pbkdf2-sha256";
+const KDF_ITERATIONS:usize = 600_000;
+const SALT_LEN:usize = 16;
+const NONCE_LEN:usize = 12; // AES-GCM
+const TAG_LEN:usize = 16;
+
+static MAIN_KEYS: OnceLock<Credentials> = OnceLock::new();
+
+/// API & secret key of one account. Debug never prints them.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Credentials {
+    pub api_key: String,
+    pub secret_key: String,
+    #[serde(skip)]
+    pub source: String // where they came from, etc: env RAILGUN_API_KEY
+}
+
+impl Credentials {
+    /// First 4 chars of the api key, enough to tell keys apart in logs.
+    pub fn redacted(&self) -> String {
+        let head: String = self.api_key.chars().take(4).collect();
+        return format!("{}****", head);
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Credentials {{ api_key: {}, secret_key: ****, source: {} }}", self.redacted(), self.source)
+    }
+}
+
+/// Encrypted `Credentials` : AES-256-GCM with a key derived from a passphrase.
+#[derive(Serialize, Deserialize)]
+struct Keystore {
+    kdf: String,
+    iterations: usize,
+    salt: String,   // hex
+    nonce: String,  // hex
+    tag: String,    // hex
+    ciphertext: String // hex
+}
+
+/// Keys of `[keys]`, resolved once and kept for the whole run.
+pub fn load(config: &Ini) -> Result<&'static Credentials, String> {
+    if let Some(keys) = MAIN_KEYS.get() { return Ok(keys); }
+    let keys = resolve(config, "keys", ENV_PREFIX)?;
+    return Ok(MAIN_KEYS.get_or_init(|| keys));
+}
+
+/// Keys of a config section, first found of :
+/// 1. env `PREFIX_API_KEY` & `PREFIX_SECRET_KEY`
+/// 2. `keystore` : encrypted file, passphrase from env RAILGUN_KEYSTORE_PASSPHRASE or asked on stdin
+/// 3. `secrets_file` : ini file with `[keys]` api_key & secret_key, must not be readable by others
+/// 4. `api_key` & `secret_key` in the section itself, plaintext in config.toml ( deprecated )
+pub fn resolve(config: &Ini, section: &str, env_prefix: &str) -> Result<Credentials, String> {
+    let env_api = format!("{}_API_KEY", env_prefix);
+    let env_secret = format!("{}_SECRET_KEY", env_prefix);
+    if let (Ok(api_key), Ok(secret_key)) = (env::var(&env_api), env::var(&env_secret)) {
+        return Ok(Credentials { api_key, secret_key, source: format!("env {}", env_api) });
+    }
+    if let Some(path) = config.get(section, "keystore") {
+        let passphrase = passphrase(&format!("passphrase of {}: ", path))?;
+        let mut keys = open_keystore(&path, &passphrase)?;
+        keys.source = format!("keystore {}", path);
+        return Ok(keys);
+    }
+    if let Some(path) = config.get(section, "secrets_file") {
+        check_private(&path)?;
+        let mut secrets = Ini::new();
+        secrets.load(&path).map_err(|e| format!("can't read {}: {}", path, e))?;
+        return match (secrets.get("keys", "api_key"), secrets.get("keys", "secret_key")) {
+            (Some(api_key), Some(secret_key)) => Ok(Credentials { api_key, secret_key, source: format!("secrets file {}", path) }),
+            _ => Err(format!("{} has no [keys] api_key & secret_key", path))
+        };
+    }
+    if let (Some(api_key), Some(secret_key)) = (config.get(section, "api_key"), config.get(section, "secret_key")) {
+        println!("> keys: [{}] keys are plaintext in config.toml, move them to env, secrets_file or keystore.", section);
+        return Ok(Credentials { api_key, secret_key, source: format!("config [{}]", section) });
+    }
+    return Err(format!("no keys for [{}], set {} & {}, keystore or secrets_file", section, env_api, env_secret));
+}
+
+/// Refuse a secrets file other users can read or write.
+#[cfg(unix)]
+fn check_private(path: &str) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = fs::metadata(path).map_err(|e| format!("can't read {}: {}", path, e))?.permissions().mode();
+    if mode & 0o077 != 0 {
+        return Err(format!("{} is open to others ( {:o} ), chmod 600 it", path, mode & 0o777));
+    }
+    return Ok(());
+}
+
+#[cfg(not(unix))]
+fn check_private(_path: &str) -> Result<(), String> {
+    return Ok(());
+}
+
+/// Passphrase from env, else read from stdin without echo.
+fn passphrase(prompt: &str) -> Result<String, String> {
+    if let Ok(passphrase) = env::var(PASSPHRASE_ENV) { return Ok(passphrase); }
+    print!("> keys: {}", prompt);
+    let _ = io::stdout().flush();
+    let echo = |on: bool| Command::new("stty").arg(if on { "echo" } else { "-echo" }).stdin(Stdio::inherit()).status();
+    let _ = echo(false);
+    let mut line = String::new();
+    let read = io::stdin().read_line(&mut line);
+    let _ = echo(true);
+    println!();
+    read.map_err(|e| e.to_string())?;
+    return Ok(line.trim_end_matches(&['\r', '\n'][..]).to_string());
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], iterations: usize) -> Result<[u8;32], String> {
+    let mut key = [0u8;32];
+    pbkdf2_hmac(passphrase.as_bytes(), salt, iterations, MessageDigest::sha256(), &mut key).map_err(|e| e.to_string())?;
+    return Ok(key);
+}
+
+fn open_keystore(path: &str, passphrase: &str) -> Result<Credentials, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+    let keystore: Keystore = serde_json::from_str(&content).map_err(|e| format!("{} is not a keystore: {}", path, e))?;
+    if keystore.kdf != KDF { return Err(format!("{} uses unknown kdf {}", path, keystore.kdf)); }
+    let decode = |text: &str| hex::decode(text).map_err(|_| format!("{} is corrupted", path));
+    let key = derive_key(passphrase, &decode(&keystore.salt)?, keystore.iterations)?;
+    let plaintext = decrypt_aead(Cipher::aes_256_gcm(), &key, Some(&decode(&keystore.nonce)?), KDF.as_bytes(),
+        &decode(&keystore.ciphertext)?, &decode(&keystore.tag)?)
+        .map_err(|_| format!("wrong passphrase for {}", path))?;
+    return serde_json::from_slice::<Credentials>(&plaintext).map_err(|_| format!("{} is corrupted", path));
+}
+
+/// Encrypt `keys` into a new keystore at `path`, passphrase asked twice unless set in env.
+pub fn seal(keys: &Credentials, path: &str) -> Result<(), String> {
+    let passphrase = passphrase("new passphrase: ")?;
+    if env::var(PASSPHRASE_ENV).is_err() && passphrase != self::passphrase("again: ")? {
+        return Err(String::from("passphrases don't match"));
+    }
+    return seal_with(keys, path, &passphrase);
+}
+
+fn seal_with(keys: &Credentials, path: &str, passphrase: &str) -> Result<(), String> {
+    if passphrase.is_empty() { return Err(String::from("empty passphrase")); }
+    let mut salt = [0u8;SALT_LEN];
+    let mut nonce = [0u8;NONCE_LEN];
+    let mut tag = [0u8;TAG_LEN];
+    rand_bytes(&mut salt).and_then(|_| rand_bytes(&mut nonce)).map_err(|e| e.to_string())?;
+    let key = derive_key(passphrase, &salt, KDF_ITERATIONS)?;
+    let plaintext = serde_json::to_vec(keys).map_err(|e| e.to_string())?;
+    let ciphertext = encrypt_aead(Cipher::aes_256_gcm(), &key, Some(&nonce), KDF.as_bytes(), &plaintext, &mut tag)
+        .map_err(|e| e.to_string())?;
+    let keystore = Keystore {
+        kdf: KDF.to_string(),
+        iterations: KDF_ITERATIONS,
+        salt: hex::encode(salt),
+        nonce: hex::encode(nonce),
+        tag: hex::encode(tag),
+        ciphertext: hex::encode(ciphertext)
+    };
+    let content = serde_json::to_string_pretty(&keystore).map_err(|e| e.to_string())?;
+    fs::write(path, content).map_err(|e| format!("can't write {}: {}", path, e))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = fs::set_permissions(path, fs::Permissions::from_mode(0o600));
+    }
+    return Ok(());
+}
+
+/// GET /sapi/v1/account/apiRestrictions, not on testnet.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiRestrictions {
+    ip_restrict: bool,
+    enable_withdrawals: bool,
+    enable_spot_and_margin_trading: bool
+}
+
+/// `keys check` : which keys are used and what the account endpoint lets them do.
+/// The bot only needs spot trading, withdrawals enabled is a risk.
+pub fn check(config: &Ini) {
+    let keys = match load(config) {
+        Ok(keys) => keys,
+        Err(e) => { println!("> keys: {}", e); return; }
+    };
+    println!("> keys: {} from {}.", keys.redacted(), keys.source);
+    let account = match crate::analyzer::get_account(config) {
+        Ok(account) => account,
+        Err(e) => { println!("> keys: {}", e); return; }
+    };
+    match account.get_account() {
+        Ok(answer) => {
+            println!("> keys: trade {} | withdraw {} | deposit {}.", answer.can_trade, answer.can_withdraw, answer.can_deposit);
+            if !answer.can_trade { println!("> keys: {}", "can't trade, enable spot trading for this key.".red()); }
+        },
+        Err(e) => { println!("> keys: {} {:?}", "rejected by account endpoint:".red(), e.0); return; }
+    }
+    match SignedClient::from_config(config, account.recv_window).and_then(|client| client.get_signed::<ApiRestrictions>("/sapi/v1/account/apiRestrictions", Default::default())) {
+        Ok(restrictions) => {
+            println!("> keys: spot trading {} | withdrawals {} | ip restricted {}.",
+                restrictions.enable_spot_and_margin_trading, restrictions.enable_withdrawals, restrictions.ip_restrict);
+            if restrictions.enable_withdrawals { println!("> keys: {}", "withdrawals are enabled, disable them for this key.".red()); }
+            if !restrictions.ip_restrict { println!("> keys: {}", "not restricted to trusted IPs.".yellow()); }
+        },
+        Err(e) => println!("> keys: no api restrictions endpoint: {}", e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str) -> String {
+        return env::temp_dir().join(format!("railgun-{}-{}", name, std::process::id())).to_string_lossy().to_string();
+    }
+
+    fn keys() -> Credentials {
+        return Credentials { api_key: String::from("api-key"), secret_key: String::from("secret-key"), source: String::new() };
+    }
+
+    #[test]
+    fn sealed_keys_open_with_the_passphrase() {
+        let path = temp_file("keystore.json");
+        seal_with(&keys(), &path, "correct horse").unwrap();
+        let opened = open_keystore(&path, "correct horse").unwrap();
+        assert_eq!((opened.api_key.as_str(), opened.secret_key.as_str()), ("api-key", "secret-key"));
+        assert!(!fs::read_to_string(&path).unwrap().contains("secret-key"));
+        assert!(check_private(&path).is_ok());
+
+        let wrong = open_keystore(&path, "battery staple");
+        fs::remove_file(&path).unwrap();
+        assert_eq!(wrong.unwrap_err(), format!("wrong passphrase for {}", path));
+        assert!(seal_with(&keys(), &path, "").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn secrets_file_must_be_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = temp_file("secrets.toml");
+        fs::write(&path, "[keys]\napi_key = api-key\nsecret_key = secret-key\n").unwrap();
+        let mut config = Ini::new();
+        config.set("keys", "secrets_file", Some(path.clone()));
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        let open = resolve(&config, "keys", "RAILGUN_TEST_UNSET");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        let private = resolve(&config, "keys", "RAILGUN_TEST_UNSET");
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(open.unwrap_err(), format!("{} is open to others ( 644 ), chmod 600 it", path));
+        let keys = private.unwrap();
+        assert_eq!((keys.api_key.as_str(), keys.secret_key.as_str()), ("api-key", "secret-key"));
+        assert_eq!(keys.source, format!("secrets file {}", path));
+    }
+}
@@ -0,0 +1,334 @@
+use std::{
+    thread,
+    cmp::Ordering,
+    sync::Arc,
+    collections::HashMap,
+    time::{Duration, Instant}};
+
+use colored::*;
+use configparser::ini::Ini;
+
+use crate::analyzer::Side;
+use crate::error::{Error, Result};
+use crate::venue::{BinanceVenue, Venue, VenueOrder};
+
+// default of [cross]
+const MIN_PROFIT:f64 = 0.2;      // percent, after fees of both venues
+const MAX_INVEST:f64 = 100.0;    // in quote asset
+const TIMEOUT_MS:u64 = 3000;     // cancel what's not filled after this
+const INTERVAL_MS:u64 = 1000;    // between 2 scans
+const POLLING:Duration = Duration::from_millis(200);
+const CANCEL_POLLS:u32 = 10;     // after a cancel, before we stop waiting for its final state
+
+/// Same pair on every venue, e.g. "BTC/USDT".
+struct Pair {
+    symbol: String,
+    base: String,
+    quote: String
+}
+
+/// Best ask on one venue against best bid on another.
+struct Spread {
+    buy: usize,
+    sell: usize,
+    ask: f64,
+    bid: f64,
+    qty: f64,
+    percentage: f64
+}
+
+/// Free balance of each asset on each venue, and base traded there by us, indexed by venue.
+struct Inventory {
+    balances: Vec<HashMap<String, f64>>,
+    traded: Vec<HashMap<String, f64>> // base bought ( + ) or sold ( - ), NaN once a leg's fill is unknown
+}
+
+impl Inventory {
+    fn new(venues: usize) -> Inventory {
+        return Inventory { balances: vec![HashMap::new(); venues], traded: vec![HashMap::new(); venues] };
+    }
+
+    fn refresh(&mut self, venues: &[Arc<dyn Venue>], assets: &[&str]) {
+        for (index, venue) in venues.iter().enumerate() {
+            for asset in assets {
+                match venue.balance(asset) {
+                    Ok(free) => { self.balances[index].insert(asset.to_string(), free); },
+                    Err(e) => println!("> cross: {} balance of {} failed: {}", venue.name(), asset, e)
+                }
+            }
+        }
+    }
+
+    fn get(&self, venue: usize, asset: &str) -> f64 {
+        return *self.balances[venue].get(asset).unwrap_or(&0.0);
+    }
+
+    fn record(&mut self, venue: usize, asset: &str, qty: f64) {
+        *self.traded[venue].entry(asset.to_string()).or_insert(0.0) += qty;
+    }
+
+    /// Base bought on one venue and not sold on another, NaN when a fill is unknown.
+    fn unhedged(&self, asset: &str) -> f64 {
+        return self.traded.iter().map(|traded| *traded.get(asset).unwrap_or(&0.0)).sum();
+    }
+
+    fn is_hedged(&self, asset: &str) -> bool {
+        return self.unhedged(asset).abs() <= f64::EPSILON; // false on NaN too
+    }
+}
+
+/// Price `pair` on every venue, pick cheapest ask & richest bid,
+/// size it by books, max_invest and inventory, rounded for both venues.
+fn find_spread(venues: &[Arc<dyn Venue>], inventory: &Inventory, pair: &Pair, max_invest: f64) -> Option<Spread> {
+    let mut books = vec![];
+    for (index, venue) in venues.iter().enumerate() {
+        match venue.book(&pair.symbol) {
+            Ok(book) => books.push((index, book)),
+            Err(e) => println!("> cross: {} {} book failed: {}", venue.name(), pair.symbol, e)
+        }
+    }
+    let (buy, ask) = books.iter()
+        .min_by(|a, b| a.1.ask.partial_cmp(&b.1.ask).unwrap_or(Ordering::Equal))
+        .map(|(index, book)| (*index, *book))?;
+    let (sell, bid) = books.iter()
+        .max_by(|a, b| a.1.bid.partial_cmp(&b.1.bid).unwrap_or(Ordering::Equal))
+        .map(|(index, book)| (*index, *book))?;
+    if buy == sell || bid.bid <= ask.ask { return None; }
+
+    let fees = venues[buy].fee() + venues[sell].fee();
+    let percentage = (bid.bid - ask.ask) / ask.ask * 100.0 - fees;
+
+    // we buy with quote on one venue and sell base we already hold on the other.
+    let qty = (max_invest / ask.ask)
+        .min(ask.ask_qty).min(bid.bid_qty)
+        .min(inventory.get(buy, &pair.quote) / ask.ask)
+        .min(inventory.get(sell, &pair.base));
+    let buy_info = venues[buy].quantity_info(&pair.symbol)?;
+    let sell_info = venues[sell].quantity_info(&pair.symbol)?;
+    let qty = buy_info.round_qty(qty).min(sell_info.round_qty(qty));
+    let min_qty = |min_qty: &String| min_qty.parse::<f64>().unwrap_or(0.0);
+    if qty <= 0.0 || qty < min_qty(&buy_info.min_qty) || qty < min_qty(&sell_info.min_qty) { return None; }
+
+    return Some(Spread { buy, sell, ask: ask.ask, bid: bid.bid, qty, percentage });
+}
+
+fn is_final(status: &str) -> bool {
+    return matches!(status, "FILLED" | "CANCELED" | "EXPIRED" | "EXPIRED_IN_MATCH" | "REJECTED");
+}
+
+/// Wait for an order until final or timeout, then cancel the rest and read it again,
+/// so fills up to the cancel are counted. None when its final state can't be read.
+fn settle(venue: &Arc<dyn Venue>, symbol: &str, order_id: u64, timeout: Duration) -> Option<VenueOrder> {
+    let started = Instant::now();
+    while started.elapsed() < timeout {
+        match venue.order_status(symbol, order_id) {
+            Ok(order) if is_final(&order.status) => return Some(order),
+            Ok(_) => {},
+            Err(e) => println!("> cross: {} order {} failed: {}", venue.name(), order_id, e)
+        }
+        thread::sleep(POLLING);
+    }
+    // cancel even when we can't see it, never leave it on the book. -2011 : already final.
+    if let Err(e) = venue.cancel(symbol, order_id) {
+        println!("> cross: {} cancel {} failed: {}", venue.name(), order_id, e);
+    }
+    for _ in 0..CANCEL_POLLS {
+        match venue.order_status(symbol, order_id) {
+            Ok(order) if is_final(&order.status) => return Some(order),
+            Ok(_) => {},
+            Err(e) => println!("> cross: {} order {} failed: {}", venue.name(), order_id, e)
+        }
+        thread::sleep(POLLING);
+    }
+    println!("{} {} order {} {} unknown after cancel.", "> cross:".red(), venue.name(), order_id, symbol);
+    return None;
+}
+
+/// Place both legs at once, each on its own thread, record what they traded and report PnL.
+fn execute(venues: &[Arc<dyn Venue>], inventory: &mut Inventory, pair: &Pair, spread: &Spread, timeout: Duration) {
+    let legs = [(spread.buy, Side::Buy, spread.ask), (spread.sell, Side::Sell, spread.bid)];
+    let threads: Vec<thread::JoinHandle<Option<VenueOrder>>> = legs.iter().map(|(index, side, price)| {
+        let venue = venues[*index].clone();
+        let symbol = pair.symbol.clone();
+        let (side, price, qty) = (*side, *price, spread.qty);
+        thread::spawn(move || {
+            match venue.limit_order(&symbol, side, qty, price) {
+                Ok(order_id) => settle(&venue, &symbol, order_id, timeout),
+                Err(e) => {
+                    println!("> cross: {} {:?} {} failed: {}", venue.name(), side, symbol, e);
+                    Some(VenueOrder { order_id: 0, status: String::from("REJECTED"), executed_qty: 0.0 }) // never placed, nothing traded
+                }
+            }
+        })
+    }).collect();
+    let results: Vec<Option<VenueOrder>> = threads.into_iter().map(|thread| thread.join().unwrap_or(None)).collect();
+
+    // a leg we lost track of may have filled anything, the pair stays unhedged until checked by hand.
+    let bought = results[0].as_ref().map_or(f64::NAN, |order| order.executed_qty);
+    let sold = results[1].as_ref().map_or(f64::NAN, |order| order.executed_qty);
+    inventory.record(spread.buy, &pair.base, bought);
+    inventory.record(spread.sell, &pair.base, -sold);
+    let fees = (bought * spread.ask * venues[spread.buy].fee() + sold * spread.bid * venues[spread.sell].fee()) / 100.0;
+    let pnl = sold * spread.bid - bought * spread.ask - fees;
+    let pnl_text = format!("{:.6} {}", pnl, pair.quote);
+    println!("> cross: {} bought {} on {} | sold {} on {} | PnL = {}",
+        pair.symbol.bold(), bought, venues[spread.buy].name(), sold, venues[spread.sell].name(),
+        if pnl >= 0.0 { pnl_text.green() } else { pnl_text.red() });
+    if !inventory.is_hedged(&pair.base) {
+        println!("{} {} unhedged by {}, no new spread on it until hedged by hand.", "> cross:".red(), pair.base, inventory.unhedged(&pair.base));
+    }
+}
+
+/// Cross-venue mode : same pairs priced on 2+ venues, buy low on one & sell high on another.
+/// `[cross]` venues, symbols ( BASE/QUOTE ), min_profit, max_invest, timeout_ms, interval_ms.
+/// Each venue is a `[venue.NAME]` section, see `BinanceVenue::from_config`.
+pub fn run(config: &Ini) -> Result<()> {
+    let pairs: Vec<Pair> = config.get("cross", "symbols").unwrap_or_default()
+        .split(',')
+        .filter_map(|pair| {
+            let mut assets = pair.trim().split('/');
+            match (assets.next(), assets.next()) {
+                (Some(base), Some(quote)) if !base.is_empty() && !quote.is_empty() => {
+                    let (base, quote) = (base.to_uppercase(), quote.to_uppercase());
+                    Some(Pair { symbol: format!("{}{}", base, quote), base, quote })
+                },
+                _ => None
+            }
+        }).collect();
+    let symbols: Vec<String> = pairs.iter().map(|pair| pair.symbol.clone()).collect();
+    let mut venues: Vec<Arc<dyn Venue>> = vec![];
+    for name in config.get("cross", "venues").unwrap_or_default().split(',').map(|name| name.trim()).filter(|name| !name.is_empty()) {
+        match BinanceVenue::from_config(config, name, &symbols) {
+            Ok(venue) => venues.push(Arc::new(venue)),
+            // a misconfigured venue is our fault, an unreachable one may come back next run.
+            Err(e) if matches!(e.root(), Error::Config { .. }) => return Err(e),
+            Err(e) => println!("> venue {}: {}", name, e)
+        }
+    }
+    if venues.len() < 2 || pairs.is_empty() {
+        println!("> cross: need 2+ venues and 1+ symbols in [cross].");
+        return Ok(());
+    }
+    let min_profit = config.getfloat("cross", "min_profit").unwrap_or(None).unwrap_or(MIN_PROFIT);
+    let max_invest = config.getfloat("cross", "max_invest").unwrap_or(None).unwrap_or(MAX_INVEST);
+    let timeout = Duration::from_millis(config.getuint("cross", "timeout_ms").unwrap_or(None).unwrap_or(TIMEOUT_MS));
+    let interval = Duration::from_millis(config.getuint("cross", "interval_ms").unwrap_or(None).unwrap_or(INTERVAL_MS));
+
+    let mut inventory = Inventory::new(venues.len());
+    let mut assets: Vec<&str> = pairs.iter().flat_map(|pair| vec![pair.base.as_str(), pair.quote.as_str()]).collect();
+    assets.sort();
+    assets.dedup();
+    inventory.refresh(&venues, &assets);
+    println!("> cross: {} pairs on {} venues, min profit {}%.", pairs.len(), venues.len(), min_profit);
+
+    loop {
+        for pair in &pairs {
+            if !inventory.is_hedged(&pair.base) { continue; }
+            let spread = match find_spread(&venues, &inventory, pair, max_invest) {
+                Some(spread) if spread.percentage >= min_profit => spread,
+                _ => continue
+            };
+            println!("> cross: {} buy {} @ {} on {} | sell @ {} on {} | {:.3}%",
+                pair.symbol.bold(), spread.qty, spread.ask, venues[spread.buy].name(),
+                spread.bid, venues[spread.sell].name(), spread.percentage);
+            execute(&venues, &mut inventory, pair, &spread, timeout);
+            inventory.refresh(&venues, &[&pair.base, &pair.quote]);
+        }
+        thread::sleep(interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::mock::{MockExchange, Scenario};
+
+    /// XTZ/BUSD priced at `book` on a mock holding `balances`.
+    fn mock(book: [f64;2], balances: serde_json::Value) -> MockExchange {
+        let scenario: Scenario = serde_json::from_value(json!({
+            "symbols": [{ "symbol": "XTZBUSD", "base": "XTZ", "quote": "BUSD", "step_size": "0.01", "tick_size": "0.001" }],
+            "balances": balances,
+            "prices": [{ "XTZBUSD": book }]
+        })).unwrap();
+        return MockExchange::start(scenario).unwrap();
+    }
+
+    /// One `[venue.N]` per mock, loaded as `cross::run` does.
+    fn venues(mocks: &[&MockExchange], pair: &Pair) -> Vec<Arc<dyn Venue>> {
+        let mut config = Ini::new();
+        for (index, mock) in mocks.iter().enumerate() {
+            let section = format!("venue.{}", index);
+            config.set(&section, "preset", Some(String::from("custom")));
+            config.set(&section, "rest_endpoint", Some(mock.url.clone()));
+            config.set(&section, "ws_endpoint", Some(String::from("ws://127.0.0.1:1/ws")));
+            config.set(&section, "api_key", Some(String::from("key")));
+            config.set(&section, "secret_key", Some(String::from("secret")));
+        }
+        return (0..mocks.len())
+            .map(|index| Arc::new(BinanceVenue::from_config(&config, &index.to_string(), std::slice::from_ref(&pair.symbol)).unwrap()) as Arc<dyn Venue>)
+            .collect();
+    }
+
+    fn xtzbusd() -> Pair {
+        return Pair { symbol: String::from("XTZBUSD"), base: String::from("XTZ"), quote: String::from("BUSD") };
+    }
+
+    #[test]
+    fn buys_on_the_cheap_venue_and_sells_on_the_rich_one() {
+        let rich = mock([1.02, 1.03], json!({ "XTZ": 100.0 }));
+        let cheap = mock([0.99, 1.0], json!({ "BUSD": 1000.0 }));
+        let pair = xtzbusd();
+        let venues = venues(&[&rich, &cheap], &pair);
+        let mut inventory = Inventory::new(venues.len());
+        inventory.refresh(&venues, &["XTZ", "BUSD"]);
+
+        let spread = find_spread(&venues, &inventory, &pair, 50.0).expect("a 2% gap is a spread");
+        assert_eq!((spread.buy, spread.sell), (1, 0));
+        assert_eq!((spread.ask, spread.bid, spread.qty), (1.0, 1.02, 50.0));
+        assert!((spread.percentage - 1.8).abs() < 1e-9);
+
+        execute(&venues, &mut inventory, &pair, &spread, Duration::from_millis(1000));
+        assert!(inventory.is_hedged("XTZ"));
+        let legs = |mock: &MockExchange| mock.orders().iter()
+            .map(|order| (order.side.clone(), order.price, order.executed_qty, order.status.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(legs(&cheap), vec![(String::from("BUY"), 1.0, 50.0, String::from("FILLED"))]);
+        assert_eq!(legs(&rich), vec![(String::from("SELL"), 1.02, 50.0, String::from("FILLED"))]);
+    }
+
+    #[test]
+    fn no_spread_when_books_overlap() {
+        let balances = json!({ "BUSD": 1000.0, "XTZ": 100.0 });
+        let (first, second) = (mock([0.99, 1.0], balances.clone()), mock([0.995, 1.005], balances));
+        let pair = xtzbusd();
+        let venues = venues(&[&first, &second], &pair);
+        let mut inventory = Inventory::new(venues.len());
+        inventory.refresh(&venues, &["XTZ", "BUSD"]);
+        assert!(find_spread(&venues, &inventory, &pair, 50.0).is_none());
+    }
+
+    #[test]
+    fn unseen_leg_is_cancelled_and_left_unhedged() {
+        let rich = mock([1.02, 1.03], json!({ "XTZ": 100.0 }));
+        // buy rests and its status can't be read.
+        let cheap: Scenario = serde_json::from_value(json!({
+            "symbols": [{ "symbol": "XTZBUSD", "base": "XTZ", "quote": "BUSD", "step_size": "0.01", "tick_size": "0.001" }],
+            "balances": { "BUSD": 1000.0 },
+            "prices": [{ "XTZBUSD": [0.99, 1.0] }],
+            "fills": { "XTZBUSD": { "mode": "rest" } },
+            "faults": [{ "method": "GET", "path": "/api/v3/order", "code": -1000, "times": 100 }]
+        })).unwrap();
+        let cheap = MockExchange::start(cheap).unwrap();
+        let pair = xtzbusd();
+        let venues = venues(&[&rich, &cheap], &pair);
+        let mut inventory = Inventory::new(venues.len());
+        inventory.refresh(&venues, &["XTZ", "BUSD"]);
+
+        let spread = find_spread(&venues, &inventory, &pair, 50.0).unwrap();
+        execute(&venues, &mut inventory, &pair, &spread, Duration::from_millis(400));
+        assert_eq!(cheap.orders()[0].status, "CANCELED");
+        assert_eq!(rich.orders()[0].status, "FILLED");
+        assert!(!inventory.is_hedged("XTZ"));
+    }
+}
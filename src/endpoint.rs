@@ -0,0 +1,90 @@
+use configparser::ini::Ini;
+
+use binance::config::Config;
+
+use crate::error::{Error, Result};
+
+// presets of [endpoint] : (name, REST, WebSocket)
+const PRESETS: [(&str, &str, &str); 2] = [
+    ("mainnet", "https://api.binance.com", "wss://stream.binance.com:9443/ws"),
+    ("testnet", "https://testnet.binance.vision", "wss://testnet.binance.vision/ws")
+];
+
+/// REST & WebSocket base URLs the bot talks to.
+#[derive(Clone, Debug)]
+pub struct Endpoint {
+    pub preset: String,
+    pub rest: String,
+    pub ws: String
+}
+
+impl Endpoint {
+    /// Load `[endpoint]` : preset = mainnet | testnet | custom ( default mainnet ),
+    /// `rest_endpoint` and `ws_endpoint` override the preset, custom needs both.
+    pub fn load(config: &Ini) -> Result<Endpoint> {
+        return Endpoint::from_section(config, "endpoint");
+    }
+
+    /// Same keys as `[endpoint]` from any section, e.g. `[venue.NAME]`.
+    pub fn from_section(config: &Ini, section: &str) -> Result<Endpoint> {
+        let preset = config.get(section, "preset").unwrap_or(String::from("mainnet")).to_lowercase();
+        let (rest, ws) = match PRESETS.iter().find(|(name, _, _)| *name == preset) {
+            Some((_, rest, ws)) => (config.get(section, "rest_endpoint").unwrap_or(String::from(*rest)),
+                config.get(section, "ws_endpoint").unwrap_or(String::from(*ws))),
+            None if preset == "custom" => {
+                let url = |key: &str| config.get(section, key)
+                    .ok_or_else(|| Error::config(section, key, "preset custom needs rest_endpoint and ws_endpoint"));
+                (url("rest_endpoint")?, url("ws_endpoint")?)
+            },
+            None => return Err(Error::config(section, "preset", &format!("unknown preset {}, use mainnet | testnet | custom", preset)))
+        };
+        return Ok(Endpoint { preset, rest: rest.trim_end_matches('/').to_string(), ws: ws.trim_end_matches('/').to_string() });
+    }
+
+    /// binance-rs config pointing at these URLs.
+    pub fn binance_config(&self) -> Config {
+        return Config::default()
+            .set_rest_api_endpoint(self.rest.clone())
+            .set_ws_endpoint(self.ws.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(lines: &str) -> Ini {
+        let mut config = Ini::new();
+        config.read(format!("[endpoint]\n{}", lines)).unwrap();
+        return config;
+    }
+
+    #[test]
+    fn presets_and_overrides_load() {
+        let endpoint = Endpoint::load(&config("preset = testnet")).unwrap();
+        assert_eq!(endpoint.rest, "https://testnet.binance.vision");
+        let endpoint = Endpoint::load(&config("preset = custom\nrest_endpoint = http://127.0.0.1:9000/\nws_endpoint = ws://127.0.0.1:9001/ws")).unwrap();
+        assert_eq!(endpoint.rest, "http://127.0.0.1:9000");
+        assert_eq!(endpoint.ws, "ws://127.0.0.1:9001/ws");
+    }
+
+    #[test]
+    fn unknown_preset_is_a_config_error() {
+        match Endpoint::load(&config("preset = mainet")) {
+            Err(Error::Config { section, key, .. }) => assert_eq!((section.as_str(), key.as_str()), ("endpoint", "preset")),
+            other => panic!("expected a config error, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn custom_preset_needs_both_urls() {
+        match Endpoint::load(&config("preset = custom\nrest_endpoint = http://127.0.0.1:9000")) {
+            Err(Error::Config { key, .. }) => assert_eq!(key, "ws_endpoint"),
+            other => panic!("expected a config error, got {:?}", other)
+        }
+        match Endpoint::from_section(&config("preset = custom\nws_endpoint = ws://127.0.0.1:9001/ws"), "endpoint") {
+            Err(Error::Config { key, .. }) => assert_eq!(key, "rest_endpoint"),
+            other => panic!("expected a config error, got {:?}", other)
+        }
+    }
+}
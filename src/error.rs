@@ -0,0 +1,163 @@
+use std::fmt;
+
+use binance::errors::{Error as BinanceLibError, ErrorKind as BinanceLibErrorKind};
+
+/// Every error of the bot, so callers can tell what went wrong and what to do about it.
+#[derive(Debug)]
+pub enum Error {
+    /// missing or invalid key in config.toml.
+    Config { section: String, key: String, reason: String },
+    /// request never got an answer : timeout, connection refused, bad response body.
+    Network(String),
+    /// answered by the exchange with an error code, etc: -2010 insufficient balance.
+    Exchange { code: i16, msg: String },
+    /// HTTP 429 ( slow down ) or 418 ( IP banned for ignoring 429 ).
+    RateLimited(u16),
+    /// cache file can't be read, parsed or written.
+    Cache { file: String, reason: String },
+    /// symbol not in quantity info, etc: cache built for other rings.
+    MissingSymbol(String),
+    /// number we can't work with : unparsable, NaN, zero step.
+    Arithmetic(String),
+    /// answer that parsed but can't be used, etc: exchangeInfo filter without its fields. Retrying gets the same.
+    Malformed(String),
+    /// order kind the symbol doesn't list, etc: LIMIT_MAKER on a pair without it.
+    Unsupported { symbol: String, kind: String },
+    /// a safety check tripped, etc: balance too low to trade.
+    Halt(String),
+    /// `source` happened while doing `context`.
+    Context { context: String, source: Box<Error> }
+}
+
+/// What the main loop does about an error.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Retry,           // transient, try again after a backoff
+    ResyncTime,      // our clock is off from the exchange's
+    RefreshBalances, // what we think we hold is wrong
+    SkipRing,        // this ring can't go on, others may
+    Shutdown         // stop before more damage
+}
+
+impl Action {
+    /// Parse an action from config : retry, resync_time, refresh_balances, skip_ring, halt.
+    pub fn parse(text: &str) -> Option<Action> {
+        match text.trim().to_lowercase().as_str() {
+            "retry" => Some(Action::Retry),
+            "resync_time" => Some(Action::ResyncTime),
+            "refresh_balances" => Some(Action::RefreshBalances),
+            "skip_ring" | "skip" => Some(Action::SkipRing),
+            "halt" | "shutdown" => Some(Action::Shutdown),
+            _ => None
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn config(section: &str, key: &str, reason: &str) -> Error {
+        return Error::Config { section: section.to_string(), key: key.to_string(), reason: reason.to_string() };
+    }
+
+    pub fn cache(file: &str, reason: impl fmt::Display) -> Error {
+        return Error::Cache { file: file.to_string(), reason: reason.to_string() };
+    }
+
+    /// Too many requests or orders, back off longer than for other retries.
+    pub fn is_rate_limit(&self) -> bool {
+        match self.root() {
+            Error::RateLimited(_) => true,
+            Error::Exchange { code, .. } => *code == -1003 || *code == -1015,
+            _ => false
+        }
+    }
+
+    /// Innermost error, under all contexts.
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::Context { source, .. } => source.root(),
+            other => other
+        }
+    }
+
+    /// Default action, `[error_actions]` can override it per code, see retry.rs.
+    pub fn action(&self) -> Action {
+        match self.root() {
+            Error::Network(_) | Error::RateLimited(429) => Action::Retry,
+            Error::RateLimited(_) => Action::Shutdown, // banned, retrying makes it longer
+            Error::Exchange { code, .. } => match code {
+                // unknown, disconnected, too many requests, unexpected answer, timeout, busy, too many orders, shutting down
+                -1000 | -1001 | -1003 | -1006 | -1007 | -1008 | -1015 | -1016 => Action::Retry,
+                -1021 => Action::ResyncTime,      // timestamp outside recvWindow
+                -2010 => Action::RefreshBalances, // new order rejected, etc: insufficient balance
+                -2013 => Action::Retry,           // order does not exist ( yet )
+                -1002 | -1022 | -2014 | -2015 => Action::Shutdown, // bad key, signature or permissions
+                _ => Action::SkipRing              // filters, parameters, cancel rejected ...
+            },
+            Error::MissingSymbol(_) | Error::Arithmetic(_) | Error::Unsupported { .. } => Action::SkipRing,
+            Error::Config { .. } | Error::Cache { .. } | Error::Malformed(_) | Error::Halt(_) => Action::Shutdown,
+            Error::Context { .. } => Action::Shutdown // root is never a context
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Config { section, key, reason } => write!(f, "config [{}] {}: {}", section, key, reason),
+            Error::Network(msg) => write!(f, "network: {}", msg),
+            Error::Exchange { code, msg } => write!(f, "exchange {}: {}", code, msg),
+            Error::RateLimited(status) => write!(f, "rate limited ( HTTP {} )", status),
+            Error::Cache { file, reason } => write!(f, "cache {}: {}", file, reason),
+            Error::MissingSymbol(symbol) => write!(f, "no quantity info for {}", symbol),
+            Error::Arithmetic(msg) => write!(f, "arithmetic: {}", msg),
+            Error::Malformed(msg) => write!(f, "malformed: {}", msg),
+            Error::Unsupported { symbol, kind } => write!(f, "{} doesn't support {} orders", symbol, kind),
+            Error::Halt(msg) => write!(f, "halt: {}", msg),
+            Error::Context { context, source } => write!(f, "{}: {}", context, source)
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<BinanceLibErrorKind> for Error {
+    fn from(e: BinanceLibErrorKind) -> Error {
+        match e {
+            BinanceLibErrorKind::BinanceError(response) => Error::Exchange { code: response.code, msg: response.msg },
+            // binance-rs only keeps the status of non 400 answers : "Received response: 429"
+            BinanceLibErrorKind::Msg(msg) if msg.contains("429") => Error::RateLimited(429),
+            BinanceLibErrorKind::Msg(msg) if msg.contains("418") => Error::RateLimited(418),
+            other => Error::Network(format!("{:?}", other))
+        }
+    }
+}
+
+impl From<BinanceLibError> for Error {
+    fn from(e: BinanceLibError) -> Error {
+        return Error::from(e.0);
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Error {
+        return Error::Network(e.to_string());
+    }
+}
+
+/// Wrap an error with what we were doing, etc: `.context("leg b XTZBNB")`.
+pub trait Context<T> {
+    fn context(self, context: impl fmt::Display) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> Context<T> for std::result::Result<T, E> {
+    fn context(self, context: impl fmt::Display) -> Result<T> {
+        return self.map_err(|e| Error::Context { context: context.to_string(), source: Box::new(e.into()) });
+    }
+}
+
+/// Parse a number sent by the exchange, etc: "0.00100000".
+pub fn parse_f64(text: &str, what: &str) -> Result<f64> {
+    return text.trim().parse::<f64>().map_err(|_| Error::Arithmetic(format!("{} is not a number: {:?}", what, text)));
+}
@@ -4,6 +4,8 @@ use std::{
 
 use serde::{Deserialize, Serialize};
 
+use crate::error::{parse_f64, Context, Error, Result};
+
 #[allow(non_snake_case)]
 #[derive(Serialize, Deserialize)]
 struct ExchangeInfo {
@@ -79,9 +81,17 @@ pub struct QuantityInfo {
     pub step_qty: f64,          // step size
     pub step_price: f64,        // step price
     pub move_qty: f64,          // we pre-calculate for step_qty correction 
-    pub move_price: f64         // we pre-calculate for step_price correction 
+    pub move_price: f64,        // we pre-calculate for step_price correction 
+    #[serde(default)]
+    pub order_types: Vec<String>,            // LIMIT, LIMIT_MAKER, MARKET, ... ( empty in old cache )
+    #[serde(default)]
+    pub quote_order_qty_market_allowed: bool, // MARKET by quoteOrderQty
+    #[serde(default = "default_quote_precision")]
+    pub quote_precision: i32                  // decimals of quoteOrderQty ( 8 in old cache )
 }
 
+fn default_quote_precision() -> i32 { return 8; }
+
 impl Default for QuantityInfo { 
     fn default() -> QuantityInfo {
         QuantityInfo { 
@@ -91,101 +101,190 @@ impl Default for QuantityInfo {
             step_qty: 0.0,
             step_price: 0.0,
             move_qty: 0.0,
-            move_price: 0.0
+            move_price: 0.0,
+            order_types: vec![],
+            quote_order_qty_market_allowed: false,
+            quote_precision: default_quote_precision()
         }
     }
 }
 
+/// Trading state of a symbol, always fetched fresh ( never cached ).
+#[derive(Clone)]
+pub struct SymbolInfo {
+    pub symbol: String,
+    pub status: String,           // TRADING, BREAK, ...
+    pub base_asset: String,
+    pub quote_asset: String,
+    pub is_spot_trading_allowed: bool,
+    pub order_types: Vec<String>,
+    pub quote_order_qty_market_allowed: bool
+}
+
+impl QuantityInfo {
+    /// truncate qty to LOT_SIZE step.
+    pub fn round_qty(&self, qty: f64) -> f64 {
+        return truncate(qty, self.move_qty);
+    }
+
+    /// truncate price to PRICE_FILTER tick.
+    pub fn round_price(&self, price: f64) -> f64 {
+        return truncate(price, self.move_price);
+    }
+
+    /// truncate a quote amount ( quoteOrderQty ) to quote asset precision.
+    pub fn round_quote(&self, quote: f64) -> f64 {
+        return truncate(quote, 10f64.powi(self.quote_precision));
+    }
+}
+
+/// `value` truncated to 1/`moved` steps, a step that binary floats miss by an ulp is kept :
+/// 0.29 * 100 is 28.999999999999996, still 0.29.
+fn truncate(value: f64, moved: f64) -> f64 {
+    return f64::trunc(value * moved * (1.0 + 1e-12)) / moved;
+}
+
 const QUANTITY_INFO_FILE:&str = "quantity.cache";
 
-/// fetch and build exchange info map
-pub fn fetch(symbols_cache: &Vec<String>) -> Option<HashMap<String, QuantityInfo>>{
-    //
-    // 1. Fetch + Map Data into Structs
-    let mut quantity_info: HashMap<String, QuantityInfo> = HashMap::new();
+/// exchangeInfo of any binance-compatible REST endpoint ( mainnet, testnet, local mock ).
+fn fetch_exchange_info(rest_endpoint: &str) -> Result<ExchangeInfo> {
+    let url = format!("{}/api/v3/exchangeInfo", rest_endpoint.trim_end_matches('/'));
+    let content = reqwest::blocking::get(&url).and_then(|res| res.text()).context("exchangeInfo")?;
+    return parse_exchange_info(&content);
+}
+
+fn parse_exchange_info(content: &str) -> Result<ExchangeInfo> {
+    return serde_json::from_str::<ExchangeInfo>(content)
+        .map_err(|e| Error::Network(format!("can't parse exchangeInfo: {}", e)));
+}
+
+/// trading state of all symbols from an exchangeInfo answer fetched elsewhere ( or recorded ).
+pub fn symbols_from(content: &str) -> Result<HashMap<String, SymbolInfo>> {
+    return Ok(symbols_of(parse_exchange_info(content)?));
+}
+
+/// quantity info of `symbols` from an exchangeInfo answer fetched elsewhere ( or recorded ).
+pub fn quantity_from(content: &str, symbols: &[String]) -> Result<HashMap<String, QuantityInfo>> {
+    return build_quantity_info(&parse_exchange_info(content)?, symbols);
+}
+
+/// fetch quantity info of `symbols` from any binance-compatible REST venue, not cached.
+pub fn fetch_quantity_from(rest_endpoint: &str, symbols: &[String]) -> Result<HashMap<String, QuantityInfo>> {
+    let exchange_info = fetch_exchange_info(rest_endpoint)?;
+    return build_quantity_info(&exchange_info, symbols);
+}
+
+/// fetch trading state of all symbols.
+pub fn fetch_symbols(rest_endpoint: &str) -> Result<HashMap<String, SymbolInfo>> {
+    return Ok(symbols_of(fetch_exchange_info(rest_endpoint)?));
+}
+
+fn symbols_of(exchange_info: ExchangeInfo) -> HashMap<String, SymbolInfo> {
+    let mut symbols: HashMap<String, SymbolInfo> = HashMap::new();
+    for symbol in exchange_info.symbols {
+        symbols.insert(symbol.symbol.clone(), SymbolInfo {
+            symbol: symbol.symbol,
+            status: symbol.status,
+            base_asset: symbol.baseAsset,
+            quote_asset: symbol.quoteAsset,
+            is_spot_trading_allowed: symbol.isSpotTradingAllowed,
+            order_types: symbol.orderTypes,
+            quote_order_qty_market_allowed: symbol.quoteOrderQtyMarketAllowed
+        });
+    }
+    return symbols;
+}
 
+/// fetch and build exchange info map, cache is only used if it came from the same `rest_endpoint`.
+pub fn fetch(rest_endpoint: &str, symbols_cache: &Vec<String>) -> Result<HashMap<String, QuantityInfo>> {
     let mut cache_file = configparser::ini::Ini::new();
-    match cache_file.load(QUANTITY_INFO_FILE) {
-        Ok(_) => { 
-            for sym_map in cache_file.get_map() {
-                for _quantity_info in sym_map {
-                    for stuff in _quantity_info.1 {
-                        quantity_info = serde_json::from_str(stuff.1.unwrap().as_str()).unwrap();
-                        // println!("{:?}", stuff.1);
-                    }
-                }
-            }
+    let is_cached = cache_file.load(QUANTITY_INFO_FILE).is_ok()
+        && cache_file.get("endpoint", "rest").is_none_or(|cached| cached == rest_endpoint);
+    if is_cached {
+        let json = cache_file.get("quantity_info", "json").unwrap_or_default();
+        let quantity_info: HashMap<String, QuantityInfo> = serde_json::from_str(json.as_str())
+            .map_err(|e| Error::cache(QUANTITY_INFO_FILE, format!("{}, delete it to rebuild", e)))?;
+        // a symbol added since ( new ring, new strategy pair ) rebuilds it.
+        if symbols_cache.iter().all(|symbol| quantity_info.contains_key(symbol)) {
             println!("> loaded quantity info.");
-            return Some(quantity_info);
-        },
-        _error => {
-            let req = reqwest::blocking::get("https://www.binance.com/api/v3/exchangeInfo");
-            match req {
-                Ok(res) => {
-                    let content = res.text();
-                    // println!("{:?}", content);
-                    let exchange_info:ExchangeInfo = serde_json::from_str(&content.unwrap()).unwrap();
-                    // start building map
-                    for symbol in &exchange_info.symbols {
-                        if symbols_cache.contains(&symbol.symbol) {
-                            //
-                            // init data
-                            let _symbol = String::from(&symbol.symbol);
-                            let mut _min_qty = String::new();
-                            let mut _max_qty =String::new();
-                            let mut _step_qty: f64 = 0.0;
-                            let mut _step_price : f64 = 0.0;
-                            let mut _move_qty : f64 = 0.0;
-                            let mut _move_price : f64 = 0.0;
-                            //
-                            // collect quantity info for 1 symbol :
-                            for filter in &symbol.filters {
-                                match &filter.filterType.as_str() { 
-                                    &"LOT_SIZE" => {
-                                        let _step_size = filter.stepSize.as_ref().unwrap();
-                                        let _step_size_decimal = move_decimal(_step_size);
-                                        _min_qty = filter.minQty.as_ref().unwrap().clone(); 
-                                        _max_qty = filter.maxQty.as_ref().unwrap().clone(); 
-                                        _step_qty = _step_size.parse::<f64>().unwrap();
-                                        _move_qty = _step_size_decimal;
-                                    },
-                                    &"PRICE_FILTER" => {
-                                        let tick_size = filter.tickSize.as_ref().unwrap();
-                                        _step_price = tick_size.parse::<f64>().unwrap();
-                                        _move_price = move_decimal(tick_size);
-                                    }
-                                    _ => {}
-                                }
-                                let new_quantity_info = QuantityInfo {
-                                    symbol : _symbol.clone(),
-                                    min_qty : _min_qty.clone(), 
-                                    max_qty : _max_qty.clone(), 
-                                    step_qty : _step_qty,
-                                    step_price : _step_price,
-                                    move_qty : _move_qty,
-                                    move_price: _move_price
-                                };
-                                quantity_info.insert(_symbol.clone(), new_quantity_info);
-                            }
-                        }
-                    }
-                    // Save all 
-                    cache_file.set("quantity_info", "json" , Some(serde_json::to_string(&quantity_info).unwrap()));
-                    match cache_file.write(QUANTITY_INFO_FILE) {
-                        Ok(_) => println!("> saved quantity info to cache."),
-                        msg => println!("Error saving cache: {:?}", msg)
+            return Ok(quantity_info);
+        }
+        println!("> quantity info misses symbols, rebuilding...");
+    }
+    cache_file.clear();
+    let exchange_info = fetch_exchange_info(rest_endpoint)?;
+    let quantity_info = build_quantity_info(&exchange_info, symbols_cache)?;
+    // Save all 
+    let json = serde_json::to_string(&quantity_info).map_err(|e| Error::cache(QUANTITY_INFO_FILE, e))?;
+    cache_file.set("endpoint", "rest", Some(rest_endpoint.to_string()));
+    cache_file.set("quantity_info", "json" , Some(json));
+    match cache_file.write(QUANTITY_INFO_FILE) {
+        Ok(_) => println!("> saved quantity info to cache."),
+        msg => println!("Error saving cache: {:?}", msg)
+    }
+    return Ok(quantity_info);
+}
+
+/// build quantity info map of `symbols_cache` from LOT_SIZE & PRICE_FILTER.
+fn build_quantity_info(exchange_info: &ExchangeInfo, symbols_cache: &[String]) -> Result<HashMap<String, QuantityInfo>> {
+    let mut quantity_info: HashMap<String, QuantityInfo> = HashMap::new();
+    // start building map
+    for symbol in &exchange_info.symbols {
+        if symbols_cache.contains(&symbol.symbol) {
+            //
+            // init data
+            let _symbol = String::from(&symbol.symbol);
+            let mut _min_qty = String::new();
+            let mut _max_qty =String::new();
+            let mut _step_qty: f64 = 0.0;
+            let mut _step_price : f64 = 0.0;
+            let mut _move_qty : f64 = 0.0;
+            let mut _move_price : f64 = 0.0;
+            //
+            // collect quantity info for 1 symbol :
+            for filter in &symbol.filters {
+                match &filter.filterType.as_str() { 
+                    &"LOT_SIZE" => {
+                        let missing = |field: &str| Error::Malformed(format!("{} LOT_SIZE has no {}", _symbol, field));
+                        let _step_size = filter.stepSize.as_ref().ok_or_else(|| missing("stepSize"))?;
+                        let _step_size_decimal = move_decimal(_step_size)?;
+                        _min_qty = filter.minQty.as_ref().ok_or_else(|| missing("minQty"))?.clone(); 
+                        _max_qty = filter.maxQty.as_ref().ok_or_else(|| missing("maxQty"))?.clone(); 
+                        _step_qty = parse_f64(_step_size, "stepSize")?;
+                        _move_qty = _step_size_decimal;
+                    },
+                    &"PRICE_FILTER" => {
+                        let tick_size = filter.tickSize.as_ref()
+                            .ok_or_else(|| Error::Malformed(format!("{} PRICE_FILTER has no tickSize", _symbol)))?;
+                        _step_price = parse_f64(tick_size, "tickSize")?;
+                        _move_price = move_decimal(tick_size)?;
                     }
-                    return Some(quantity_info);
+                    _ => {}
                 }
-                _ => return None
+                let new_quantity_info = QuantityInfo {
+                    symbol : _symbol.clone(),
+                    min_qty : _min_qty.clone(), 
+                    max_qty : _max_qty.clone(), 
+                    step_qty : _step_qty,
+                    step_price : _step_price,
+                    move_qty : _move_qty,
+                    move_price: _move_price,
+                    order_types: symbol.orderTypes.clone(),
+                    quote_order_qty_market_allowed: symbol.quoteOrderQtyMarketAllowed,
+                    quote_precision: symbol.quoteAssetPrecision
+                };
+                quantity_info.insert(_symbol.clone(), new_quantity_info);
             }
         }
     }
+    return Ok(quantity_info);
 }
 
-fn move_decimal (step: &str) -> f64 {
+/// "0.00100000" -> 1000.0, what a qty/price is multiplied by before truncating to its step.
+fn move_decimal (step: &str) -> Result<f64> {
     let arr:Vec<&str> = step.split(".").collect();
-    let decimal_arr:Vec<&str> = arr[1].split("1").collect();
+    let decimals = arr.get(1).ok_or_else(|| Error::Arithmetic(format!("step {} has no decimal point", step)))?;
+    let decimal_arr:Vec<&str> = decimals.split("1").collect();
     let decimal_point = match decimal_arr.len() {
         2 => { // less than 1.00..
             (decimal_arr[0].len() + 1) as f64 
@@ -193,10 +292,83 @@ fn move_decimal (step: &str) -> f64 {
         1 => { // is 1.000
             0.0 // no decimal point. 
         },
-        _ => {println!("ERROR ?"); 0.0}
+        _ => return Err(Error::Arithmetic(format!("step {} is not a power of 10", step)))
     };
     let one_decimal:f64 = 10.0;
     let move_qty = one_decimal.powf(decimal_point);
     // result
-    return move_qty
-}
\ No newline at end of file
+    return Ok(move_qty);
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Action;
+
+    const EXCHANGE_INFO:&str = include_str!("../tests/fixtures/exchange_info.json");
+
+    fn quantity_info(symbols: &[&str]) -> HashMap<String, QuantityInfo> {
+        let symbols: Vec<String> = symbols.iter().map(|symbol| symbol.to_string()).collect();
+        return quantity_from(EXCHANGE_INFO, &symbols).unwrap();
+    }
+
+    #[test]
+    fn move_decimal_of_steps() {
+        assert_eq!(move_decimal("0.00100000").unwrap(), 1000.0);
+        assert_eq!(move_decimal("0.10000000").unwrap(), 10.0);
+        assert_eq!(move_decimal("0.00000001").unwrap(), 100_000_000.0);
+        assert_eq!(move_decimal("1.00000000").unwrap(), 1.0);
+    }
+
+    #[test]
+    fn move_decimal_rejects_bad_steps() {
+        assert!(move_decimal("1").is_err());
+        assert!(move_decimal("0.00110000").is_err());
+    }
+
+    #[test]
+    fn quantity_info_from_recorded_exchange_info() {
+        let quantity_info = quantity_info(&["XTZBNB", "BTCBUSD"]);
+        assert_eq!(quantity_info.len(), 2);
+        let xtz = &quantity_info["XTZBNB"];
+        assert_eq!(xtz.min_qty, "0.01000000");
+        assert_eq!(xtz.step_qty, 0.01);
+        assert_eq!(xtz.move_qty, 100.0);
+        assert_eq!(xtz.step_price, 0.000001);
+        assert_eq!(xtz.move_price, 1_000_000.0);
+        assert!(xtz.order_types.contains(&String::from("LIMIT_MAKER")));
+        assert_eq!(quantity_info["BTCBUSD"].move_qty, 100_000.0);
+    }
+
+    #[test]
+    fn missing_lot_size_field_is_an_error() {
+        let mut exchange_info: serde_json::Value = serde_json::from_str(EXCHANGE_INFO).unwrap();
+        exchange_info["symbols"][1]["filters"][2]["stepSize"] = serde_json::Value::Null;
+        let exchange_info: ExchangeInfo = serde_json::from_value(exchange_info).unwrap();
+        let result = build_quantity_info(&exchange_info, &[String::from("XTZBUSD")]);
+        assert!(matches!(result, Err(Error::Malformed(_))));
+        // same answer next time, not retried.
+        assert_eq!(result.err().map(|e| e.action()), Some(Action::Shutdown));
+    }
+
+    #[test]
+    fn rounding_truncates_to_steps() {
+        let quantity_info = quantity_info(&["XTZBUSD", "BTCBUSD"]);
+        let xtz = &quantity_info["XTZBUSD"];
+        assert_eq!(xtz.round_qty(12.3456), 12.34);
+        assert_eq!(xtz.round_qty(0.009), 0.0);
+        assert_eq!(xtz.round_price(1.0049), 1.004);
+        // quoteOrderQty goes by quote asset precision, not the tick.
+        assert_eq!(xtz.round_quote(12.123456789), 12.12345678);
+        assert_eq!(quantity_info["BTCBUSD"].round_qty(0.123456789), 0.12345);
+    }
+
+    #[test]
+    fn rounding_keeps_exact_steps() {
+        let quantity_info = quantity_info(&["XTZBUSD"]);
+        let xtz = &quantity_info["XTZBUSD"];
+        // binary floats land an ulp under these.
+        assert_eq!(xtz.round_qty(0.29), 0.29);
+        assert_eq!(xtz.round_qty(0.57), 0.57);
+        assert_eq!(xtz.round_price(1.005), 1.005);
+    }
+}
@@ -0,0 +1,191 @@
+use std::{
+    fs::File,
+    io::Write,
+    collections::HashMap};
+
+use colored::*;
+use configparser::ini::Ini;
+
+use binance::market::Market;
+use binance::model::PriceStats;
+
+use crate::analyzer::RingLeg;
+use crate::endpoint::Endpoint;
+use crate::exchangeinfo::{self, SymbolInfo};
+
+const FILTER_REPORT_FILE:&str = "filter.report";
+
+// default thresholds of [filter]
+const MIN_QUOTE_VOLUME:f64 = 10_000.0; // 24h volume in stablecoin
+const MIN_TRADES:u64 = 500;            // 24h trade count
+const MAX_SPREAD:f64 = 0.5;            // percent of mid price
+
+/// Health thresholds, loaded from `[filter]`.
+pub struct FilterConfig {
+    pub enabled: bool,
+    pub min_quote_volume: f64,
+    pub min_trades: u64,
+    pub max_spread: f64
+}
+
+impl FilterConfig {
+    pub fn load(config: &Ini) -> FilterConfig {
+        return FilterConfig {
+            enabled: config.getbool("filter", "enabled").unwrap_or(None).unwrap_or(true),
+            min_quote_volume: config.getfloat("filter", "min_quote_volume").unwrap_or(None).unwrap_or(MIN_QUOTE_VOLUME),
+            min_trades: config.getuint("filter", "min_trades").unwrap_or(None).unwrap_or(MIN_TRADES),
+            max_spread: config.getfloat("filter", "max_spread").unwrap_or(None).unwrap_or(MAX_SPREAD)
+        };
+    }
+}
+
+/// Why a pair is unhealthy, None when it's fine.
+/// `quote_rate` converts the pair's quote asset into stablecoin, or says why it can't.
+fn check_pair(filter: &FilterConfig, info: Option<&SymbolInfo>, stats: Option<&PriceStats>, quote_rate: Result<f64, String>) -> Option<String> {
+    match info {
+        None => return Some(String::from("not listed")),
+        Some(info) if info.status != "TRADING" => return Some(format!("status {}", info.status)),
+        Some(info) if !info.is_spot_trading_allowed => return Some(String::from("spot trading disabled")),
+        _ => {}
+    }
+    let stats = match stats {
+        Some(stats) => stats,
+        None => return Some(String::from("no 24h stats"))
+    };
+    let quote_rate = match quote_rate {
+        Ok(quote_rate) => quote_rate,
+        Err(reason) => return Some(reason)
+    };
+    let weighted_price = stats.weighted_avg_price.parse::<f64>().unwrap_or(stats.last_price);
+    let quote_volume = stats.volume * weighted_price * quote_rate;
+    if quote_volume < filter.min_quote_volume {
+        return Some(format!("24h quote volume {:.0} < {:.0}", quote_volume, filter.min_quote_volume));
+    }
+    if stats.count < filter.min_trades {
+        return Some(format!("24h trades {} < {}", stats.count, filter.min_trades));
+    }
+    let mid = (stats.bid_price + stats.ask_price) / 2.0;
+    let spread = if mid > 0.0 { (stats.ask_price - stats.bid_price) / mid * 100.0 } else { 100.0 };
+    if spread > filter.max_spread {
+        return Some(format!("spread {:.3}% > {:.3}%", spread, filter.max_spread));
+    }
+    return None;
+}
+
+/// Drop rings with any unhealthy pair : not TRADING, spot disabled,
+/// low 24h quote volume, low trade count or too-wide spread.
+/// What's removed and why is written to `filter.report`.
+pub fn filter_rings(config: &Ini, market: &Market, rings: HashMap<String, Vec<RingLeg>>) -> HashMap<String, Vec<RingLeg>> {
+    let filter = FilterConfig::load(config);
+    if !filter.enabled { return rings; }
+    println!("> filtering {} rings...", rings.len());
+    let symbols = match Endpoint::load(config).and_then(|endpoint| exchangeinfo::fetch_symbols(&endpoint.rest)) {
+        Ok(symbols) => symbols,
+        Err(e) => { println!("> filter: no exchange info, skip filtering: {}", e); return rings; }
+    };
+    let stats: HashMap<String, PriceStats> = match market.get_all_24h_price_stats() {
+        Ok(all) => all.into_iter().map(|stats| (stats.symbol.clone(), stats)).collect(),
+        Err(e) => { println!("> filter: no 24h stats, skip filtering: {:?}", e.0); return rings; }
+    };
+
+    let (healthy, report) = split_rings(&filter, &symbols, &stats, rings);
+    println!("> filter: kept {} rings, removed {}.", healthy.len().to_string().green(), report.len().to_string().red());
+    match File::create(FILTER_REPORT_FILE) {
+        Ok(mut file) => {
+            let _ = writeln!(file, "# min_quote_volume = {} | min_trades = {} | max_spread = {}%",
+                filter.min_quote_volume, filter.min_trades, filter.max_spread);
+            for line in &report { let _ = writeln!(file, "{}", line); }
+            println!("> filter: report saved to {}.", FILTER_REPORT_FILE);
+        },
+        Err(e) => println!("Error saving filter report: {:?}", e)
+    }
+    return healthy;
+}
+
+/// Healthy rings, and a sorted report line for each removed one.
+fn split_rings(filter: &FilterConfig, symbols: &HashMap<String, SymbolInfo>, stats: &HashMap<String, PriceStats>,
+    rings: HashMap<String, Vec<RingLeg>>) -> (HashMap<String, Vec<RingLeg>>, Vec<String>) {
+    let mut report: Vec<String> = vec![];
+    let mut healthy: HashMap<String, Vec<RingLeg>> = HashMap::new();
+    let mut checked: HashMap<String, Option<String>> = HashMap::new();
+    for (symbol, ring) in rings {
+        // ring = [ symbol-stable, symbol-bridge, bridge-stable ], bridge quoted pair is converted by leg 3.
+        let bridge_stable = &ring[2];
+        let bridge_rate = stats.get(&bridge_stable.symbol).map(|stats| stats.last_price).filter(|rate| *rate > 0.0);
+        let mut reasons: Vec<String> = vec![];
+        for leg in &ring {
+            let quote_rate = match leg.quote == bridge_stable.base {
+                true => bridge_rate.ok_or_else(|| format!("no bridge rate for {}", bridge_stable.symbol)),
+                false => Ok(1.0)
+            };
+            let pair = &leg.symbol;
+            let reason = checked.entry(pair.clone())
+                .or_insert_with(|| check_pair(filter, symbols.get(pair), stats.get(pair), quote_rate));
+            if let Some(reason) = reason { reasons.push(format!("{}: {}", pair, reason)); }
+        }
+        if reasons.is_empty() {
+            healthy.insert(symbol, ring);
+        } else {
+            report.push(format!("{}\t{}", symbol, reasons.join(" | ")));
+        }
+    }
+    report.sort();
+    return (healthy, report);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::fixture::{xtz_legs, EXCHANGE_INFO};
+
+    fn filter() -> FilterConfig {
+        return FilterConfig { enabled: true, min_quote_volume: 10_000.0, min_trades: 500, max_spread: 0.5 };
+    }
+
+    /// Healthy 24h stats of a pair around `price` : 100k quote volume, 1000 trades, 0.1% spread.
+    fn stats(symbol: &str, price: f64) -> PriceStats {
+        return PriceStats { symbol: symbol.to_string(), price_change: String::from("0"), price_change_percent: String::from("0"),
+            weighted_avg_price: price.to_string(), prev_close_price: price, last_price: price,
+            bid_price: price * 0.9995, ask_price: price * 1.0005, open_price: price, high_price: price, low_price: price,
+            volume: 100_000.0 / price, open_time: 0, close_time: 0, first_id: 0, last_id: 0, count: 1000 };
+    }
+
+    /// Edit of the recorded exchangeInfo & healthy stats.
+    type Change = dyn Fn(&mut HashMap<String, SymbolInfo>, &mut HashMap<String, PriceStats>);
+
+    /// Report of the XTZ ring, `change` applied to the recorded exchangeInfo & healthy stats first.
+    fn report(change: &Change) -> Vec<String> {
+        let mut symbols = exchangeinfo::symbols_from(EXCHANGE_INFO).unwrap();
+        let rings = xtz_legs(&symbols);
+        let mut stats: HashMap<String, PriceStats> = [("XTZBUSD", 1.0), ("XTZBNB", 0.0034), ("BNBBUSD", 300.0)].iter()
+            .map(|(symbol, price)| (symbol.to_string(), stats(symbol, *price))).collect();
+        change(&mut symbols, &mut stats);
+        let (healthy, report) = split_rings(&filter(), &symbols, &stats, rings);
+        assert_eq!(healthy.len() + report.len(), 1);
+        return report;
+    }
+
+    #[test]
+    fn healthy_ring_is_kept() {
+        assert!(report(&|_, _| {}).is_empty());
+    }
+
+    #[test]
+    fn every_reason_is_reported() {
+        let reason = |change: &Change| report(change).join("");
+        assert_eq!(reason(&|symbols, _| { symbols.remove("XTZBNB"); }), "XTZ\tXTZBNB: not listed");
+        assert_eq!(reason(&|symbols, _| { symbols.get_mut("XTZBNB").unwrap().status = String::from("BREAK"); }), "XTZ\tXTZBNB: status BREAK");
+        assert_eq!(reason(&|symbols, _| { symbols.get_mut("XTZBNB").unwrap().is_spot_trading_allowed = false; }), "XTZ\tXTZBNB: spot trading disabled");
+        assert_eq!(reason(&|_, stats| { stats.remove("XTZBUSD"); }), "XTZ\tXTZBUSD: no 24h stats");
+        assert_eq!(reason(&|_, stats| { stats.get_mut("XTZBUSD").unwrap().volume = 100.0; }), "XTZ\tXTZBUSD: 24h quote volume 100 < 10000");
+        assert_eq!(reason(&|_, stats| { stats.get_mut("XTZBUSD").unwrap().count = 20; }), "XTZ\tXTZBUSD: 24h trades 20 < 500");
+        assert_eq!(reason(&|_, stats| { let xtz = stats.get_mut("XTZBUSD").unwrap(); xtz.bid_price = 0.995; xtz.ask_price = 1.005; }), "XTZ\tXTZBUSD: spread 1.000% > 0.500%");
+    }
+
+    #[test]
+    fn missing_bridge_rate_is_its_own_reason() {
+        assert_eq!(report(&|_, stats| { stats.get_mut("BNBBUSD").unwrap().last_price = 0.0; }), ["XTZ\tXTZBNB: no bridge rate for BNBBUSD"]);
+        // bridge quoted volume is valued at the bridge rate : 100k BNB at 0.01
+        assert_eq!(report(&|_, stats| { stats.get_mut("BNBBUSD").unwrap().last_price = 0.01; }), ["XTZ\tXTZBNB: 24h quote volume 1000 < 10000"]);
+    }
+}
@@ -0,0 +1,27 @@
+//! Railgun : triangular arbitrage on binance-compatible spot venues.
+//!
+//! The bot binary is a thin layer over this crate, other tools can embed the ring scanner :
+//! - `discover_rings` builds rings from exchangeInfo ( `exchangeinfo::symbols_from` or `fetch_symbols` ).
+//! - `Scanner` is fed books every block, finds profitable rings and plans their orders,
+//!   without config files, caches nor binance-rs clients.
+//! - `analyzer::init_threads` is the bot itself : every enabled `Strategy` gets the same books each block,
+//!   what they ask for is executed, reconciled & journaled there, driven by `config.toml`.
+
+pub mod credentials;
+pub mod endpoint;
+pub mod error;
+pub mod exchangeinfo;
+pub mod rest;
+pub mod retry;
+pub mod analyzer;
+pub mod filter;
+pub mod quarantine;
+pub mod userdata;
+pub mod venue;
+pub mod cross;
+pub mod mock;
+
+pub use analyzer::{discover_rings, ExecutionPlan, LegPrice, PlannedOrder, PricingPolicy, Quote, Ring, RingLeg, RingResult, Scanner, Side, Snapshot};
+pub use analyzer::{AccountView, Expected, Intent, MarketView, OrderIntent, Report, RingIntent, Strategy};
+pub use error::{Error, Result};
+pub use exchangeinfo::{QuantityInfo, SymbolInfo};
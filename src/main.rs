@@ -1,15 +1,12 @@
-extern crate binance;
-
 use configparser::ini::Ini;
 
-pub mod exchangeinfo;
-pub mod analyzer;
+use railgun::{analyzer, credentials, cross, endpoint, error, exchangeinfo, filter, mock, quarantine};
 
 // NOTE:
 //
 // 1. Analyze
 // [done] Discover all trade-able symbols and serialize to cache on 1st run.
-// [*] Could make a filtering phrase by testing run to filter old/unused pairs.
+// [done] Filter unhealthy pairs by status, 24h volume, trades & spread ( see filter.report ).
 //
 // 2. Update Orderbooks
 // [wip] Update price by bookTickers to cache + Calculate profit through each ring.
@@ -31,25 +28,76 @@ fn main() {
     //
     let mut config = Ini::new();
     let _ = config.load("config.toml");
-    let market = analyzer::get_market(&mut config);
+    let mut quarantine = quarantine::Quarantine::load(&config);
+    //
+    // COMMANDS
+    // - quarantine               : list quarantined rings.
+    // - quarantine release XTZ   : release a ring before cooldown.
+    // - cross                    : cross-venue arbitrage, see [cross].
+    // - keys check               : show where keys come from & what they can do.
+    // - keys seal keystore.json  : encrypt current keys into a keystore.
+    // - mock [scenario.json]     : local binance REST server, see mock.rs.
+    //
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() > 1 {
+        match args[1].as_str() {
+            "quarantine" => {
+                if args.len() > 3 && args[2] == "release" {
+                    if !quarantine.release(&args[3]) { println!("> {} is not quarantined.", args[3]); }
+                }
+                quarantine.list();
+            },
+            "cross" => if let Err(e) = cross::run(&config) {
+                println!("> cross: {}", e);
+                std::process::exit(1);
+            },
+            "keys" if args.len() > 2 && args[2] == "check" => credentials::check(&config),
+            "keys" if args.len() > 3 && args[2] == "seal" => {
+                match credentials::load(&config).and_then(|keys| credentials::seal(keys, &args[3])) {
+                    Ok(_) => println!("> keys: sealed into {}, set [keys] keystore = {} and remove plaintext keys.", args[3], args[3]),
+                    Err(e) => println!("> keys: {}", e)
+                }
+            },
+            "mock" => mock::run(args.get(2).map_or("mock.json", |path| path.as_str())),
+            command => println!("> unknown command: {}", command)
+        }
+        return;
+    }
+    if let Err(e) = credentials::load(&config) {
+        println!("> keys: {}", e);
+        return;
+    }
+    if let Err(e) = run(&mut config, quarantine) {
+        println!("> shutdown: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(config: &mut Ini, quarantine: quarantine::Quarantine) -> error::Result<()> {
+    let endpoint = endpoint::Endpoint::load(config)?;
+    let market = analyzer::get_market(config)?;
     //
     // BUILD RINGS
     //
-    let rings = analyzer::symbol_discovery(&config, &market);
-    let symbols_cache = make_symcache(&rings);
-    let quantity_info = exchangeinfo::fetch(&symbols_cache).unwrap();
+    let rings = analyzer::symbol_discovery(config)?;
+    let rings = filter::filter_rings(config, &market, rings);
+    let mut symbols_cache = make_symcache(&rings);
+    for symbol in analyzer::strategy_symbols(config) {
+        if !symbols_cache.contains(&symbol) { symbols_cache.push(symbol); }
+    }
+    let quantity_info = exchangeinfo::fetch(&endpoint.rest, &symbols_cache)?;
     // return;
     //
     // UPDATE PRICES
     //
-    analyzer::init_threads(&config, &market, &symbols_cache, rings, &quantity_info);
+    return analyzer::init_threads(config, &market, quarantine, rings, &quantity_info);
 }
 
-fn make_symcache(rings: &std::collections::HashMap<String, Vec<String>>) -> Vec<String> {
+fn make_symcache(rings: &std::collections::HashMap<String, Vec<analyzer::RingLeg>>) -> Vec<String> {
     let mut symbols_cache: Vec<String> = vec![];
     for ring in rings {
-        for symbol in ring.1 {
-            symbols_cache.push(symbol.clone());
+        for leg in ring.1 {
+            symbols_cache.push(leg.symbol.clone());
         }
     }
     return symbols_cache;
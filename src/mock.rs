@@ -0,0 +1,594 @@
+use std::{
+    fs, thread,
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    time::Duration};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::quarantine;
+
+const DEFAULT_PORT:u16 = 8080;
+const DEFAULT_FEE_RATE:f64 = 0.001; // taken from what a fill receives, as binance without BNB
+
+/// One pair listed by the mock, with its LOT_SIZE & PRICE_FILTER.
+#[derive(Clone, Deserialize)]
+pub struct MockSymbol {
+    pub symbol: String,
+    pub base: String,
+    pub quote: String,
+    #[serde(default = "default_step")]
+    pub step_size: String,
+    #[serde(default = "default_step")]
+    pub tick_size: String,
+    #[serde(default = "default_step")]
+    pub min_qty: String,
+    #[serde(default = "default_max_qty")]
+    pub max_qty: String,
+    #[serde(default = "default_status")]
+    pub status: String,
+    #[serde(default = "default_order_types")]
+    pub order_types: Vec<String>
+}
+
+fn default_step() -> String { return String::from("0.00100000"); }
+fn default_max_qty() -> String { return String::from("900000.00000000"); }
+fn default_status() -> String { return String::from("TRADING"); }
+fn default_order_types() -> Vec<String> {
+    return ["LIMIT", "LIMIT_MAKER", "MARKET"].iter().map(|kind| kind.to_string()).collect();
+}
+
+/// How orders of a pair get filled.
+/// cross   : fill once the limit price crosses the book ( default )
+/// fill    : fill all after `after_polls` queries, whatever the price
+/// partial : fill `ratio` of it after `after_polls` queries, rest stays open
+/// rest    : never fill, until cancelled
+/// reject  : refuse it, -2010
+/// expire  : EXPIRED right away with `ratio` filled
+#[derive(Clone, Deserialize)]
+pub struct FillRule {
+    #[serde(default = "default_mode")]
+    pub mode: String,
+    #[serde(default)]
+    pub after_polls: u32,
+    #[serde(default = "default_ratio")]
+    pub ratio: f64
+}
+
+fn default_mode() -> String { return String::from("cross"); }
+fn default_ratio() -> f64 { return 0.5; }
+
+impl Default for FillRule {
+    fn default() -> FillRule {
+        return FillRule { mode: default_mode(), after_polls: 0, ratio: default_ratio() };
+    }
+}
+
+/// An error answered instead of the real thing, `times` times, to `method` `path` ( any method if empty ).
+/// `place_anyway` still places an order it fails, like a timeout after the exchange took it.
+#[derive(Clone, Deserialize)]
+pub struct Fault {
+    #[serde(default)]
+    pub method: String,
+    pub path: String,
+    #[serde(default = "default_fault_status")]
+    pub status: u16,
+    #[serde(default = "default_fault_code")]
+    pub code: i16,
+    #[serde(default)]
+    pub msg: String,
+    #[serde(default = "default_times")]
+    pub times: u32,
+    #[serde(default)]
+    pub delay_ms: u64,
+    #[serde(default)]
+    pub place_anyway: bool
+}
+
+fn default_fault_status() -> u16 { return 400; }
+fn default_fault_code() -> i16 { return -1000; }
+fn default_times() -> u32 { return 1; }
+
+/// Everything the mock serves, loaded from a json scenario :
+/// `prices` are [ bid, ask ] per pair, one step per all-tickers call, the last one stays.
+/// `exchange_info` is a recorded exchangeInfo answer served as is, instead of one built from `symbols`.
+#[derive(Clone, Deserialize, Default)]
+pub struct Scenario {
+    #[serde(default)]
+    pub port: u16,
+    #[serde(default)]
+    pub exchange_info: Option<String>,
+    #[serde(default)]
+    pub latency_ms: u64,
+    #[serde(default)]
+    pub fee_rate: Option<f64>,
+    #[serde(default)]
+    pub symbols: Vec<MockSymbol>,
+    #[serde(default)]
+    pub balances: HashMap<String, f64>,
+    #[serde(default)]
+    pub prices: Vec<HashMap<String, [f64;2]>>,
+    #[serde(default)]
+    pub fills: HashMap<String, FillRule>,
+    #[serde(default)]
+    pub faults: Vec<Fault>
+}
+
+impl Scenario {
+    pub fn load(path: &str) -> Result<Scenario, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+        return serde_json::from_str(&content).map_err(|e| format!("{} is not a scenario: {}", path, e));
+    }
+}
+
+/// An order as the mock keeps it.
+#[derive(Clone, Debug)]
+pub struct MockOrder {
+    pub order_id: u64,
+    pub client_order_id: String,
+    pub symbol: String,
+    pub side: String,
+    pub kind: String,          // LIMIT, LIMIT_MAKER, MARKET
+    pub time_in_force: String,
+    pub price: f64,
+    pub qty: f64,
+    pub executed_qty: f64,
+    pub quote_qty: f64,
+    pub status: String,
+    pub polls: u32,
+    pub time: u64
+}
+
+impl MockOrder {
+    fn is_final(&self) -> bool {
+        return matches!(self.status.as_str(), "FILLED" | "CANCELED" | "EXPIRED" | "REJECTED");
+    }
+}
+
+struct State {
+    scenario: Scenario,
+    step: usize,
+    balances: HashMap<String, f64>,
+    orders: Vec<MockOrder>,
+    next_id: u64
+}
+
+/// A binance-compatible REST server on localhost, for running the bot & tests without the network.
+/// Point `[endpoint] preset = custom` & `rest_endpoint` at `url`.
+pub struct MockExchange {
+    pub url: String,
+    state: Arc<Mutex<State>>
+}
+
+impl MockExchange {
+    /// Serve `scenario` in the background, on its port or any free one when 0.
+    pub fn start(scenario: Scenario) -> io::Result<MockExchange> {
+        let listener = TcpListener::bind(("127.0.0.1", scenario.port))?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let state = Arc::new(Mutex::new(State {
+            balances: scenario.balances.clone(),
+            scenario,
+            step: 0,
+            orders: vec![],
+            next_id: 1
+        }));
+        let shared = state.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let state = shared.clone();
+                thread::spawn(move || { let _ = serve(stream, &state); });
+            }
+        });
+        return Ok(MockExchange { url, state });
+    }
+
+    pub fn balance(&self, asset: &str) -> f64 {
+        return *self.state.lock().unwrap().balances.get(asset).unwrap_or(&0.0);
+    }
+
+    pub fn orders(&self) -> Vec<MockOrder> {
+        return self.state.lock().unwrap().orders.clone();
+    }
+
+    /// Jump to a price step, etc: to move the book under a resting order.
+    pub fn set_step(&self, step: usize) {
+        self.state.lock().unwrap().step = step;
+    }
+}
+
+/// `mock [scenario.json]` : serve a scenario until killed.
+pub fn run(path: &str) {
+    let mut scenario = match Scenario::load(path) {
+        Ok(scenario) => scenario,
+        Err(e) => { println!("> mock: {}", e); return; }
+    };
+    if scenario.port == 0 { scenario.port = DEFAULT_PORT; }
+    let symbols = scenario.symbols.len();
+    let steps = scenario.prices.len();
+    match MockExchange::start(scenario) {
+        Ok(mock) => {
+            println!("> mock: {} symbols, {} price steps on {}", symbols, steps, mock.url);
+            println!("> mock: set [endpoint] preset = custom & rest_endpoint = {}", mock.url);
+            loop { thread::sleep(Duration::from_secs(3600)); }
+        },
+        Err(e) => println!("> mock: can't listen: {}", e)
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    params: HashMap<String, String>
+}
+
+/// One request per connection, answered with `Connection: close`.
+fn serve(stream: TcpStream, state: &Mutex<State>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_string();
+    let target = parts.next().unwrap_or("").to_string();
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() { break; }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") { content_length = value.trim().parse().unwrap_or(0); }
+        }
+    }
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body)?;
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+    let mut params = parse_params(query);
+    params.extend(parse_params(&String::from_utf8_lossy(&body)));
+    let request = Request { method, path: path.to_string(), params };
+
+    let (status, answer) = handle(state, &request);
+    let reason = match status { 200 => "OK", 400 => "Bad Request", 404 => "Not Found", 418 => "I'm a teapot", 429 => "Too Many Requests", _ => "Error" };
+    let body = answer.to_string();
+    let mut stream = stream;
+    write!(stream, "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, reason, body.len(), body)?;
+    return stream.flush();
+}
+
+fn parse_params(text: &str) -> HashMap<String, String> {
+    return text.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (decode(key), decode(value)))
+        .collect();
+}
+
+/// %XX & + of a query string.
+fn decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = vec![];
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'%' if index + 2 < bytes.len() => {
+                match std::str::from_utf8(&bytes[index + 1..index + 3]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(byte) => { decoded.push(byte); index += 3; continue; },
+                    None => decoded.push(b'%')
+                }
+            },
+            b'+' => decoded.push(b' '),
+            byte => decoded.push(byte)
+        }
+        index += 1;
+    }
+    return String::from_utf8_lossy(&decoded).to_string();
+}
+
+fn error(code: i16, msg: &str) -> (u16, Value) {
+    return (400, json!({ "code": code, "msg": msg }));
+}
+
+fn handle(state: &Mutex<State>, request: &Request) -> (u16, Value) {
+    let (latency_ms, fault) = {
+        let mut state = state.lock().unwrap();
+        let fault = state.scenario.faults.iter_mut()
+            .find(|fault| fault.times > 0 && fault.path == request.path && (fault.method.is_empty() || fault.method.eq_ignore_ascii_case(&request.method)))
+            .map(|fault| { fault.times -= 1; fault.clone() });
+        (state.scenario.latency_ms, fault)
+    };
+    thread::sleep(Duration::from_millis(latency_ms + fault.as_ref().map_or(0, |fault| fault.delay_ms)));
+    if let Some(fault) = fault {
+        if fault.place_anyway && request.method == "POST" && request.path == "/api/v3/order" {
+            let _ = new_order(&mut state.lock().unwrap(), &request.params);
+        }
+        return match fault.status {
+            400 => error(fault.code, &fault.msg),
+            status => (status, json!({}))
+        };
+    }
+    let mut state = state.lock().unwrap();
+    let params = &request.params;
+    return match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/api/v3/ping") => (200, json!({})),
+        ("GET", "/api/v3/time") => (200, json!({ "serverTime": quarantine::now_ms() as u64 })),
+        ("GET", "/api/v3/exchangeInfo") => match &state.scenario.exchange_info {
+            Some(path) => match fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|text| serde_json::from_str(&text).map_err(|e| e.to_string())) {
+                Ok(recorded) => (200, recorded),
+                Err(e) => error(-1000, &format!("can't serve {}: {}", path, e))
+            },
+            None => (200, exchange_info(&state.scenario))
+        },
+        ("GET", "/api/v3/ticker/bookTicker") => book_tickers(&mut state, params.get("symbol")),
+        ("GET", "/api/v3/ticker/price") => prices(&state, params.get("symbol")),
+        ("GET", "/api/v3/ticker/24hr") => (200, stats(&state)),
+        ("GET", "/api/v3/account") => (200, account(&state)),
+        ("GET", "/api/v3/account/commission") => (200, commission(&state, params.get("symbol"))),
+        ("GET", "/api/v3/openOrders") => {
+            let open: Vec<Value> = state.orders.iter().filter(|order| !order.is_final()).map(order_json).collect();
+            (200, Value::Array(open))
+        },
+        ("POST", "/api/v3/order") => new_order(&mut state, params),
+        ("GET", "/api/v3/order") => query_order(&mut state, params),
+        ("DELETE", "/api/v3/order") => cancel_order(&mut state, params),
+        // user data stream, events themselves come from a websocket the mock doesn't serve.
+        ("POST", "/api/v3/userDataStream") => (200, json!({ "listenKey": "mock-listen-key" })),
+        ("PUT", "/api/v3/userDataStream") | ("DELETE", "/api/v3/userDataStream") => (200, json!({})),
+        _ => (404, json!({ "code": -1, "msg": format!("mock has no {} {}", request.method, request.path) }))
+    };
+}
+
+/// [ bid, ask ] of `symbol` at current step.
+fn book(state: &State, symbol: &str) -> Option<[f64;2]> {
+    let prices = &state.scenario.prices;
+    if prices.is_empty() { return None; }
+    return prices[state.step.min(prices.len() - 1)].get(symbol).cloned();
+}
+
+/// Every pair priced at current step, by name.
+fn books(state: &State) -> Vec<(String, [f64;2])> {
+    let prices = &state.scenario.prices;
+    if prices.is_empty() { return vec![]; }
+    let mut books: Vec<(String, [f64;2])> = prices[state.step.min(prices.len() - 1)].iter()
+        .map(|(symbol, book)| (symbol.clone(), *book))
+        .collect();
+    books.sort_by(|a, b| a.0.cmp(&b.0));
+    return books;
+}
+
+fn exchange_info(scenario: &Scenario) -> Value {
+    let symbols: Vec<Value> = scenario.symbols.iter().map(|symbol| json!({
+        "symbol": symbol.symbol,
+        "status": symbol.status,
+        "baseAsset": symbol.base,
+        "baseAssetPrecision": 8,
+        "quoteAsset": symbol.quote,
+        "quotePrecision": 8,
+        "quoteAssetPrecision": 8,
+        "baseCommissionPrecision": 8,
+        "quoteCommissionPrecision": 8,
+        "orderTypes": symbol.order_types,
+        "icebergAllowed": false,
+        "ocoAllowed": false,
+        "quoteOrderQtyMarketAllowed": true,
+        "isSpotTradingAllowed": true,
+        "isMarginTradingAllowed": false,
+        "permissions": ["SPOT"],
+        "filters": [
+            { "filterType": "PRICE_FILTER", "minPrice": symbol.tick_size, "maxPrice": "1000000.00000000", "tickSize": symbol.tick_size },
+            { "filterType": "LOT_SIZE", "minQty": symbol.min_qty, "maxQty": symbol.max_qty, "stepSize": symbol.step_size }
+        ]
+    })).collect();
+    return json!({
+        "timezone": "UTC",
+        "serverTime": quarantine::now_ms() as u64,
+        "rateLimits": [],
+        "exchangeFilters": [],
+        "symbols": symbols
+    });
+}
+
+fn ticker_json(symbol: &str, book: [f64;2]) -> Value {
+    return json!({ "symbol": symbol, "bidPrice": book[0].to_string(), "bidQty": "1000.0",
+        "askPrice": book[1].to_string(), "askQty": "1000.0" });
+}
+
+/// All tickers move the scenario one step forward, one symbol doesn't.
+fn book_tickers(state: &mut State, symbol: Option<&String>) -> (u16, Value) {
+    if let Some(symbol) = symbol {
+        return match book(state, symbol) {
+            Some(book) => (200, ticker_json(symbol, book)),
+            None => error(-1121, "Invalid symbol.")
+        };
+    }
+    let tickers: Vec<Value> = books(state).iter().map(|(symbol, book)| ticker_json(symbol, *book)).collect();
+    state.step += 1;
+    return (200, Value::Array(tickers));
+}
+
+fn prices(state: &State, symbol: Option<&String>) -> (u16, Value) {
+    let price = |symbol: &str| book(state, symbol).map(|book| json!({ "symbol": symbol, "price": ((book[0] + book[1]) / 2.0).to_string() }));
+    if let Some(symbol) = symbol {
+        return price(symbol).map_or_else(|| error(-1121, "Invalid symbol."), |price| (200, price));
+    }
+    return (200, Value::Array(books(state).iter().filter_map(|(symbol, _)| price(symbol)).collect()));
+}
+
+/// 24h stats, busy & tight enough to pass the default filter.
+fn stats(state: &State) -> Value {
+    let stats: Vec<Value> = books(state).iter().map(|(symbol, book)| {
+        let last = (book[0] + book[1]) / 2.0;
+        json!({
+            "symbol": symbol, "priceChange": "0", "priceChangePercent": "0", "weightedAvgPrice": last.to_string(),
+            "prevClosePrice": last.to_string(), "lastPrice": last.to_string(), "bidPrice": book[0].to_string(), "askPrice": book[1].to_string(),
+            "openPrice": last.to_string(), "highPrice": last.to_string(), "lowPrice": last.to_string(),
+            "volume": "10000000", "quoteVolume": (10_000_000.0 * last).to_string(),
+            "openTime": 0, "closeTime": 0, "firstId": 0, "lastId": 100000, "count": 100000
+        })
+    }).collect();
+    return Value::Array(stats);
+}
+
+fn fee_rate(state: &State) -> f64 {
+    return state.scenario.fee_rate.unwrap_or(DEFAULT_FEE_RATE);
+}
+
+fn account(state: &State) -> Value {
+    let commission = (fee_rate(state) * 10_000.0).round();
+    let balances: Vec<Value> = state.balances.iter()
+        .map(|(asset, free)| json!({ "asset": asset, "free": format!("{:.8}", free), "locked": "0.00000000" }))
+        .collect();
+    return json!({
+        "makerCommission": commission, "takerCommission": commission, "buyerCommission": 0, "sellerCommission": 0,
+        "canTrade": true, "canWithdraw": false, "canDeposit": true, "balances": balances
+    });
+}
+
+fn commission(state: &State, symbol: Option<&String>) -> Value {
+    let rate = fee_rate(state).to_string();
+    return json!({
+        "symbol": symbol.cloned().unwrap_or_default(),
+        "standardCommission": { "maker": rate, "taker": rate, "buyer": "0", "seller": "0" },
+        "taxCommission": { "maker": "0", "taker": "0", "buyer": "0", "seller": "0" },
+        "discount": { "enabledForAccount": false, "enabledForSymbol": false, "discountAsset": "BNB", "discount": "0.75" }
+    });
+}
+
+fn order_json(order: &MockOrder) -> Value {
+    return json!({
+        "symbol": order.symbol, "orderId": order.order_id, "orderListId": -1, "clientOrderId": order.client_order_id,
+        "price": order.price.to_string(), "origQty": order.qty.to_string(), "executedQty": order.executed_qty.to_string(),
+        "cummulativeQuoteQty": order.quote_qty.to_string(), "status": order.status, "timeInForce": order.time_in_force,
+        "type": order.kind, "side": order.side, "stopPrice": "0", "icebergQty": "0", "time": order.time,
+        "updateTime": order.time, "isWorking": !order.is_final(), "origQuoteOrderQty": "0"
+    });
+}
+
+fn new_order(state: &mut State, params: &HashMap<String, String>) -> (u16, Value) {
+    let number = |key: &str| params.get(key).and_then(|value| value.parse::<f64>().ok());
+    let symbol = params.get("symbol").cloned().unwrap_or_default();
+    let info = match state.scenario.symbols.iter().find(|info| info.symbol == symbol) {
+        Some(info) => info.clone(),
+        None => return error(-1121, "Invalid symbol.")
+    };
+    let side = params.get("side").cloned().unwrap_or_default();
+    let kind = params.get("type").cloned().unwrap_or_default();
+    let client_order_id = params.get("newClientOrderId").cloned().unwrap_or_else(|| format!("mock-{}", state.next_id));
+    if state.orders.iter().any(|order| order.client_order_id == client_order_id && !order.is_final()) {
+        return error(-2010, "Duplicate order sent.");
+    }
+    let book = book(state, &symbol).unwrap_or([0.0, 0.0]);
+    let is_buy = side == "BUY";
+    let price = if kind == "MARKET" { if is_buy { book[1] } else { book[0] } } else { number("price").unwrap_or(0.0) };
+    let qty = match (number("quantity"), number("quoteOrderQty")) {
+        (Some(qty), _) => qty,
+        (None, Some(quote_qty)) if price > 0.0 => quote_qty / price,
+        _ => return error(-1102, "Mandatory parameter 'quantity' was not sent, was empty/null, or malformed.")
+    };
+    if price <= 0.0 || qty <= 0.0 { return error(-1013, "Filter failure: invalid price or quantity."); }
+    let (paid, needed) = if is_buy { (&info.quote, qty * price) } else { (&info.base, qty) };
+    if *state.balances.get(paid).unwrap_or(&0.0) < needed - 1e-9 {
+        return error(-2010, "Account has insufficient balance for requested action.");
+    }
+    let rule = state.scenario.fills.get(&symbol).cloned().unwrap_or_default();
+    if rule.mode == "reject" { return error(-2010, "Order rejected by mock."); }
+    let crosses = if is_buy { price >= book[1] } else { price <= book[0] };
+    if kind == "LIMIT_MAKER" && crosses { return error(-2010, "Order would immediately match and take."); }
+
+    let mut order = MockOrder {
+        order_id: state.next_id,
+        client_order_id,
+        symbol,
+        side,
+        kind: kind.clone(),
+        time_in_force: params.get("timeInForce").cloned().unwrap_or(String::from("GTC")),
+        price,
+        qty,
+        executed_qty: 0.0,
+        quote_qty: 0.0,
+        status: String::from("NEW"),
+        polls: 0,
+        time: quarantine::now_ms() as u64
+    };
+    state.next_id += 1;
+    advance(state, &mut order, &info, &rule);
+    // what can't fill right away doesn't rest.
+    let is_immediate = kind == "MARKET" || order.time_in_force == "IOC" || order.time_in_force == "FOK";
+    if !order.is_final() && (is_immediate || rule.mode == "expire") {
+        if kind == "MARKET" && rule.mode != "expire" { let qty = order.qty; fill(state, &mut order, &info, qty); }
+        else { order.status = String::from("EXPIRED"); }
+    }
+    state.orders.push(order.clone());
+    let mut answer = order_json(&order);
+    answer["transactTime"] = json!(order.time);
+    answer["fills"] = json!([]);
+    return (200, answer);
+}
+
+/// Fill `qty` more of `order` at its price, moving balances less the fee on what it receives.
+fn fill(state: &mut State, order: &mut MockOrder, info: &MockSymbol, qty: f64) {
+    let qty = qty.min(order.qty - order.executed_qty);
+    if qty <= 0.0 { return; }
+    let quote = qty * order.price;
+    let rate = fee_rate(state);
+    let (paid, paid_qty, received, received_qty) = if order.side == "BUY" {
+        (&info.quote, quote, &info.base, qty)
+    } else {
+        (&info.base, qty, &info.quote, quote)
+    };
+    *state.balances.entry(paid.clone()).or_insert(0.0) -= paid_qty;
+    *state.balances.entry(received.clone()).or_insert(0.0) += received_qty * (1.0 - rate);
+    order.executed_qty += qty;
+    order.quote_qty += quote;
+    order.status = String::from(if order.executed_qty >= order.qty - 1e-12 { "FILLED" } else { "PARTIALLY_FILLED" });
+}
+
+/// Move an open order along its fill rule, once per query.
+fn advance(state: &mut State, order: &mut MockOrder, info: &MockSymbol, rule: &FillRule) {
+    if order.is_final() { return; }
+    let is_due = order.polls >= rule.after_polls;
+    match rule.mode.as_str() {
+        "fill" if is_due => { let qty = order.qty; fill(state, order, info, qty); },
+        "partial" | "expire" if is_due => {
+            let target = order.qty * rule.ratio;
+            fill(state, order, info, target - order.executed_qty);
+        },
+        "cross" => {
+            let book = book(state, &order.symbol).unwrap_or([0.0, 0.0]);
+            let crosses = if order.side == "BUY" { book[1] > 0.0 && order.price >= book[1] } else { book[0] > 0.0 && order.price <= book[0] };
+            if crosses { let qty = order.qty; fill(state, order, info, qty); }
+        },
+        _ => {}
+    }
+}
+
+fn find_order(state: &State, params: &HashMap<String, String>) -> Option<usize> {
+    let order_id = params.get("orderId").and_then(|id| id.parse::<u64>().ok());
+    let client_order_id = params.get("origClientOrderId");
+    return state.orders.iter().position(|order| Some(order.order_id) == order_id || Some(&order.client_order_id) == client_order_id);
+}
+
+fn query_order(state: &mut State, params: &HashMap<String, String>) -> (u16, Value) {
+    let index = match find_order(state, params) {
+        Some(index) => index,
+        None => return error(-2013, "Order does not exist.")
+    };
+    let mut order = state.orders[index].clone();
+    let info = state.scenario.symbols.iter().find(|info| info.symbol == order.symbol).cloned();
+    let rule = state.scenario.fills.get(&order.symbol).cloned().unwrap_or_default();
+    order.polls += 1;
+    if let Some(info) = info { advance(state, &mut order, &info, &rule); }
+    state.orders[index] = order.clone();
+    return (200, order_json(&order));
+}
+
+fn cancel_order(state: &mut State, params: &HashMap<String, String>) -> (u16, Value) {
+    let order = match find_order(state, params) {
+        Some(index) => &mut state.orders[index],
+        None => return error(-2011, "Unknown order sent.")
+    };
+    if order.is_final() { return error(-2011, "Unknown order sent."); }
+    order.status = String::from("CANCELED");
+    let mut answer = order_json(order);
+    answer["origClientOrderId"] = json!(order.client_order_id);
+    return (200, answer);
+}
@@ -0,0 +1,177 @@
+use std::{
+    fs,
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH}};
+
+use colored::*;
+use configparser::ini::Ini;
+use serde::{Deserialize, Serialize};
+
+const QUARANTINE_FILE:&str = "quarantine.json"; // json, evidence arrays don't fit in ini
+const DEFAULT_COOLDOWN_HOURS:u64 = 24;
+
+/// What the ring looked like when it was flagged.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Evidence {
+    pub pairs: Vec<String>,     // [ stable-symbol, symbol-bridge, bridge-stable ]
+    pub books: Vec<[f64;2]>,    // [ bid, ask ] of each pair
+    pub prices: Vec<f64>,       // policy prices used to compute profit
+    pub ticks: Vec<f64>,        // spread of each pair in ticks
+    pub profit: f64,
+    pub percentage: f64
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Entry {
+    pub since_ms: u128,
+    pub until_ms: u128,
+    pub evidence: Evidence
+}
+
+/// Rings with an anomalous profit ( > warning ratio ), skipped by analysis
+/// until their cooldown expires. Persisted to `quarantine.json`.
+pub struct Quarantine {
+    entries: HashMap<String, Entry>,
+    cooldown_ms: u128,
+    file: String
+}
+
+pub fn now_ms() -> u128 {
+    return SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+}
+
+impl Quarantine {
+    /// Load quarantine, `[quarantine] cooldown_hours` (default 24) for new entries.
+    pub fn load(config: &Ini) -> Quarantine {
+        let cooldown_hours = config.getuint("quarantine", "cooldown_hours").unwrap_or(None).unwrap_or(DEFAULT_COOLDOWN_HOURS);
+        return Quarantine::from_file(QUARANTINE_FILE, cooldown_hours as u128 * 3_600_000);
+    }
+
+    fn from_file(file: &str, cooldown_ms: u128) -> Quarantine {
+        let mut quarantine = Quarantine { entries: HashMap::new(), cooldown_ms, file: file.to_string() };
+        if let Ok(content) = fs::read_to_string(file) {
+            match serde_json::from_str::<HashMap<String, Entry>>(&content) {
+                Ok(entries) => quarantine.entries = entries,
+                Err(e) => println!("> quarantine: can't read {}: {}", file, e)
+            }
+        }
+        quarantine.purge();
+        return quarantine;
+    }
+
+    fn save(&self) {
+        let content = match serde_json::to_string_pretty(&self.entries) {
+            Ok(content) => content,
+            Err(e) => { println!("Error saving cache: {:?}", e); return; }
+        };
+        if let Err(msg) = fs::write(&self.file, content) {
+            println!("Error saving cache: {:?}", msg);
+        }
+    }
+
+    /// Drop expired entries, return how many.
+    pub fn purge(&mut self) -> usize {
+        let now = now_ms();
+        let count = self.entries.len();
+        self.entries.retain(|_, entry| entry.until_ms > now);
+        let expired = count - self.entries.len();
+        if expired > 0 {
+            println!("> quarantine: {} rings released.", expired);
+            self.save();
+        }
+        return expired;
+    }
+
+    /// Is this ring still in cooldown ? expired entries don't count even before `purge`.
+    pub fn contains(&self, symbol: &str) -> bool {
+        return self.entries.get(&symbol.to_uppercase()).is_some_and(|entry| entry.until_ms > now_ms());
+    }
+
+    /// Quarantine a ring with its evidence until cooldown expires.
+    pub fn add(&mut self, symbol: &str, evidence: Evidence) {
+        let now = now_ms();
+        println!("{} {} at {:.2}% for {} hours.", "> quarantine:".red(), symbol.bold(), evidence.percentage, self.cooldown_ms / 3_600_000);
+        self.entries.insert(symbol.to_uppercase(), Entry { since_ms: now, until_ms: now + self.cooldown_ms, evidence });
+        self.save();
+    }
+
+    /// Release a ring by hand, return false if it wasn't quarantined.
+    pub fn release(&mut self, symbol: &str) -> bool {
+        let removed = self.entries.remove(&symbol.to_uppercase()).is_some();
+        if removed { self.save(); }
+        return removed;
+    }
+
+    /// Print all quarantined rings with their evidence.
+    pub fn list(&self) {
+        let now = now_ms();
+        println!("> quarantine: {} rings.", self.entries.len());
+        let mut entries: Vec<(&String, &Entry)> = self.entries.iter().collect();
+        entries.sort_by_key(|(_, entry)| entry.since_ms);
+        for (symbol, entry) in entries {
+            let evidence = &entry.evidence;
+            println!("| {} | {:.2}% = ${:.4} | expires in {} min",
+                symbol.bold(), evidence.percentage, evidence.profit, (entry.until_ms.saturating_sub(now)) / 60_000);
+            for (index, pair) in evidence.pairs.iter().enumerate() {
+                // evidence comes from a file that may have been edited by hand.
+                let book = evidence.books.get(index).map_or(String::from("?"), |book| format!("{:?}", book));
+                let price = evidence.prices.get(index).map_or(String::from("?"), |price| price.to_string());
+                let ticks = evidence.ticks.get(index).map_or(String::from("?"), |ticks| ticks.to_string());
+                println!("|   {} {} @ {} ({} ticks)", pair, book, price, ticks);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evidence() -> Evidence {
+        return Evidence { pairs: vec![String::from("XTZBUSD"), String::from("XTZBNB"), String::from("BNBBUSD")],
+            books: vec![[0.999, 1.0]], prices: vec![1.0], ticks: vec![1.0], profit: 12.0, percentage: 12.0 };
+    }
+
+    /// Quarantine kept in its own temp file.
+    fn quarantine(name: &str, cooldown_ms: u128) -> (Quarantine, String) {
+        let file = std::env::temp_dir().join(format!("railgun-{}-{}.json", name, std::process::id())).to_string_lossy().to_string();
+        let _ = fs::remove_file(&file);
+        return (Quarantine::from_file(&file, cooldown_ms), file);
+    }
+
+    #[test]
+    fn expired_ring_is_out_of_quarantine() {
+        let (mut quarantine, file) = quarantine("expiry", 0);
+        quarantine.add("xtz", evidence());
+        // still listed until purged, but not skipped anymore.
+        assert!(!quarantine.contains("XTZ"));
+        assert_eq!(quarantine.purge(), 1);
+        let _ = fs::remove_file(file);
+    }
+
+    #[test]
+    fn ring_in_cooldown_until_released() {
+        let (mut quarantine, file) = quarantine("release", 3_600_000);
+        quarantine.add("XTZ", evidence());
+        assert!(quarantine.contains("XTZ") && quarantine.contains("xtz"));
+        assert_eq!(quarantine.purge(), 0);
+        // short evidence is listed as is.
+        quarantine.list();
+        assert!(quarantine.release("xtz"));
+        assert!(!quarantine.release("XTZ"));
+        assert!(!quarantine.contains("XTZ"));
+        let _ = fs::remove_file(file);
+    }
+
+    #[test]
+    fn entries_survive_a_restart() {
+        let (mut quarantine, file) = quarantine("restart", 3_600_000);
+        quarantine.add("XTZ", evidence());
+        let loaded = Quarantine::from_file(&file, 3_600_000);
+        assert!(loaded.contains("XTZ"));
+        let entry = &loaded.entries["XTZ"];
+        assert_eq!(entry.until_ms - entry.since_ms, 3_600_000);
+        assert_eq!((entry.evidence.pairs.len(), entry.evidence.percentage), (3, 12.0));
+        let _ = fs::remove_file(file);
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::BTreeMap;
+
+use configparser::ini::Ini;
+use hex::encode as hex_encode;
+use hmac::{Hmac, Mac, NewMac};
+use serde::de::DeserializeOwned;
+use sha2::Sha256;
+
+use binance::errors::BinanceContentError;
+use binance::util::build_signed_request;
+
+use crate::credentials;
+use crate::endpoint::Endpoint;
+use crate::error::{Context, Error, Result};
+
+/// Signed calls to REST endpoints binance-rs has no method for.
+pub struct SignedClient {
+    api_key: String,
+    secret_key: String,
+    host: String,
+    recv_window: u64,
+    inner_client: reqwest::blocking::Client
+}
+
+impl SignedClient {
+    /// Keys from `credentials`, an error when they can't be resolved, host from `[endpoint]`.
+    /// `recv_window` is the account's, so it follows what `retry::resync_time` widened.
+    pub fn from_config(config: &Ini, recv_window: u64) -> Result<SignedClient> {
+        let keys = credentials::load(config).map_err(|e| Error::config("keys", "credentials", &e))?;
+        return Ok(SignedClient {
+            api_key: keys.api_key.clone(),
+            secret_key: keys.secret_key.clone(),
+            host: Endpoint::load(config)?.rest,
+            recv_window,
+            inner_client: reqwest::blocking::Client::new()
+        });
+    }
+
+    /// GET `path` ( etc: /api/v3/account/commission ) with signed `parameters`.
+    pub fn get_signed<T: DeserializeOwned>(&self, path: &str, parameters: BTreeMap<String, String>) -> Result<T> {
+        let request = build_signed_request(parameters, self.recv_window)?;
+        let mut signed_key = Hmac::<Sha256>::new_from_slice(self.secret_key.as_bytes())
+            .map_err(|e| Error::config("keys", "secret_key", &e.to_string()))?;
+        signed_key.update(request.as_bytes());
+        let signature = hex_encode(signed_key.finalize().into_bytes());
+        let url = format!("{}{}?{}&signature={}", self.host, path, request, signature);
+        let response = self.inner_client.get(url.as_str())
+            .header("X-MBX-APIKEY", self.api_key.as_str())
+            .send().context(path)?;
+        let status = response.status();
+        let content = response.text().context(path)?;
+        if status.as_u16() == 429 || status.as_u16() == 418 { return Err(Error::RateLimited(status.as_u16())).context(path); }
+        if !status.is_success() {
+            // binance answers errors as {"code":-1100,"msg":"..."}
+            return Err(match serde_json::from_str::<BinanceContentError>(&content) {
+                Ok(error) => Error::Exchange { code: error.code, msg: error.msg },
+                Err(_) => Error::Network(format!("{} {}", status.as_u16(), content))
+            }).context(path);
+        }
+        return serde_json::from_str::<T>(&content).map_err(|e| Error::Network(format!("can't parse {}: {}", path, e)));
+    }
+}
@@ -0,0 +1,138 @@
+use std::{
+    thread,
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use configparser::ini::Ini;
+
+use binance::api::*;
+use binance::account::Account;
+use binance::general::General;
+
+use crate::endpoint::Endpoint;
+use crate::error::{Action, Context, Error, Result};
+
+// default of [retry]
+const MAX_ATTEMPTS:u32 = 3;       // tries of one call, 1st included
+const BASE_MS:u64 = 200;          // 1st backoff
+const MULTIPLIER:f64 = 2.0;       // each backoff is this much longer
+const MAX_MS:u64 = 5000;          // backoff cap
+const RATE_LIMIT_MS:u64 = 10_000; // backoff after 429, -1003 or -1015
+const HALT_AFTER:u32 = 10;        // failed rings in a row before we stop
+const MAX_RECV_WINDOW:u64 = 60_000; // binance refuses more
+const MAX_AHEAD_MS:i64 = 1000;      // binance refuses timestamps further in the future, whatever the recvWindow
+
+/// How failed calls are retried, and what each Binance error code leads to.
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub halt_after: u32,
+    base_ms: u64,
+    multiplier: f64,
+    max_ms: u64,
+    rate_limit_ms: u64,
+    overrides: HashMap<i16, Action>
+}
+
+impl RetryPolicy {
+    /// `[retry]` max_attempts, base_ms, multiplier, max_ms, rate_limit_ms, halt_after,
+    /// `[error_actions]` CODE = retry | resync_time | refresh_balances | skip_ring | halt overrides one code.
+    pub fn from_config(config: &Ini) -> RetryPolicy {
+        let mut overrides = HashMap::new();
+        if let Some(section) = config.get_map_ref().get("error_actions") {
+            for (code, action) in section {
+                match (code.trim().parse::<i16>(), action.as_deref().and_then(Action::parse)) {
+                    (Ok(code), Some(action)) => { overrides.insert(code, action); },
+                    _ => println!("> retry: invalid [error_actions] {} = {:?}", code, action)
+                }
+            }
+        }
+        let policy = RetryPolicy {
+            max_attempts: config.getuint("retry", "max_attempts").unwrap_or(None).unwrap_or(MAX_ATTEMPTS as u64).max(1) as u32,
+            halt_after: config.getuint("retry", "halt_after").unwrap_or(None).unwrap_or(HALT_AFTER as u64).max(1) as u32,
+            base_ms: config.getuint("retry", "base_ms").unwrap_or(None).unwrap_or(BASE_MS),
+            multiplier: config.getfloat("retry", "multiplier").unwrap_or(None).unwrap_or(MULTIPLIER),
+            max_ms: config.getuint("retry", "max_ms").unwrap_or(None).unwrap_or(MAX_MS),
+            rate_limit_ms: config.getuint("retry", "rate_limit_ms").unwrap_or(None).unwrap_or(RATE_LIMIT_MS),
+            overrides
+        };
+        println!("> retry: {} attempts, backoff {} ms x{} up to {} ms, {} ms when rate limited.",
+            policy.max_attempts, policy.base_ms, policy.multiplier, policy.max_ms, policy.rate_limit_ms);
+        return policy;
+    }
+
+    /// What to do about `error` : `[error_actions]` first, then the default of its code.
+    pub fn classify(&self, error: &Error) -> Action {
+        if let Error::Exchange { code, .. } = error.root() {
+            if let Some(action) = self.overrides.get(code) { return *action; }
+        }
+        return error.action();
+    }
+
+    /// Wait before retry number `attempt` ( from 1 ).
+    pub fn backoff(&self, attempt: u32, error: &Error) -> Duration {
+        if error.is_rate_limit() { return Duration::from_millis(self.rate_limit_ms); }
+        let ms = self.base_ms as f64 * self.multiplier.powi(attempt.saturating_sub(1) as i32);
+        return Duration::from_millis((ms as u64).min(self.max_ms));
+    }
+
+    /// Call `f` until it succeeds, gets an error that isn't worth retrying, or runs out of attempts.
+    pub fn run<T>(&self, what: &str, mut f: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut attempt = 1;
+        loop {
+            match f() {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt < self.max_attempts && self.classify(&e) == Action::Retry => {
+                    let wait = self.backoff(attempt, &e);
+                    println!("> retry: {} ({}/{}) in {} ms: {}", what, attempt, self.max_attempts - 1, wait.as_millis(), e);
+                    thread::sleep(wait);
+                    attempt += 1;
+                },
+                Err(e) => return Err(e).context(what)
+            }
+        }
+    }
+}
+
+/// Measure how far our clock is from the exchange's and widen `account` recvWindow to cover it.
+/// binance-rs signs with the local clock, so a clock off by more than 60s, or ahead by more than 1s, can't be fixed here.
+pub fn resync_time(config: &Ini, account: &mut Account) -> Result<()> {
+    let general: General = Binance::new_with_config(None, None, &Endpoint::load(config)?.binance_config());
+    let local_ms = || SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_millis() as i64).unwrap_or(0);
+    let sent_ms = local_ms();
+    let server_ms = general.get_server_time().context("server time")?.server_time as i64;
+    let received_ms = local_ms();
+    let offset_ms = server_ms - (sent_ms + received_ms) / 2;
+    println!("> time: local clock is {} ms {} the exchange.", offset_ms.abs(), if offset_ms > 0 { "behind" } else { "ahead of" });
+    let needed = recv_window_for(offset_ms, (received_ms - sent_ms).max(0) as u64)?;
+    if needed > account.recv_window {
+        account.recv_window = needed;
+        println!("> time: recvWindow is now {} ms.", needed);
+    }
+    return Ok(());
+}
+
+/// recvWindow covering a clock `offset_ms` behind the exchange ( negative when ahead ) and a round trip,
+/// Halt when no recvWindow can.
+fn recv_window_for(offset_ms: i64, round_trip_ms: u64) -> Result<u64> {
+    if offset_ms < -MAX_AHEAD_MS {
+        return Err(Error::Halt(format!("clock is {} ms ahead, binance refuses timestamps over {} ms in the future: sync it ( ntp ) and restart", -offset_ms, MAX_AHEAD_MS)));
+    }
+    let needed = offset_ms.unsigned_abs() * 2 + round_trip_ms + 1000;
+    if needed > MAX_RECV_WINDOW {
+        return Err(Error::Halt(format!("clock is {} ms off, sync it ( ntp ) and restart", offset_ms)));
+    }
+    return Ok(needed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_ahead_halts_instead_of_widening() {
+        assert_eq!(recv_window_for(3000, 100).unwrap(), 7100);
+        assert_eq!(recv_window_for(-500, 100).unwrap(), 2100);
+        assert!(matches!(recv_window_for(-1500, 100), Err(Error::Halt(_))));
+        assert!(matches!(recv_window_for(40_000, 100), Err(Error::Halt(_))));
+    }
+}
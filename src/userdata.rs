@@ -0,0 +1,351 @@
+use std::{
+    thread,
+    collections::HashMap,
+    sync::{Arc, Condvar, Mutex, atomic::{AtomicBool, Ordering}},
+    time::{Duration, Instant}};
+
+use colored::*;
+use configparser::ini::Ini;
+
+use binance::account::Account;
+use binance::model::EventBalance;
+use binance::userstream::UserStream;
+use binance::websockets::{WebSockets, WebsocketEvent};
+
+use crate::endpoint::Endpoint;
+use crate::error::Result;
+
+/// Binance drops a listenKey after 60 minutes without keepalive.
+const KEEPALIVE_EVERY: Duration = Duration::from_secs(30 * 60);
+/// Wait before opening a new socket after a disconnect.
+const RECONNECT_DELAY: Duration = Duration::from_millis(2000);
+/// How long a balance read waits for the account update following a fill.
+const BALANCE_WAIT: Duration = Duration::from_millis(500);
+
+/// Last known state of an order, pushed by executionReport.
+#[derive(Clone, Debug)]
+pub struct OrderUpdate {
+    pub symbol: String,
+    pub order_id: u64,
+    pub client_order_id: String,
+    pub status: String,
+    pub orig_qty: f64,
+    pub executed_qty: f64,
+    pub quote_qty: f64,   // cumulative quote filled, executed_qty at quote_qty / executed_qty on average
+    pub event_time: u64,
+    pub seq: u64 // local counter, to tell a new update from an old one.
+}
+
+struct Store {
+    orders: HashMap<u64, OrderUpdate>,
+    balances: HashMap<String, f64>,
+    balance_time: u64,   // event time of last account update
+    fill_time: u64,      // event time of last trade, balances older than this are stale
+    seq: u64
+}
+
+/// In-memory order & balance store fed by the user data stream.
+/// Executor waits on it instead of polling REST; any miss falls back to REST.
+pub struct UserData {
+    store: Mutex<Store>,
+    changed: Condvar,
+    connected: AtomicBool,
+    running: AtomicBool
+}
+
+impl UserData {
+    fn new() -> UserData {
+        UserData {
+            store: Mutex::new(Store { orders: HashMap::new(), balances: HashMap::new(), balance_time: 0, fill_time: 0, seq: 0 }),
+            changed: Condvar::new(),
+            connected: AtomicBool::new(false),
+            running: AtomicBool::new(true)
+        }
+    }
+
+    /// A store that is never connected, every read falls back to REST.
+    pub fn disabled() -> UserData {
+        let user_data = UserData::new();
+        user_data.running.store(false, Ordering::Relaxed);
+        return user_data;
+    }
+
+    pub fn is_connected(&self) -> bool {
+        return self.connected.load(Ordering::Relaxed);
+    }
+
+    /// Wait until `order_id` has an update newer than `seen_seq` or `timeout` passes.
+    /// None when nothing newer came in time, the stream may have missed it so the caller asks REST.
+    pub fn wait_order(&self, order_id: u64, seen_seq: u64, timeout: Duration) -> Option<OrderUpdate> {
+        if !self.is_connected() { return None; }
+        let deadline = Instant::now() + timeout;
+        let mut store = self.store.lock().unwrap();
+        loop {
+            if let Some(update) = store.orders.get(&order_id) {
+                if update.seq > seen_seq { return Some(update.clone()); }
+            }
+            let now = Instant::now();
+            if now >= deadline { break; }
+            store = self.changed.wait_timeout(store, deadline - now).unwrap().0;
+        }
+        return None;
+    }
+
+    /// Free balance of `asset`, waiting briefly for the account update after the last fill.
+    /// None when the stream is down or has no fresh value, so the caller asks REST.
+    pub fn balance(&self, asset: &str) -> Option<f64> {
+        if !self.is_connected() { return None; }
+        let deadline = Instant::now() + BALANCE_WAIT;
+        let mut store = self.store.lock().unwrap();
+        while store.balance_time < store.fill_time {
+            let now = Instant::now();
+            if now >= deadline { return None; }
+            store = self.changed.wait_timeout(store, deadline - now).unwrap().0;
+        }
+        return store.balances.get(asset).cloned();
+    }
+
+    fn seed_balances(&self, account: &Account) {
+        match account.get_account() {
+            Ok(answer) => {
+                let mut store = self.store.lock().unwrap();
+                for balance in answer.balances {
+                    store.balances.insert(balance.asset, balance.free.parse::<f64>().unwrap_or(0.0));
+                }
+            },
+            Err(e) => println!("> userdata: can't seed balances: {:?}", e.0)
+        }
+    }
+
+    fn on_balances(&self, balances: &[EventBalance], event_time: u64) {
+        let mut store = self.store.lock().unwrap();
+        for balance in balances {
+            store.balances.insert(balance.asset.clone(), balance.free.parse::<f64>().unwrap_or(0.0));
+        }
+        if event_time > store.balance_time { store.balance_time = event_time; }
+        self.changed.notify_all();
+    }
+
+    fn on_order(&self, mut update: OrderUpdate, is_trade: bool) {
+        let mut store = self.store.lock().unwrap();
+        store.seq += 1;
+        update.seq = store.seq;
+        if is_trade && update.event_time > store.fill_time { store.fill_time = update.event_time; }
+        // stream only tells the last fill, add up what came before.
+        update.quote_qty += store.orders.get(&update.order_id).map_or(0.0, |order| order.quote_qty);
+        store.orders.insert(update.order_id, update);
+        self.changed.notify_all();
+    }
+
+    fn on_event(&self, event: WebsocketEvent) {
+        match event {
+            WebsocketEvent::OrderTrade(trade) => {
+                let is_trade = trade.execution_type == "TRADE";
+                let last_quote = match is_trade {
+                    true => trade.qty_last_filled_trade.parse::<f64>().unwrap_or(0.0) * trade.price_last_filled_trade.parse::<f64>().unwrap_or(0.0),
+                    false => 0.0
+                };
+                let update = OrderUpdate {
+                    symbol: trade.symbol,
+                    order_id: trade.order_id,
+                    client_order_id: trade.new_client_order_id,
+                    status: trade.order_status,
+                    orig_qty: trade.qty.parse::<f64>().unwrap_or(0.0),
+                    executed_qty: trade.accumulated_qty_filled_trades.parse::<f64>().unwrap_or(0.0),
+                    quote_qty: last_quote,
+                    event_time: trade.event_time,
+                    seq: 0
+                };
+                self.on_order(update, is_trade);
+            },
+            WebsocketEvent::AccountUpdate(account) => self.on_balances(&account.balance, account.event_time),
+            WebsocketEvent::BalanceUpdate(account) => self.on_balances(&account.balance, account.event_time),
+            _ => {}
+        }
+    }
+
+    /// A connected store holding `updates`, as if the stream pushed them.
+    #[cfg(test)]
+    pub(crate) fn fed(updates: Vec<OrderUpdate>) -> UserData {
+        let user_data = UserData::new();
+        user_data.connected.store(true, Ordering::Relaxed);
+        for update in updates { user_data.on_order(update, false); }
+        return user_data;
+    }
+
+    /// Stop keepalive and socket threads after their current cycle.
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Open the user data stream if `[stream] enabled` (default true).
+/// listenKey is created, kept alive and re-created on every reconnect.
+pub fn start(config: &Ini, account: &Account, user_stream: UserStream) -> Result<Arc<UserData>> {
+    let enabled = config.getbool("stream", "enabled").unwrap_or(None).unwrap_or(true);
+    if !enabled {
+        println!("> userdata: stream disabled, polling REST.");
+        return Ok(Arc::new(UserData::disabled()));
+    }
+    let mut endpoint = Endpoint::load(config)?;
+    if let Some(ws_endpoint) = config.get("stream", "ws_endpoint") { endpoint.ws = ws_endpoint; }
+    let ws_config = endpoint.binance_config();
+
+    let user_data = Arc::new(UserData::new());
+    user_data.seed_balances(account);
+
+    let listen_key: Arc<Mutex<String>> = Arc::new(Mutex::new(String::new()));
+    //
+    // KEEPALIVE
+    //
+    let _user_data = user_data.clone();
+    let _listen_key = listen_key.clone();
+    let _user_stream = user_stream.clone();
+    thread::spawn(move || {
+        while _user_data.running.load(Ordering::Relaxed) {
+            thread::sleep(KEEPALIVE_EVERY);
+            let key = _listen_key.lock().unwrap().clone();
+            if key.is_empty() { continue; }
+            match _user_stream.keep_alive(&key) {
+                Ok(_) => println!("> userdata: keepalive sent."),
+                Err(e) => println!("> userdata: keepalive failed: {:?}", e.0)
+            }
+        }
+    });
+    //
+    // SOCKET: (re)connect forever until stopped.
+    //
+    let _user_data = user_data.clone();
+    thread::spawn(move || {
+        while _user_data.running.load(Ordering::Relaxed) {
+            let key = match user_stream.start() {
+                Ok(answer) => answer.listen_key,
+                Err(e) => {
+                    println!("> userdata: can't create listenKey: {:?}", e.0);
+                    thread::sleep(RECONNECT_DELAY);
+                    continue;
+                }
+            };
+            *listen_key.lock().unwrap() = key.clone();
+
+            let handler_data = _user_data.clone();
+            // binance-rs decides the handler's error type, we never return one.
+            #[allow(clippy::result_large_err)]
+            let mut web_socket = WebSockets::new(move |event: WebsocketEvent| {
+                handler_data.on_event(event);
+                Ok(())
+            });
+            match web_socket.connect_with_config(&key, &ws_config) {
+                Ok(_) => {
+                    println!("> userdata: {}", "connected.".green());
+                    _user_data.connected.store(true, Ordering::Relaxed);
+                    if let Err(e) = web_socket.event_loop(&_user_data.running) {
+                        println!("> userdata: {} {:?}", "disconnected:".red(), e.0);
+                    }
+                    _user_data.connected.store(false, Ordering::Relaxed);
+                    let _ = web_socket.disconnect();
+                },
+                Err(e) => println!("> userdata: can't connect: {:?}", e.0)
+            }
+            thread::sleep(RECONNECT_DELAY);
+        }
+        let key = listen_key.lock().unwrap().clone();
+        if !key.is_empty() { let _ = user_stream.close(&key); }
+    });
+    return Ok(user_data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::TcpListener, sync::mpsc};
+    use binance::api::Binance;
+    use serde_json::{json, Value};
+    use tungstenite::Message;
+    use crate::mock::{MockExchange, Scenario};
+
+    /// A user data stream on localhost, pushing every frame sent to it, closed on None.
+    fn stream_server() -> (String, mpsc::Sender<Option<Value>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}/ws", listener.local_addr().unwrap());
+        let (sender, frames) = mpsc::channel::<Option<Value>>();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = tungstenite::accept(stream).unwrap();
+            while let Ok(Some(frame)) = frames.recv() {
+                socket.write_message(Message::Text(frame.to_string())).unwrap();
+            }
+            let _ = socket.close(None);
+            let _ = socket.write_pending();
+        });
+        return (url, sender);
+    }
+
+    fn execution_report(status: &str, execution: &str, executed: &str, last: [&str;2], time: u64) -> Value {
+        return json!({ "e": "executionReport", "E": time, "s": "XTZBUSD", "c": "rg-test-0-XTZ-a", "S": "BUY", "o": "LIMIT", "f": "GTC",
+            "q": "100.00000000", "p": "1.01000000", "x": execution, "X": status, "r": "NONE", "i": 7,
+            "l": last[0], "z": executed, "L": last[1], "n": "0", "T": time, "t": 1, "m": false });
+    }
+
+    fn account_position(free: &str, time: u64) -> Value {
+        return json!({ "e": "outboundAccountPosition", "E": time, "u": time, "B": [{ "a": "BUSD", "f": free, "l": "0.00000000" }] });
+    }
+
+    fn wait_for(condition: impl Fn() -> bool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !condition() && Instant::now() < deadline { thread::sleep(Duration::from_millis(10)); }
+        return condition();
+    }
+
+    #[test]
+    fn stream_feeds_orders_and_balances() {
+        let mock = MockExchange::start(Scenario { balances: HashMap::from([(String::from("BUSD"), 1000.0)]), ..Default::default() }).unwrap();
+        let (ws_url, frames) = stream_server();
+        let mut config = Ini::new();
+        config.set("endpoint", "preset", Some(String::from("custom")));
+        config.set("endpoint", "rest_endpoint", Some(mock.url.clone()));
+        config.set("endpoint", "ws_endpoint", Some(ws_url));
+        let endpoint = Endpoint::load(&config).unwrap().binance_config();
+        let account: Account = Binance::new_with_config(Some("key".into()), Some("secret".into()), &endpoint);
+        let user_stream: UserStream = Binance::new_with_config(Some("key".into()), None, &endpoint);
+        let user_data = start(&config, &account, user_stream).unwrap();
+        assert!(wait_for(|| user_data.is_connected()));
+        // seeded from REST
+        assert_eq!(user_data.balance("BUSD"), Some(1000.0));
+
+        frames.send(Some(execution_report("NEW", "NEW", "0.00000000", ["0.00000000", "0.00000000"], 1000))).unwrap();
+        let new = user_data.wait_order(7, 0, Duration::from_secs(2)).unwrap();
+        assert_eq!((new.status.as_str(), new.executed_qty), ("NEW", 0.0));
+        // nothing newer in time : None, so REST is asked.
+        assert!(user_data.wait_order(7, new.seq, Duration::from_millis(100)).is_none());
+
+        frames.send(Some(execution_report("PARTIALLY_FILLED", "TRADE", "40.00000000", ["40.00000000", "1.00000000"], 2000))).unwrap();
+        let partial = user_data.wait_order(7, new.seq, Duration::from_secs(2)).unwrap();
+        assert_eq!((partial.status.as_str(), partial.executed_qty, partial.quote_qty), ("PARTIALLY_FILLED", 40.0, 40.0));
+        // balances from before the fill are stale, REST is asked instead.
+        assert_eq!(user_data.balance("BUSD"), None);
+        frames.send(Some(account_position("960.00000000", 2000))).unwrap();
+        assert_eq!(user_data.balance("BUSD"), Some(960.0));
+
+        frames.send(Some(execution_report("FILLED", "TRADE", "100.00000000", ["60.00000000", "1.01000000"], 3000))).unwrap();
+        let filled = user_data.wait_order(7, partial.seq, Duration::from_secs(2)).unwrap();
+        assert_eq!((filled.status.as_str(), filled.executed_qty), ("FILLED", 100.0));
+        assert!((filled.quote_qty - 100.6).abs() < 1e-9);
+
+        // disconnected : every read falls back to REST.
+        frames.send(None).unwrap();
+        assert!(wait_for(|| !user_data.is_connected()));
+        assert!(user_data.wait_order(7, 0, Duration::from_millis(100)).is_none());
+        assert_eq!(user_data.balance("BUSD"), None);
+        user_data.stop();
+    }
+
+    #[test]
+    fn disabled_store_falls_back_to_rest() {
+        let user_data = UserData::disabled();
+        user_data.on_balances(&[EventBalance { asset: String::from("BUSD"), free: String::from("5.0"), locked: String::from("0.0") }], 1);
+        assert!(!user_data.is_connected());
+        assert_eq!(user_data.balance("BUSD"), None);
+        assert!(user_data.wait_order(7, 0, Duration::from_millis(10)).is_none());
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use configparser::ini::Ini;
+
+use binance::api::*;
+use binance::account::Account;
+use binance::market::Market;
+
+use crate::analyzer::Side;
+use crate::credentials;
+use crate::endpoint::Endpoint;
+use crate::error::{Context, Error, Result};
+use crate::exchangeinfo::{self, QuantityInfo};
+
+/// Top of book of one symbol on one venue.
+#[derive(Clone, Copy, Debug)]
+pub struct Book {
+    pub bid: f64,
+    pub ask: f64,
+    pub bid_qty: f64,
+    pub ask_qty: f64
+}
+
+/// State of an order on a venue.
+#[derive(Clone, Debug)]
+pub struct VenueOrder {
+    pub order_id: u64,
+    pub status: String,
+    pub executed_qty: f64
+}
+
+/// What the bot needs from an exchange, so strategies can trade on more than one.
+pub trait Venue: Send + Sync {
+    fn name(&self) -> &str;
+    fn book(&self, symbol: &str) -> Result<Book>;
+    fn balance(&self, asset: &str) -> Result<f64>;
+    fn limit_order(&self, symbol: &str, side: Side, qty: f64, price: f64) -> Result<u64>;
+    fn order_status(&self, symbol: &str, order_id: u64) -> Result<VenueOrder>;
+    fn cancel(&self, symbol: &str, order_id: u64) -> Result<()>;
+    /// LOT_SIZE & PRICE_FILTER of this venue, rounding differs per venue.
+    fn quantity_info(&self, symbol: &str) -> Option<&QuantityInfo>;
+    /// taker fee in percent.
+    fn fee(&self) -> f64;
+}
+
+/// Any binance-compatible REST venue : binance, testnet or a local mock.
+pub struct BinanceVenue {
+    name: String,
+    account: Account,
+    market: Market,
+    quantity_info: HashMap<String, QuantityInfo>,
+    fee: f64
+}
+
+impl BinanceVenue {
+    /// Load venue from `[venue.NAME]` : preset / rest_endpoint ( as `[endpoint]` ), fee,
+    /// and keys as `[keys]` with env RAILGUN_NAME_API_KEY & RAILGUN_NAME_SECRET_KEY.
+    pub fn from_config(config: &Ini, name: &str, symbols: &[String]) -> Result<BinanceVenue> {
+        let section = format!("venue.{}", name);
+        let endpoint = Endpoint::from_section(config, &section)?;
+        let rest_endpoint = endpoint.rest.clone();
+        let (api_key, secret_key) = match credentials::resolve(config, &section, &format!("RAILGUN_{}", name.to_uppercase())) {
+            Ok(keys) => (Some(keys.api_key), Some(keys.secret_key)),
+            Err(e) => { println!("> venue {}: {}, public endpoints only.", name, e); (None, None) }
+        };
+        let fee = config.getfloat(&section, "fee").unwrap_or(None).unwrap_or(0.1);
+        let endpoint = endpoint.binance_config();
+        let quantity_info = exchangeinfo::fetch_quantity_from(&rest_endpoint, symbols)
+            .context(format!("no exchange info at {}", rest_endpoint))?;
+        println!("> venue {}: {} with {} symbols.", name, rest_endpoint, quantity_info.len());
+        return Ok(BinanceVenue {
+            name: name.to_string(),
+            account: Binance::new_with_config(api_key.clone(), secret_key.clone(), &endpoint),
+            market: Binance::new_with_config(api_key, secret_key, &endpoint),
+            quantity_info,
+            fee
+        });
+    }
+}
+
+impl Venue for BinanceVenue {
+    fn name(&self) -> &str { return &self.name; }
+
+    fn book(&self, symbol: &str) -> Result<Book> {
+        match self.market.get_book_ticker(symbol) {
+            Ok(ticker) => Ok(Book { bid: ticker.bid_price, ask: ticker.ask_price, bid_qty: ticker.bid_qty, ask_qty: ticker.ask_qty }),
+            Err(e) => Err(Error::from(e))
+        }
+    }
+
+    fn balance(&self, asset: &str) -> Result<f64> {
+        match self.account.get_balance(asset) {
+            Ok(balance) => Ok(balance.free.parse::<f64>().unwrap_or(0.0)),
+            Err(e) => Err(Error::from(e))
+        }
+    }
+
+    fn limit_order(&self, symbol: &str, side: Side, qty: f64, price: f64) -> Result<u64> {
+        let result = match side {
+            Side::Buy => self.account.limit_buy(symbol, qty, price),
+            Side::Sell => self.account.limit_sell(symbol, qty, price)
+        };
+        match result {
+            Ok(transaction) => Ok(transaction.order_id),
+            Err(e) => Err(Error::from(e))
+        }
+    }
+
+    fn order_status(&self, symbol: &str, order_id: u64) -> Result<VenueOrder> {
+        match self.account.order_status(symbol, order_id) {
+            Ok(order) => Ok(VenueOrder { order_id, status: order.status, executed_qty: order.executed_qty.parse::<f64>().unwrap_or(0.0) }),
+            Err(e) => Err(Error::from(e))
+        }
+    }
+
+    fn cancel(&self, symbol: &str, order_id: u64) -> Result<()> {
+        match self.account.cancel_order(symbol, order_id) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Error::from(e))
+        }
+    }
+
+    fn quantity_info(&self, symbol: &str) -> Option<&QuantityInfo> {
+        return self.quantity_info.get(symbol);
+    }
+
+    fn fee(&self) -> f64 { return self.fee; }
+}